@@ -15,7 +15,10 @@ use std::{
     fmt, mem,
     ops::Range,
     ptr,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
 };
 
 use parking_lot::{Condvar, Mutex, RwLock};
@@ -55,6 +58,8 @@ pub struct Device {
     pub(crate) context: ComPtr<d3d11::ID3D11DeviceContext>,
     features: hal::Features,
     memory_properties: MemoryProperties,
+    // Bytes allocated from each memory heap, shared with the physical device.
+    heap_usage: Arc<[AtomicU64; 2]>,
     pub(crate) internal: Arc<internal::Internal>,
 }
 
@@ -85,6 +90,7 @@ impl Device {
         features: hal::Features,
         downlevel: hal::DownlevelProperties,
         memory_properties: MemoryProperties,
+        heap_usage: Arc<[AtomicU64; 2]>,
         feature_level: u32,
     ) -> Self {
         Device {
@@ -94,6 +100,7 @@ impl Device {
             context,
             features,
             memory_properties,
+            heap_usage,
         }
     }
 
@@ -848,7 +855,8 @@ impl device::Device<Backend> for Device {
         mem_type: hal::MemoryTypeId,
         size: u64,
     ) -> Result<Memory, device::AllocationError> {
-        let properties = self.memory_properties.memory_types[mem_type.0].properties;
+        let memory_type = &self.memory_properties.memory_types[mem_type.0];
+        let properties = memory_type.properties;
         let host_ptr = if properties.contains(hal::memory::Properties::CPU_VISIBLE) {
             let mut data = vec![0u8; size as usize];
             let ptr = data.as_mut_ptr();
@@ -857,8 +865,10 @@ impl device::Device<Backend> for Device {
         } else {
             ptr::null_mut()
         };
+        self.heap_usage[memory_type.heap_index].fetch_add(size, Ordering::Relaxed);
         Ok(Memory {
             properties,
+            heap_index: memory_type.heap_index,
             size,
             host_ptr,
             local_buffers: Arc::new(RwLock::new(thunderdome::Arena::new())),
@@ -2199,7 +2209,61 @@ impl device::Device<Backend> for Device {
 
     fn create_semaphore(&self) -> Result<Semaphore, device::OutOfMemory> {
         // TODO:
-        Ok(Semaphore)
+        self.create_timeline_semaphore(0)
+    }
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<Semaphore, device::OutOfMemory> {
+        Ok(Semaphore {
+            value: Mutex::new(initial_value),
+            condvar: Condvar::new(),
+        })
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &Semaphore,
+    ) -> Result<u64, device::DeviceLost> {
+        Ok(*semaphore.value.lock())
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &Semaphore,
+        value: u64,
+    ) -> Result<(), device::OutOfMemory> {
+        semaphore.signal(value);
+        Ok(())
+    }
+
+    unsafe fn wait_for_semaphore(
+        &self,
+        semaphore: &Semaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, device::WaitError> {
+        use std::time::{Duration, Instant};
+
+        debug!(
+            "wait_for_semaphore {:?} to reach {} for {} ns",
+            semaphore, value, timeout_ns
+        );
+        let mut guard = semaphore.value.lock();
+        let total = Duration::from_nanos(timeout_ns);
+        let now = Instant::now();
+        while *guard < value {
+            let duration = match total.checked_sub(now.elapsed()) {
+                Some(dur) => dur,
+                None => return Ok(false),
+            };
+            let result = semaphore.condvar.wait_for(&mut guard, duration);
+            if result.timed_out() && *guard < value {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
     fn create_fence(&self, signalled: bool) -> Result<Fence, device::OutOfMemory> {
@@ -2279,6 +2343,7 @@ impl device::Device<Backend> for Device {
         for (_, (_range, mut internal)) in memory.local_buffers.write().drain() {
             internal.release_resources()
         }
+        self.heap_usage[memory.heap_index].fetch_sub(memory.size, Ordering::Relaxed);
     }
 
     unsafe fn create_query_pool(
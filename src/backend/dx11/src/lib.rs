@@ -33,7 +33,7 @@ use smallvec::SmallVec;
 use winapi::{
     shared::{
         dxgi::{IDXGIAdapter, IDXGIFactory, IDXGISwapChain},
-        dxgiformat,
+        dxgi1_4, dxgiformat,
         minwindef::{FALSE, HMODULE, UINT},
         windef::{HWND, RECT},
         winerror,
@@ -50,11 +50,14 @@ use parking_lot::{Condvar, Mutex, RwLock};
 use std::{
     borrow::Borrow,
     cell::RefCell,
-    fmt, mem,
+    fmt, iter, mem,
     ops::Range,
     os::raw::c_void,
     ptr,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
 };
 
 macro_rules! debug_scope {
@@ -631,6 +634,7 @@ impl hal::Instance<Backend> for Instance {
                 },
                 memory_properties,
                 format_properties,
+                heap_usage: Arc::default(),
             };
 
             info!("{:#?}", info);
@@ -669,6 +673,8 @@ pub struct PhysicalDevice {
     properties: hal::PhysicalDeviceProperties,
     memory_properties: adapter::MemoryProperties,
     format_properties: [format::Properties; format::NUM_FORMATS],
+    // Bytes allocated from each memory heap by the logical devices.
+    heap_usage: Arc<[AtomicU64; 2]>,
 }
 
 impl fmt::Debug for PhysicalDevice {
@@ -832,6 +838,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             requested_features,
             self.properties.downlevel,
             self.memory_properties.clone(),
+            Arc::clone(&self.heap_usage),
             feature_level,
         );
 
@@ -965,6 +972,42 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         self.memory_properties.clone()
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        let adapter3 = self.adapter.cast::<dxgi1_4::IDXGIAdapter3>().ok();
+        // Heaps are ordered as local memory, followed by non-local memory.
+        let segments = [
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_LOCAL,
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_NON_LOCAL,
+        ];
+        self.memory_properties
+            .memory_heaps
+            .iter()
+            .zip(segments.iter())
+            .zip(self.heap_usage.iter())
+            .map(|((heap, &segment), usage)| {
+                let mut mem_info: dxgi1_4::DXGI_QUERY_VIDEO_MEMORY_INFO = unsafe { mem::zeroed() };
+                let hr = match adapter3 {
+                    Some(ref adapter3) => unsafe {
+                        adapter3.QueryVideoMemoryInfo(0, segment, &mut mem_info)
+                    },
+                    // Without DXGI 1.4, only the allocations of the logical devices are known.
+                    None => winerror::E_NOINTERFACE,
+                };
+                if winerror::SUCCEEDED(hr) {
+                    adapter::MemoryHeapBudget {
+                        budget: mem_info.Budget,
+                        usage: mem_info.CurrentUsage,
+                    }
+                } else {
+                    adapter::MemoryHeapBudget {
+                        budget: heap.size,
+                        usage: usage.load(Ordering::Relaxed),
+                    }
+                }
+            })
+            .collect()
+    }
+
     fn features(&self) -> hal::Features {
         self.features
     }
@@ -1286,6 +1329,33 @@ impl queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut Fence>,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a Semaphore, pso::PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a Semaphore, u64)>,
+    {
+        // Command buffers are executed right away, so the values have to be
+        // reached already. Waiting for the host to signal them would block the
+        // submitting thread, which is why `TIMELINE_SEMAPHORE` is not exposed.
+        for (semaphore, _, value) in wait_semaphores {
+            if *semaphore.value.lock() < value {
+                error!("Timeline value {} is not signaled yet", value);
+            }
+        }
+
+        self.submit(command_buffers, iter::empty(), iter::empty(), fence);
+
+        for (semaphore, value) in signal_semaphores {
+            semaphore.signal(value);
+        }
+    }
+
     unsafe fn present(
         &mut self,
         surface: &mut Surface,
@@ -3314,6 +3384,7 @@ type LocalResourceArena<T> = thunderdome::Arena<(Range<u64>, T)>;
 // from all the dx11 resources we store in the struct.
 pub struct Memory {
     properties: memory::Properties,
+    heap_index: usize,
     size: u64,
 
     // pointer to staging memory, if it's HOST_VISIBLE
@@ -4329,8 +4400,23 @@ pub struct RawFence {
 
 pub type Fence = Arc<RawFence>;
 
+/// Semaphore, with the counter of timeline semaphores being advanced at submission
+/// time, the same way fences are signaled.
 #[derive(Debug)]
-pub struct Semaphore;
+pub struct Semaphore {
+    value: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl Semaphore {
+    fn signal(&self, value: u64) {
+        let mut guard = self.value.lock();
+        if *guard < value {
+            *guard = value;
+        }
+        self.condvar.notify_all();
+    }
+}
 #[derive(Debug)]
 pub struct QueryPool;
 
@@ -1,4 +1,10 @@
-use std::{collections::hash_map::Entry, ffi, iter, mem, ops::Range, ptr, slice, sync::Arc};
+use std::{
+    collections::hash_map::Entry,
+    ffi, iter, mem,
+    ops::Range,
+    ptr, slice,
+    sync::{atomic::Ordering, Arc},
+};
 
 use range_alloc::RangeAllocator;
 use smallvec::SmallVec;
@@ -1028,6 +1034,64 @@ impl Device {
         Ok(handle)
     }
 
+    /// Wait for the fences to reach their associated values.
+    unsafe fn wait_for_raw_fences<I>(
+        &self,
+        fences: I,
+        wait: d::WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError>
+    where
+        I: Iterator<Item = (native::Fence, u64)>,
+    {
+        let mut count = 0;
+        let mut events = self.events.lock();
+
+        for (fence, value) in fences {
+            if count == events.len() {
+                events.push(native::Event::create(false, false));
+            }
+            let event = events[count];
+            synchapi::ResetEvent(event.0);
+            assert_eq!(winerror::S_OK, fence.set_event_on_completion(event, value));
+            count += 1;
+        }
+
+        let all = match wait {
+            d::WaitFor::Any => FALSE,
+            d::WaitFor::All => TRUE,
+        };
+
+        let hr = {
+            // This block handles overflow when converting to u32 and always rounds up
+            // The Vulkan specification allows to wait more than specified
+            let timeout_ms = {
+                if timeout_ns > (<u32>::max_value() as u64) * 1_000_000 {
+                    <u32>::max_value()
+                } else {
+                    ((timeout_ns + 999_999) / 1_000_000) as u32
+                }
+            };
+
+            synchapi::WaitForMultipleObjects(
+                count as u32,
+                events.as_ptr() as *const _,
+                all,
+                timeout_ms,
+            )
+        };
+
+        const WAIT_OBJECT_LAST: u32 = winbase::WAIT_OBJECT_0 + winnt::MAXIMUM_WAIT_OBJECTS;
+        const WAIT_ABANDONED_LAST: u32 = winbase::WAIT_ABANDONED_0 + winnt::MAXIMUM_WAIT_OBJECTS;
+        match hr {
+            winbase::WAIT_OBJECT_0..=WAIT_OBJECT_LAST => Ok(true),
+            winbase::WAIT_ABANDONED_0..=WAIT_ABANDONED_LAST => Ok(true), //TODO?
+            winbase::WAIT_FAILED => Err(d::WaitError::DeviceLost(d::DeviceLost)),
+            winerror::WAIT_TIMEOUT => Ok(false),
+            _ => panic!("Unexpected wait status 0x{:X}", hr),
+        }
+    }
+
     pub(crate) fn create_raw_fence(&self, signalled: bool) -> native::Fence {
         let mut handle = native::Fence::null();
         assert_eq!(winerror::S_OK, unsafe {
@@ -1343,6 +1407,7 @@ impl d::Device<B> for Device {
             None
         };
 
+        self.heap_usage[self.memory_heap_indices[mem_type]].fetch_add(size, Ordering::Relaxed);
         Ok(r::Memory {
             heap,
             type_id: mem_type,
@@ -3306,6 +3371,50 @@ impl d::Device<B> for Device {
         Ok(r::Semaphore { raw: fence.raw })
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<r::Semaphore, d::OutOfMemory> {
+        let semaphore = self.create_semaphore()?;
+        assert_eq!(winerror::S_OK, semaphore.raw.signal(initial_value));
+        Ok(semaphore)
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &r::Semaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        match semaphore.raw.GetCompletedValue() {
+            u64::MAX => Err(d::DeviceLost),
+            value => Ok(value),
+        }
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &r::Semaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        assert_eq!(winerror::S_OK, semaphore.raw.signal(value));
+        Ok(())
+    }
+
+    unsafe fn wait_for_semaphores<'a, I>(
+        &self,
+        semaphores: I,
+        wait: d::WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError>
+    where
+        I: Iterator<Item = (&'a r::Semaphore, u64)>,
+    {
+        self.wait_for_raw_fences(
+            semaphores.map(|(semaphore, value)| (semaphore.raw, value)),
+            wait,
+            timeout_ns,
+        )
+    }
+
     fn create_fence(&self, signalled: bool) -> Result<r::Fence, d::OutOfMemory> {
         Ok(r::Fence {
             raw: self.create_raw_fence(signalled),
@@ -3326,52 +3435,7 @@ impl d::Device<B> for Device {
     where
         I: Iterator<Item = &'a r::Fence>,
     {
-        let mut count = 0;
-        let mut events = self.events.lock();
-
-        for fence in fences {
-            if count == events.len() {
-                events.push(native::Event::create(false, false));
-            }
-            let event = events[count];
-            synchapi::ResetEvent(event.0);
-            assert_eq!(winerror::S_OK, fence.raw.set_event_on_completion(event, 1));
-            count += 1;
-        }
-
-        let all = match wait {
-            d::WaitFor::Any => FALSE,
-            d::WaitFor::All => TRUE,
-        };
-
-        let hr = {
-            // This block handles overflow when converting to u32 and always rounds up
-            // The Vulkan specification allows to wait more than specified
-            let timeout_ms = {
-                if timeout_ns > (<u32>::max_value() as u64) * 1_000_000 {
-                    <u32>::max_value()
-                } else {
-                    ((timeout_ns + 999_999) / 1_000_000) as u32
-                }
-            };
-
-            synchapi::WaitForMultipleObjects(
-                count as u32,
-                events.as_ptr() as *const _,
-                all,
-                timeout_ms,
-            )
-        };
-
-        const WAIT_OBJECT_LAST: u32 = winbase::WAIT_OBJECT_0 + winnt::MAXIMUM_WAIT_OBJECTS;
-        const WAIT_ABANDONED_LAST: u32 = winbase::WAIT_ABANDONED_0 + winnt::MAXIMUM_WAIT_OBJECTS;
-        match hr {
-            winbase::WAIT_OBJECT_0..=WAIT_OBJECT_LAST => Ok(true),
-            winbase::WAIT_ABANDONED_0..=WAIT_ABANDONED_LAST => Ok(true), //TODO?
-            winbase::WAIT_FAILED => Err(d::WaitError::DeviceLost(d::DeviceLost)),
-            winerror::WAIT_TIMEOUT => Ok(false),
-            _ => panic!("Unexpected wait status 0x{:X}", hr),
-        }
+        self.wait_for_raw_fences(fences.map(|fence| (fence.raw, 1)), wait, timeout_ns)
     }
 
     unsafe fn get_fence_status(&self, fence: &r::Fence) -> Result<bool, d::DeviceLost> {
@@ -3403,6 +3467,8 @@ impl d::Device<B> for Device {
         if let Some(buffer) = memory.resource {
             buffer.destroy();
         }
+        self.heap_usage[self.memory_heap_indices[memory.type_id]]
+            .fetch_sub(memory.size, Ordering::Relaxed);
     }
 
     unsafe fn create_query_pool(
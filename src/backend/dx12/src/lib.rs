@@ -52,10 +52,14 @@ use std::{
     borrow::{Borrow, BorrowMut},
     ffi::OsString,
     fmt,
+    iter,
     mem,
     os::windows::ffi::OsStringExt,
     //TODO: use parking_lot
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use self::descriptors_cpu::DescriptorCpuPool;
@@ -226,6 +230,8 @@ pub struct PhysicalDevice {
     is_open: Arc<Mutex<bool>>,
     adapter: native::WeakPtr<dxgi1_2::IDXGIAdapter2>,
     library: Arc<native::D3D12Lib>,
+    // Bytes allocated from each memory heap by the logical devices.
+    heap_usage: Arc<[AtomicU64; 2]>,
 }
 
 impl fmt::Debug for PhysicalDevice {
@@ -442,6 +448,48 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         self.memory_properties.clone()
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        // Without DXGI 1.4, the whole heap is the budget,
+        // and only the allocations of the logical devices are known.
+        let estimate = |heap_index: usize| adapter::MemoryHeapBudget {
+            budget: self.memory_properties.memory_heaps[heap_index].size,
+            usage: self.heap_usage[heap_index].load(Ordering::Relaxed),
+        };
+        let num_heaps = self.memory_properties.memory_heaps.len();
+
+        let (adapter3, hr) = unsafe { self.adapter.cast::<dxgi1_4::IDXGIAdapter3>() };
+        if !winerror::SUCCEEDED(hr) {
+            return (0..num_heaps).map(estimate).collect();
+        }
+
+        // Heaps are ordered as local memory, followed by non-local memory on NUMA.
+        let segments = [
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_LOCAL,
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_NON_LOCAL,
+        ];
+        let budgets = segments[..num_heaps]
+            .iter()
+            .enumerate()
+            .map(|(heap_index, &segment)| unsafe {
+                let mut mem_info: dxgi1_4::DXGI_QUERY_VIDEO_MEMORY_INFO = mem::zeroed();
+                let hr = adapter3.QueryVideoMemoryInfo(0, segment, &mut mem_info);
+                if winerror::SUCCEEDED(hr) {
+                    adapter::MemoryHeapBudget {
+                        budget: mem_info.Budget,
+                        usage: mem_info.CurrentUsage,
+                    }
+                } else {
+                    estimate(heap_index)
+                }
+            })
+            .collect();
+
+        unsafe {
+            adapter3.destroy();
+        }
+        budgets
+    }
+
     fn features(&self) -> Features {
         self.features
     }
@@ -518,6 +566,36 @@ impl q::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut resource::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a command::CommandBuffer>,
+        Iw: Iterator<Item = (&'a resource::Semaphore, PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a resource::Semaphore, u64)>,
+    {
+        // Semaphores are backed by fences, which are waited on and signaled
+        // by the queue with the timeline values directly.
+        for (semaphore, _, value) in wait_semaphores {
+            assert_eq!(
+                winerror::S_OK,
+                self.raw.Wait(semaphore.raw.as_mut_ptr(), value)
+            );
+        }
+
+        self.submit(command_buffers, iter::empty(), iter::empty(), fence);
+
+        for (semaphore, value) in signal_semaphores {
+            assert_eq!(
+                winerror::S_OK,
+                self.raw.Signal(semaphore.raw.as_mut_ptr(), value)
+            );
+        }
+    }
+
     unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
         &mut self,
         _wait_semaphores: Iw,
@@ -774,6 +852,10 @@ pub struct Device {
     // Indicates that there is currently an active device.
     open: Arc<Mutex<bool>>,
     library: Arc<native::D3D12Lib>,
+    // Heap index of each memory type.
+    memory_heap_indices: Vec<usize>,
+    // Bytes allocated from each memory heap, shared with the physical device.
+    heap_usage: Arc<[AtomicU64; 2]>,
 }
 
 impl fmt::Debug for Device {
@@ -860,6 +942,13 @@ impl Device {
             present_queue,
             queues: Vec::new(),
             open: Arc::clone(&physical_device.is_open),
+            memory_heap_indices: physical_device
+                .memory_properties
+                .memory_types
+                .iter()
+                .map(|ty| ty.heap_index)
+                .collect(),
+            heap_usage: Arc::clone(&physical_device.heap_usage),
         }
     }
 
@@ -1353,6 +1442,7 @@ impl hal::Instance<Backend> for Instance {
                     Features::UNIFORM_BUFFER_DESCRIPTOR_INDEXING |
                     Features::UNSIZED_DESCRIPTOR_ARRAY |
                     Features::DRAW_INDIRECT_COUNT |
+                    Features::TIMELINE_SEMAPHORE |
                     tiled_resource_features |
                     conservative_faster_features,
                 properties: PhysicalDeviceProperties {
@@ -1465,6 +1555,7 @@ impl hal::Instance<Backend> for Instance {
                     memory_heaps,
                 },
                 is_open: Arc::new(Mutex::new(false)),
+                heap_usage: Arc::default(),
             };
 
             let queue_families = QUEUE_FAMILIES.to_vec();
@@ -13,7 +13,11 @@ use crate::{
 use hal::{adapter, command, device, format, pass, pool, pso, query, queue, window};
 use log::debug;
 
-use std::{borrow::Borrow, ops::Range};
+use std::{
+    borrow::Borrow,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 mod buffer;
 mod descriptor;
@@ -57,7 +61,7 @@ impl hal::Backend for Backend {
     type DescriptorSet = DescriptorSet;
 
     type Fence = ();
-    type Semaphore = ();
+    type Semaphore = Semaphore;
     type Event = ();
     type QueryPool = ();
 }
@@ -141,8 +145,19 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         }
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        self.memory_properties()
+            .memory_heaps
+            .iter()
+            .map(|heap| adapter::MemoryHeapBudget {
+                budget: heap.size,
+                usage: 0,
+            })
+            .collect()
+    }
+
     fn features(&self) -> hal::Features {
-        hal::Features::empty()
+        hal::Features::TIMELINE_SEMAPHORE
     }
 
     fn properties(&self) -> hal::PhysicalDeviceProperties {
@@ -167,11 +182,27 @@ impl queue::Queue<Backend> for Queue {
     {
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        _: Ic,
+        _: Iw,
+        signal_semaphores: Is,
+        _: Option<&mut ()>,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Is: Iterator<Item = (&'a Semaphore, u64)>,
+    {
+        // Nothing is executed, so the signals happen right away.
+        for (semaphore, value) in signal_semaphores {
+            semaphore.0.store(value, Ordering::Release);
+        }
+    }
+
     unsafe fn present(
         &mut self,
         _surface: &mut Surface,
         _image: SwapchainImage,
-        _wait_semaphore: Option<&mut ()>,
+        _wait_semaphore: Option<&mut Semaphore>,
     ) -> Result<Option<window::Suboptimal>, window::PresentError> {
         Ok(None)
     }
@@ -185,6 +216,10 @@ impl queue::Queue<Backend> for Queue {
     }
 }
 
+/// Dummy semaphore, holding the counter value of timeline semaphores.
+#[derive(Debug)]
+pub struct Semaphore(AtomicU64);
+
 /// Dummy device doing nothing.
 #[derive(Debug)]
 pub struct Device;
@@ -406,7 +441,30 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    fn create_semaphore(&self) -> Result<(), device::OutOfMemory> {
+    fn create_semaphore(&self) -> Result<Semaphore, device::OutOfMemory> {
+        Ok(Semaphore(AtomicU64::new(0)))
+    }
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<Semaphore, device::OutOfMemory> {
+        Ok(Semaphore(AtomicU64::new(initial_value)))
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &Semaphore,
+    ) -> Result<u64, device::DeviceLost> {
+        Ok(semaphore.0.load(Ordering::Acquire))
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &Semaphore,
+        value: u64,
+    ) -> Result<(), device::OutOfMemory> {
+        semaphore.0.store(value, Ordering::Release);
         Ok(())
     }
 
@@ -512,7 +570,7 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_fence(&self, _: ()) {}
 
-    unsafe fn destroy_semaphore(&self, _: ()) {}
+    unsafe fn destroy_semaphore(&self, _: Semaphore) {}
 
     unsafe fn destroy_event(&self, _: ()) {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
@@ -534,7 +592,7 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn set_semaphore_name(&self, _: &mut (), _: &str) {
+    unsafe fn set_semaphore_name(&self, _: &mut Semaphore, _: &str) {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
@@ -1073,3 +1131,45 @@ impl hal::Instance<Backend> for Instance {
 
     unsafe fn destroy_surface(&self, _surface: Surface) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::{device::Device as _, queue::Queue as _};
+    use std::iter;
+
+    #[test]
+    fn timeline_semaphore_counter() {
+        let device = Device;
+        let mut queue = Queue;
+        let semaphore = device.create_timeline_semaphore(1).unwrap();
+        let other = device.create_timeline_semaphore(0).unwrap();
+        unsafe {
+            assert_eq!(device.get_semaphore_counter_value(&semaphore), Ok(1));
+
+            device.signal_semaphore(&semaphore, 5).unwrap();
+            assert_eq!(device.get_semaphore_counter_value(&semaphore), Ok(5));
+
+            queue.submit_timeline(
+                iter::empty(),
+                iter::once((&semaphore, pso::PipelineStage::TOP_OF_PIPE, 5)),
+                iter::once((&semaphore, 7)),
+                None,
+            );
+            assert_eq!(device.get_semaphore_counter_value(&semaphore), Ok(7));
+            assert_eq!(device.get_semaphore_counter_value(&other), Ok(0));
+
+            assert_eq!(device.wait_for_semaphore(&semaphore, 7, 0), Ok(true));
+            assert_eq!(device.wait_for_semaphore(&semaphore, 8, 0), Ok(false));
+            let values = [(&semaphore, 7), (&other, 1)];
+            assert_eq!(
+                device.wait_for_semaphores(values.iter().cloned(), device::WaitFor::Any, 0),
+                Ok(true)
+            );
+            assert_eq!(
+                device.wait_for_semaphores(values.iter().cloned(), device::WaitFor::All, 0),
+                Ok(false)
+            );
+        }
+    }
+}
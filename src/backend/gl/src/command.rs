@@ -1,10 +1,12 @@
 #![allow(missing_docs)]
 
-use crate::{GlContext, MAX_COLOR_ATTACHMENTS, MAX_SAMPLERS, MAX_TEXTURE_SLOTS};
+use crate::{
+    GlContext, MAX_COLOR_ATTACHMENTS, MAX_PUSH_CONSTANTS_SIZE, MAX_SAMPLERS, MAX_TEXTURE_SLOTS,
+};
 
 use hal::{
     self, buffer, command,
-    format::{Aspects, ChannelType},
+    format::{Aspects, ChannelType, FormatDesc},
     image, memory, pass, pso, query,
 };
 
@@ -17,7 +19,12 @@ use crate::{
 use arrayvec::ArrayVec;
 use parking_lot::Mutex;
 
-use std::{iter, mem, ops::Range, slice, sync::Arc};
+use std::{
+    iter, mem,
+    ops::Range,
+    slice,
+    sync::{Arc, Weak},
+};
 
 // Command buffer implementation details:
 //
@@ -73,16 +80,46 @@ pub enum Command {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    /// Draw with the parameters read from the bound indirect buffer.
+    /// Indexed if the index type is set.
+    DrawIndirect {
+        primitive: u32,
+        index_type: Option<u32>,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
+    },
+    /// Same as `DrawIndirect`, with the number of draws read from the count buffer.
+    DrawIndirectCount {
+        primitive: u32,
+        index_type: Option<u32>,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        count_buffer: n::RawBuffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: hal::DrawCount,
+        stride: buffer::Stride,
+    },
     BindIndexBuffer(n::RawBuffer),
     //BindVertexBuffers(BufferSlice),
-    BindUniform {
-        uniform: n::UniformDesc,
-        buffer: BufferSlice,
-    },
+    /// Update the push constants at an offset.
+    PushConstants(u32, BufferSlice),
     BindRasterizer {
         rasterizer: pso::Rasterizer,
     },
     BindDepth(Option<pso::Comparison>),
+    /// Enable the stencil test with the operations of each face, or disable it.
+    BindStencil(Option<pso::Sided<pso::StencilFace>>),
+    /// Set the comparison of a face with `glStencilFuncSeparate`.
+    SetStencilFunc {
+        face: u32,
+        fun: pso::Comparison,
+        reference: pso::StencilValue,
+        read_mask: pso::StencilValue,
+    },
+    SetLineWidth(f32),
+    SetDepthBias(pso::DepthBias),
     SetViewports {
         first_viewport: u32,
         viewport_ptr: BufferSlice,
@@ -101,13 +138,34 @@ pub enum Command {
     ClearBufferDepthStencil(Option<pso::DepthValue>, Option<pso::StencilValue>),
     /// Clear the currently bound texture with the given color.
     ClearTexture([f32; 4]),
+    /// Restrict the following clears to the rectangle,
+    /// or lift the restriction and restore the scissor.
+    SetClearRect(Option<pso::Rect>),
     FillBuffer(n::RawBuffer, Range<buffer::Offset>, u32),
+    /// Write the data into the buffer at the offset.
+    UpdateBuffer(n::RawBuffer, buffer::Offset, BufferSlice),
+    /// Blit between single level and layer views, attached to temporary framebuffers.
+    BlitFramebuffer {
+        src: n::ImageView,
+        dst: n::ImageView,
+        src_bounds: Range<image::Offset>,
+        dst_bounds: Range<image::Offset>,
+        mask: u32,
+        filter: u32,
+    },
     BindFramebuffer {
         target: FrameBufferTarget,
         framebuffer: n::RawFramebuffer,
         colors: ArrayVec<[n::ImageView; MAX_COLOR_ATTACHMENTS]>,
         depth_stencil: Option<n::ImageView>,
     },
+    /// Detach an attachment point of the bound framebuffer.
+    UnbindAttachment(FrameBufferTarget, u32),
+    /// Discard the contents of attachment points of a framebuffer.
+    InvalidateFramebuffer {
+        framebuffer: n::RawFramebuffer,
+        attachments: ArrayVec<[u32; MAX_COLOR_ATTACHMENTS + 2]>,
+    },
     SetDrawColorBuffers(ArrayVec<[u8; MAX_COLOR_ATTACHMENTS]>),
     SetPatchSize(i32),
     BindProgram(<GlContext as glow::HasContext>::Program),
@@ -122,38 +180,29 @@ pub enum Command {
         dst_target: u32,
         data: command::BufferCopy,
     },
-    CopyBufferToTexture {
+    CopyBufferToImage {
         src_buffer: n::RawBuffer,
-        dst_texture: n::Texture,
-        texture_target: n::TextureTarget,
-        texture_format: n::TextureFormat,
-        pixel_type: n::DataType,
+        dst_image: n::ImageType,
+        format_desc: FormatDesc,
         data: command::BufferImageCopy,
     },
-    CopyBufferToRenderbuffer(n::RawBuffer, n::Renderbuffer, command::BufferImageCopy),
-    CopyTextureToBuffer {
-        src_texture: n::Texture,
-        texture_target: n::TextureTarget,
-        texture_format: n::TextureFormat,
-        pixel_type: n::DataType,
+    CopyImageToBuffer {
+        src_image: n::ImageType,
+        format_desc: FormatDesc,
         dst_buffer: n::RawBuffer,
         data: command::BufferImageCopy,
     },
-    CopyRenderbufferToBuffer(n::Renderbuffer, n::RawBuffer, command::BufferImageCopy),
-    CopyImageToTexture(
-        n::ImageType,
-        n::Texture,
-        n::TextureTarget,
-        command::ImageCopy,
-    ),
-    CopyImageToRenderbuffer {
+    /// Copy with `glCopyImageSubData` between textures if supported,
+    /// or blit slice by slice through temporary framebuffers.
+    CopyImage {
         src_image: n::ImageType,
-        dst_renderbuffer: n::Renderbuffer,
-        dst_format: n::TextureFormat,
+        dst_image: n::ImageType,
         data: command::ImageCopy,
     },
     BindBufferRange(u32, u32, n::RawBuffer, i32, i32),
     BindTexture(u32, n::Texture, n::TextureTarget),
+    /// Bind a buffer texture to an image unit, with the access and internal format.
+    BindImageTexture(u32, n::Texture, u32, u32),
     BindSampler(u32, n::Sampler),
     SetTextureSamplerSettings(u32, n::TextureTarget, image::SamplerDesc),
     SetColorMask(Option<DrawBuffer>, pso::ColorMask),
@@ -161,6 +210,38 @@ pub enum Command {
     SetStencilMask(pso::StencilValue),
     SetStencilMaskSeparate(pso::Sided<pso::StencilValue>),
     MemoryBarrier(u32),
+    /// Signal the event once the previous commands are complete.
+    SetEvent(n::Event),
+    ResetEvent(n::Event),
+    BeginQuery(n::Query, u32),
+    EndQuery(u32),
+    /// Write a timestamp into the query object.
+    QueryCounter(n::Query),
+    /// Debug marker and group labels, as UTF-8 strings.
+    InsertDebugMarker(BufferSlice),
+    PushDebugGroup(BufferSlice),
+    PopDebugGroup,
+    /// Execute the commands of a secondary command buffer in place.
+    ExecuteCommands(SecondaryCommands),
+    CopyQueryPoolResults {
+        /// GL query objects of the queries, each made of `counters` objects.
+        objects: Vec<n::Query>,
+        counters: usize,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
+    },
+}
+
+/// Reference to the commands of a secondary command buffer, which stay in
+/// the memory of its pool.
+#[derive(Debug)]
+pub struct SecondaryCommands {
+    // Weak, since the commands may be stored in the same memory.
+    pub(crate) memory: Weak<Mutex<BufferMemory>>,
+    pub(crate) buf: BufferSlice,
+    pub(crate) id: u64,
 }
 
 pub type FrameBufferTarget = u32;
@@ -168,15 +249,23 @@ pub type DrawBuffer = u32;
 
 #[derive(Clone, Debug)]
 struct AttachmentInfo {
-    //subpass_id: pass::SubpassId,
-    color_index: Option<u8>,
+    /// Image view bound by the framebuffer, unknown to render pass continuations.
+    view: Option<n::ImageView>,
     clear_value: command::ClearValue,
 }
 
+impl AttachmentInfo {
+    fn view(&self) -> n::ImageView {
+        self.view.clone().expect("Missing attachment view")
+    }
+}
+
 #[derive(Debug)]
 pub struct RenderPassCache {
     render_pass: n::RenderPass,
     attachments: Vec<AttachmentInfo>,
+    /// Framebuffer and render area, unknown to render pass continuations.
+    framebuffer: Option<(n::RawFramebuffer, pso::Rect)>,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -194,6 +283,10 @@ struct Cache {
     index_type_range: Option<(hal::IndexType, Range<buffer::Offset>)>,
     // Stencil reference values (front, back).
     stencil_ref: Option<(pso::StencilValue, pso::StencilValue)>,
+    // Stencil read masks.
+    stencil_read_mask: Option<pso::Sided<pso::StencilValue>>,
+    // Stencil test operations, set by the current pipeline.
+    stencil_faces: Option<pso::Sided<pso::StencilFace>>,
     // Blend color.
     blend_color: Option<pso::ColorValue>,
     ///
@@ -213,8 +306,6 @@ struct Cache {
     vertex_buffer_descs: Vec<Option<pso::VertexBufferDesc>>,
     // Active attributes.
     attributes: Vec<n::AttributeDesc>,
-    // Active uniforms
-    uniforms: Vec<n::UniformDesc>,
     // Current depth mask
     depth_mask: Option<bool>,
     // Current stencil mask
@@ -231,6 +322,8 @@ impl Cache {
             primitive: None,
             index_type_range: None,
             stencil_ref: None,
+            stencil_read_mask: None,
+            stencil_faces: None,
             blend_color: None,
             framebuffer: None,
             error_state: false,
@@ -240,7 +333,6 @@ impl Cache {
             vertex_buffers: Vec::new(),
             vertex_buffer_descs: Vec::new(),
             attributes: Vec::new(),
-            uniforms: Vec::new(),
             depth_mask: None,
             stencil_mask: None,
             samplers: (0..MAX_SAMPLERS).map(|_| None).collect(),
@@ -273,6 +365,71 @@ pub struct CommandStorage {
     pub(crate) id: u64,
 }
 
+/// Returns a view of a single level and layer of the image,
+/// to be attached to a framebuffer.
+fn attachment_view(
+    image: &n::Image,
+    aspects: Aspects,
+    level: image::Level,
+    layer: image::Layer,
+) -> n::ImageView {
+    image.object_type.slice_view(aspects, level, layer)
+}
+
+/// Returns the `glBlitFramebuffer` mask of the aspects.
+pub(crate) fn blit_mask(aspects: Aspects) -> u32 {
+    let mut mask = 0;
+    if aspects.contains(Aspects::COLOR) {
+        mask |= glow::COLOR_BUFFER_BIT;
+    }
+    if aspects.contains(Aspects::DEPTH) {
+        mask |= glow::DEPTH_BUFFER_BIT;
+    }
+    if aspects.contains(Aspects::STENCIL) {
+        mask |= glow::STENCIL_BUFFER_BIT;
+    }
+    mask
+}
+
+/// Returns the `glMemoryBarrier` bits needed for the barriers.
+fn barrier_mask<'a, T>(barriers: T) -> u32
+where
+    T: Iterator<Item = memory::Barrier<'a, Backend>>,
+{
+    //TODO: this needs to be much more detailed. Problem is that the affected
+    // resources by a barrier have to be bound to specific slots, so, for example,
+    // doing a `set_graphics_pipeline` followed by `pipeline_barrier` may need
+    // the vertex bindings to be reinstated.
+    let mut mask = 0;
+
+    for barrier in barriers {
+        match barrier {
+            memory::Barrier::AllBuffers(access) => {
+                if access.start.contains(buffer::Access::SHADER_WRITE) {
+                    mask |= glow::SHADER_STORAGE_BARRIER_BIT;
+                }
+            }
+            memory::Barrier::Buffer { states, .. } => {
+                if states.start.contains(buffer::Access::SHADER_WRITE) {
+                    mask |= glow::SHADER_STORAGE_BARRIER_BIT;
+                }
+            }
+            memory::Barrier::AllImages(access) => {
+                if access.start.contains(image::Access::SHADER_WRITE) {
+                    mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+                }
+            }
+            memory::Barrier::Image { states, .. } => {
+                if states.start.0.contains(image::Access::SHADER_WRITE) {
+                    mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+                }
+            }
+        }
+    }
+
+    mask
+}
+
 impl CommandStorage {
     fn push_cmd(&mut self, cmd: Command) {
         let mut memory = self
@@ -519,115 +676,309 @@ impl CommandBuffer {
         }
     }
 
+    /// Returns the GL index type for an indexed indirect draw, which can't take
+    /// the offset of the index buffer into account. Draws with an index range
+    /// starting past the beginning of the memory are rejected.
+    fn indirect_index_type(&mut self, name: &str) -> Option<u32> {
+        match self.cache.index_type_range {
+            // The first index of an indirect draw is read from the GPU,
+            // so the offset can't be folded into it.
+            Some((_, ref buffer_range)) if buffer_range.start != 0 => {
+                log::error!("Index buffer offsets are not supported by `{}`", name);
+                self.cache.error_state = true;
+                None
+            }
+            Some((index_type, _)) => Some(match index_type {
+                hal::IndexType::U16 => glow::UNSIGNED_SHORT,
+                hal::IndexType::U32 => glow::UNSIGNED_INT,
+            }),
+            None => {
+                log::warn!(
+                    "No index type bound. An index buffer needs to be bound before calling `{}`.",
+                    name
+                );
+                self.cache.error_state = true;
+                None
+            }
+        }
+    }
+
     fn begin_subpass(&mut self) {
         let state = self.pass_cache.as_ref().unwrap();
-        let subpass = &state.render_pass.subpasses[self.cur_subpass as usize];
-
-        // See `begin_renderpass_cache` for clearing strategy
-
-        // Bind draw buffers for mapping color output locations with
-        // framebuffer attachments.
-        let attachment_indices = subpass
+        let render_pass = &state.render_pass;
+        let subpass = &render_pass.subpasses[self.cur_subpass as usize];
+        let (framebuffer, _) = state
+            .framebuffer
+            .expect("Missing framebuffer of a render pass");
+
+        // Attach the targets of the sub-pass in the order of its color outputs,
+        // and detach the ones left by the other sub-passes. This keeps input
+        // attachments, which are bound as textures, out of the framebuffer.
+        // Writes of the previous sub-passes are then visible to their texture
+        // fetches without further barriers.
+        let colors = subpass
             .color_attachments
             .iter()
-            .map(|&index| state.attachments[index].color_index.unwrap())
+            .map(|&index| state.attachments[index].view())
             .collect();
+        let depth_stencil = subpass
+            .depth_stencil
+            .map(|index| state.attachments[index].view());
+        self.data.push_cmd(Command::BindFramebuffer {
+            target: glow::DRAW_FRAMEBUFFER,
+            framebuffer,
+            colors,
+            depth_stencil,
+        });
+        let max_colors = render_pass
+            .subpasses
+            .iter()
+            .map(|sp| sp.color_attachments.len())
+            .max()
+            .unwrap_or(0);
+        for i in subpass.color_attachments.len()..max_colors {
+            self.data.push_cmd(Command::UnbindAttachment(
+                glow::DRAW_FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0 + i as u32,
+            ));
+        }
+        if subpass.depth_stencil.is_none()
+            && render_pass
+                .subpasses
+                .iter()
+                .any(|sp| sp.depth_stencil.is_some())
+        {
+            self.data.push_cmd(Command::UnbindAttachment(
+                glow::DRAW_FRAMEBUFFER,
+                glow::DEPTH_STENCIL_ATTACHMENT,
+            ));
+        }
+
+        // Bind draw buffers for mapping color output locations with
+        // framebuffer attachments.
+        let draw_buffers = (0..subpass.color_attachments.len() as u8).collect();
         self.data
-            .push_cmd(Command::SetDrawColorBuffers(attachment_indices));
+            .push_cmd(Command::SetDrawColorBuffers(draw_buffers));
         self.data
             .push_cmd(Command::SetColorMask(None, pso::ColorMask::ALL));
 
-        for (rat, info) in state
-            .render_pass
-            .attachments
-            .iter()
-            .zip(state.attachments.iter())
-        {
-            //TODO: check if the subpass is one where we need this clear
+        // Attachments are loaded by the first sub-pass using them.
+        let cur_subpass = self.cur_subpass;
+        let is_first_use = |index| {
+            render_pass
+                .subpass_range(index)
+                .map_or(false, |range| range.start == cur_subpass)
+        };
+
+        for (draw_buffer, &index) in subpass.color_attachments.iter().enumerate() {
+            let rat = &render_pass.attachments[index];
+            if rat.ops.load != pass::AttachmentLoadOp::Clear || !is_first_use(index) {
+                continue;
+            }
 
             // View format needs to be known at this point.
             // All attachments specified in the renderpass must have a valid,
             // matching image view bound in the framebuffer.
+            let channel = rat.format.unwrap().base_format().1;
+            let draw_buffer = draw_buffer as DrawBuffer;
+            let clear_value = state.attachments[index].clear_value;
+
+            self.data.push_cmd(match channel {
+                ChannelType::Unorm
+                | ChannelType::Snorm
+                | ChannelType::Ufloat
+                | ChannelType::Sfloat
+                | ChannelType::Srgb
+                | ChannelType::Uscaled
+                | ChannelType::Sscaled => {
+                    Command::ClearBufferColorF(draw_buffer, unsafe { clear_value.color.float32 })
+                }
+                ChannelType::Uint => {
+                    Command::ClearBufferColorU(draw_buffer, unsafe { clear_value.color.uint32 })
+                }
+                ChannelType::Sint => {
+                    Command::ClearBufferColorI(draw_buffer, unsafe { clear_value.color.sint32 })
+                }
+            });
+        }
+
+        if let Some(index) = subpass.depth_stencil.filter(|&index| is_first_use(index)) {
+            let rat = &render_pass.attachments[index];
             let view_format = rat.format.unwrap();
+            let clear_value = state.attachments[index].clear_value;
 
-            match info.color_index {
-                // Clear color target
-                Some(draw_color_index) => {
-                    if rat.ops.load != pass::AttachmentLoadOp::Clear {
-                        continue;
-                    }
-                    let channel = view_format.base_format().1;
-                    let draw_color_index = draw_color_index as u32;
-
-                    self.data.push_cmd(match channel {
-                        ChannelType::Unorm
-                        | ChannelType::Snorm
-                        | ChannelType::Ufloat
-                        | ChannelType::Sfloat
-                        | ChannelType::Srgb
-                        | ChannelType::Uscaled
-                        | ChannelType::Sscaled => {
-                            Command::ClearBufferColorF(draw_color_index, unsafe {
-                                info.clear_value.color.float32
-                            })
-                        }
-                        ChannelType::Uint => Command::ClearBufferColorU(draw_color_index, unsafe {
-                            info.clear_value.color.uint32
-                        }),
-                        ChannelType::Sint => Command::ClearBufferColorI(draw_color_index, unsafe {
-                            info.clear_value.color.sint32
-                        }),
-                    });
-                }
-                // Clear depth-stencil target
-                None => {
-                    let depth = if view_format.is_depth()
-                        && rat.ops.load == pass::AttachmentLoadOp::Clear
-                    {
-                        Some(unsafe { info.clear_value.depth_stencil.depth })
-                    } else {
-                        None
-                    };
+            let depth = if view_format.is_depth() && rat.ops.load == pass::AttachmentLoadOp::Clear {
+                Some(unsafe { clear_value.depth_stencil.depth })
+            } else {
+                None
+            };
 
-                    // Only reset depth mask if it was non writable
-                    let depth_mask = self.cache.depth_mask.filter(|mask| !mask);
+            let stencil = if view_format.is_stencil()
+                && rat.stencil_ops.load == pass::AttachmentLoadOp::Clear
+            {
+                Some(unsafe { clear_value.depth_stencil.stencil })
+            } else {
+                None
+            };
 
-                    let stencil = if view_format.is_stencil()
-                        && rat.stencil_ops.load == pass::AttachmentLoadOp::Clear
-                    {
-                        Some(unsafe { info.clear_value.depth_stencil.stencil })
-                    } else {
-                        None
-                    };
+            if depth.is_some() || stencil.is_some() {
+                // Only reset depth mask if it was non writable
+                let depth_mask = self.cache.depth_mask.filter(|mask| !mask);
 
-                    let stencil_mask = self
-                        .cache
-                        .stencil_mask
-                        .filter(|mask| mask.front != !0 || mask.back != !0);
+                let stencil_mask = self
+                    .cache
+                    .stencil_mask
+                    .filter(|mask| mask.front != !0 || mask.back != !0);
 
-                    // Temporarily reset masks as they may prevent buffer clear in gl
-                    if depth_mask.is_some() || self.cache.depth_mask.is_none() {
-                        self.data.push_cmd(Command::SetDepthMask(true));
-                    }
-                    if stencil_mask.is_some() || self.cache.stencil_mask.is_none() {
-                        self.data.push_cmd(Command::SetStencilMask(!0));
-                    }
+                // Temporarily reset masks as they may prevent buffer clear in gl
+                if depth_mask.is_some() || self.cache.depth_mask.is_none() {
+                    self.data.push_cmd(Command::SetDepthMask(true));
+                }
+                if stencil_mask.is_some() || self.cache.stencil_mask.is_none() {
+                    self.data.push_cmd(Command::SetStencilMask(!0));
+                }
 
-                    if depth.is_some() || stencil.is_some() {
-                        self.data
-                            .push_cmd(Command::ClearBufferDepthStencil(depth, stencil));
-                    }
+                self.data
+                    .push_cmd(Command::ClearBufferDepthStencil(depth, stencil));
 
-                    // Restore masks if they were reset
-                    if let Some(mask) = depth_mask {
-                        self.data.push_cmd(Command::SetDepthMask(mask));
-                    }
-                    if let Some(mask) = stencil_mask {
-                        self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
-                    }
+                // Restore masks if they were reset
+                if let Some(mask) = depth_mask {
+                    self.data.push_cmd(Command::SetDepthMask(mask));
                 }
+                if let Some(mask) = stencil_mask {
+                    self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
+                }
+            }
+        }
+    }
+
+    fn end_subpass(&mut self) {
+        let state = self.pass_cache.as_ref().unwrap();
+        let render_pass = &state.render_pass;
+        let subpass = &render_pass.subpasses[self.cur_subpass as usize];
+        let (framebuffer, area) = state
+            .framebuffer
+            .expect("Missing framebuffer of a render pass");
+
+        // Resolve the color attachments over the render area.
+        let bounds = image::Offset {
+            x: area.x as i32,
+            y: area.y as i32,
+            z: 0,
+        }
+        .into_bounds(&image::Extent {
+            width: area.w as u32,
+            height: area.h as u32,
+            depth: 1,
+        });
+        for (&color, &resolve) in subpass
+            .color_attachments
+            .iter()
+            .zip(subpass.resolve_attachments.iter())
+        {
+            self.data.push_cmd(Command::BlitFramebuffer {
+                src: state.attachments[color].view(),
+                dst: state.attachments[resolve].view(),
+                src_bounds: bounds.clone(),
+                dst_bounds: bounds.clone(),
+                mask: glow::COLOR_BUFFER_BIT,
+                filter: glow::NEAREST,
+            });
+        }
+
+        // Attachments are stored by the last sub-pass using them, and the
+        // ones not worth storing get invalidated.
+        let cur_subpass = self.cur_subpass;
+        let is_last_use = |index| {
+            render_pass
+                .subpass_range(index)
+                .map_or(false, |range| range.end == cur_subpass + 1)
+        };
+        let mut attachments = ArrayVec::new();
+
+        for (i, &index) in subpass.color_attachments.iter().enumerate() {
+            let rat = &render_pass.attachments[index];
+            if rat.ops.store == pass::AttachmentStoreOp::DontCare && is_last_use(index) {
+                attachments.push(glow::COLOR_ATTACHMENT0 + i as u32);
+            }
+        }
+        if let Some(index) = subpass.depth_stencil.filter(|&index| is_last_use(index)) {
+            let rat = &render_pass.attachments[index];
+            let format = rat.format.unwrap();
+            if format.is_depth() && rat.ops.store == pass::AttachmentStoreOp::DontCare {
+                attachments.push(glow::DEPTH_ATTACHMENT);
+            }
+            if format.is_stencil() && rat.stencil_ops.store == pass::AttachmentStoreOp::DontCare {
+                attachments.push(glow::STENCIL_ATTACHMENT);
+            }
+        }
+
+        if !attachments.is_empty() {
+            self.data.push_cmd(Command::InvalidateFramebuffer {
+                framebuffer,
+                attachments,
+            });
+        }
+    }
+
+    fn push_constants(
+        &mut self,
+        layout: &n::PipelineLayout,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        if offset % 4 != 0 {
+            log::error!("Push constant offset {} is not 4-byte aligned", offset);
+            self.cache.error_state = true;
+            return;
+        }
+        let end = offset + constants.len() as u32 * 4;
+        if end as usize > MAX_PUSH_CONSTANTS_SIZE {
+            log::error!("Push constants {:?} are out of bounds", offset..end);
+            self.cache.error_state = true;
+            return;
+        }
+
+        // The stages of the ranges being updated need to be specified, even
+        // though they all share the same uniform block.
+        for &(range_stages, ref range) in layout.push_constant_ranges.iter() {
+            if range.start < end && offset < range.end && !stages.contains(range_stages) {
+                log::error!(
+                    "Push constant range {:?} of {:?} is updated for {:?}",
+                    range,
+                    range_stages,
+                    stages
+                );
+                self.cache.error_state = true;
+                return;
             }
         }
+
+        let data = self.data.add(constants);
+        self.data.push_cmd(Command::PushConstants(offset, data));
+    }
+
+    fn update_stencil_func(&mut self) {
+        let faces = match self.cache.stencil_faces {
+            Some(faces) => faces,
+            None => return,
+        };
+        let (ref_front, ref_back) = self.cache.stencil_ref.unwrap_or((0, 0));
+        let read_mask = self.cache.stencil_read_mask.unwrap_or(pso::Sided::new(!0));
+
+        self.data.push_cmd(Command::SetStencilFunc {
+            face: glow::FRONT,
+            fun: faces.front.fun,
+            reference: ref_front,
+            read_mask: read_mask.front,
+        });
+        self.data.push_cmd(Command::SetStencilFunc {
+            face: glow::BACK,
+            fun: faces.back.fun,
+            reference: ref_back,
+            read_mask: read_mask.back,
+        });
     }
 
     fn update_sampler_states(&mut self, dirty_textures: u32, dirty_samplers: u32) {
@@ -654,6 +1005,12 @@ impl CommandBuffer {
         }
     }
 
+    fn bind_texture(&mut self, binding: u32, texture: n::Texture, textype: n::TextureTarget) {
+        self.cache.texture_slots[binding as usize].tex_target = textype;
+        self.data
+            .push_cmd(Command::BindTexture(binding, texture, textype));
+    }
+
     fn bind_descriptor_sets<'a, I, J>(
         &mut self,
         layout: &n::PipelineLayout,
@@ -664,53 +1021,100 @@ impl CommandBuffer {
         I: Iterator<Item = &'a n::DescriptorSet>,
         J: Iterator<Item = command::DescriptorSetOffset>,
     {
-        if let Some(_) = offsets.next() {
-            log::warn!("Dynamic offsets are not supported yet");
-        }
-
         let mut dirty_textures = 0u32;
         let mut dirty_samplers = 0u32;
         let mut set = first_set as usize;
         for desc_set in sets {
-            for (binding_layout, new_binding) in
-                desc_set.layout.iter().zip(desc_set.bindings.iter())
-            {
-                let binding = layout.sets[set].bindings[binding_layout.binding as usize] as u32;
-                match *new_binding {
-                    n::DescSetBindings::Buffer {
-                        register,
-                        buffer,
-                        offset,
-                        size,
-                    } => {
-                        let bind_point = match register {
-                            n::BindingRegister::UniformBuffers => glow::UNIFORM_BUFFER,
-                            n::BindingRegister::StorageBuffers => glow::SHADER_STORAGE_BUFFER,
-                            n::BindingRegister::Textures => panic!("Wrong desc set binding"),
-                        };
-                        self.data.push_cmd(Command::BindBufferRange(
-                            bind_point,
-                            binding,
-                            buffer,
-                            offset as i32,
-                            size as i32,
-                        ));
-                    }
-                    n::DescSetBindings::Texture(texture, textype) => {
-                        dirty_textures |= 1 << binding;
-                        self.cache.texture_slots[binding as usize].tex_target = textype;
-                        self.data
-                            .push_cmd(Command::BindTexture(binding, texture, textype));
-                    }
-                    n::DescSetBindings::Sampler(sampler) => {
-                        dirty_samplers |= 1 << binding;
-                        self.cache.samplers[binding as usize] =
-                            Some(n::FatSampler::Sampler(sampler));
+            let set_layout = &desc_set.layout;
+            for (index, binding_layout) in set_layout.bindings.iter().enumerate() {
+                let base_binding = layout.sets[set].bindings[binding_layout.binding as usize];
+                let base_sampler =
+                    layout.sets[set].combined_samplers[binding_layout.binding as usize];
+                let immutable_samplers = &set_layout.immutable_samplers[index];
+
+                for array_index in 0..binding_layout.count {
+                    let binding = base_binding as u32 + array_index as u32;
+                    // Index of the sampler of the descriptor in the whole layout.
+                    let sampler_binding = match binding_layout.ty {
+                        pso::DescriptorType::Sampler => binding,
+                        _ => base_sampler as u32 + array_index as u32,
+                    };
+                    // Immutable samplers are bound even if the descriptor is not written,
+                    // and take precedence over the written ones.
+                    if let Some(sampler) = immutable_samplers.get(array_index) {
+                        dirty_samplers |= 1 << sampler_binding;
+                        self.cache.samplers[sampler_binding as usize] = Some(sampler.clone());
                     }
-                    n::DescSetBindings::SamplerDesc(ref info) => {
-                        dirty_samplers |= 1 << binding;
-                        self.cache.samplers[binding as usize] =
-                            Some(n::FatSampler::Info(info.clone()));
+                    // Dynamic offsets are consumed in the order of the sets and bindings.
+                    let dynamic_offset = match binding_layout.ty {
+                        pso::DescriptorType::Buffer {
+                            format:
+                                pso::BufferDescriptorFormat::Structured {
+                                    dynamic_offset: true,
+                                },
+                            ..
+                        } => match offsets.next() {
+                            Some(offset) => offset,
+                            None => {
+                                log::error!(
+                                    "Missing dynamic offset for binding {} of set {}",
+                                    binding_layout.binding,
+                                    set
+                                );
+                                self.cache.error_state = true;
+                                return;
+                            }
+                        },
+                        _ => 0,
+                    };
+
+                    let descriptor = &desc_set.bindings[set_layout.offsets[index] + array_index];
+                    match *descriptor {
+                        Some(n::DescSetBindings::Buffer {
+                            register,
+                            buffer,
+                            offset,
+                            size,
+                        }) => {
+                            let bind_point = match register {
+                                n::BindingRegister::UniformBuffers => glow::UNIFORM_BUFFER,
+                                n::BindingRegister::StorageBuffers => glow::SHADER_STORAGE_BUFFER,
+                                n::BindingRegister::Textures => panic!("Wrong desc set binding"),
+                            };
+                            self.data.push_cmd(Command::BindBufferRange(
+                                bind_point,
+                                binding,
+                                buffer,
+                                offset + dynamic_offset as i32,
+                                size as i32,
+                            ));
+                        }
+                        Some(n::DescSetBindings::Texture(texture, textype)) => {
+                            self.bind_texture(binding, texture, textype);
+                            dirty_textures |= 1 << binding;
+                        }
+                        Some(n::DescSetBindings::TextureSampler(texture, textype, ref sampler)) => {
+                            self.bind_texture(binding, texture, textype);
+                            dirty_textures |= 1 << binding;
+                            if immutable_samplers.is_empty() {
+                                dirty_samplers |= 1 << sampler_binding;
+                                self.cache.samplers[sampler_binding as usize] =
+                                    Some(sampler.clone());
+                            }
+                        }
+                        Some(n::DescSetBindings::Image(texture, access, format)) => {
+                            self.data.push_cmd(Command::BindImageTexture(
+                                binding, texture, access, format,
+                            ));
+                        }
+                        Some(n::DescSetBindings::Sampler(ref sampler)) => {
+                            if immutable_samplers.is_empty() {
+                                dirty_samplers |= 1 << sampler_binding;
+                                self.cache.samplers[sampler_binding as usize] =
+                                    Some(sampler.clone());
+                            }
+                        }
+                        None => {}
                     }
                 }
             }
@@ -725,8 +1129,8 @@ impl CommandBuffer {
 impl command::CommandBuffer<Backend> for CommandBuffer {
     unsafe fn begin(
         &mut self,
-        _flags: command::CommandBufferFlags,
-        _inheritance_info: command::CommandBufferInheritanceInfo<Backend>,
+        flags: command::CommandBufferFlags,
+        inheritance_info: command::CommandBufferInheritanceInfo<Backend>,
     ) {
         // TODO: Implement flags!
         if self.individual_reset {
@@ -735,6 +1139,30 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         } else {
             self.soft_reset();
         }
+
+        // Secondary command buffers continuing a render pass are executed
+        // within it, with the framebuffer and draw buffers already set by
+        // the primary one. Only the attachment layout needs to be known.
+        if flags.contains(command::CommandBufferFlags::RENDER_PASS_CONTINUE) {
+            let subpass = inheritance_info
+                .subpass
+                .expect("Missing subpass of a render pass continuation");
+            let attachments = subpass
+                .main_pass
+                .attachments
+                .iter()
+                .map(|_| AttachmentInfo {
+                    view: None,
+                    clear_value: command::ClearValue::default(),
+                })
+                .collect();
+            self.pass_cache = Some(RenderPassCache {
+                render_pass: subpass.main_pass.clone(),
+                attachments,
+                framebuffer: None,
+            });
+            self.cur_subpass = subpass.index;
+        }
     }
 
     unsafe fn finish(&mut self) {
@@ -759,37 +1187,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     ) where
         T: Iterator<Item = memory::Barrier<'a, Backend>>,
     {
-        //TODO: this needs to be much more detailed. Problem is that the affected
-        // resources by a barrier have to be bound to specific slots, so, for example,
-        // doing a `set_graphics_pipeline` followed by `pipeline_barrier` may need
-        // the vertex bindings to be reinstated.
-        let mut mask = 0;
-
-        for barrier in barriers {
-            match barrier {
-                memory::Barrier::AllBuffers(access) => {
-                    if access.start.contains(buffer::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_STORAGE_BARRIER_BIT;
-                    }
-                }
-                memory::Barrier::Buffer { states, .. } => {
-                    if states.start.contains(buffer::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_STORAGE_BARRIER_BIT;
-                    }
-                }
-                memory::Barrier::AllImages(access) => {
-                    if access.start.contains(image::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
-                    }
-                }
-                memory::Barrier::Image { states, .. } => {
-                    if states.start.0.contains(image::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
-                    }
-                }
-            }
-        }
-
+        let mask = barrier_mask(barriers);
         if mask != 0 {
             self.data.push_cmd(Command::MemoryBarrier(mask));
         }
@@ -802,67 +1200,41 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             .push_cmd(Command::FillBuffer(bounded_buffer.raw, range, data));
     }
 
-    unsafe fn update_buffer(&mut self, _buffer: &n::Buffer, _offset: buffer::Offset, _data: &[u8]) {
-        unimplemented!()
+    unsafe fn update_buffer(&mut self, buffer: &n::Buffer, offset: buffer::Offset, data: &[u8]) {
+        let bounded_buffer = buffer.as_bound();
+        let data_ptr = self.data.add_raw(data);
+        self.data.push_cmd(Command::UpdateBuffer(
+            bounded_buffer.raw,
+            bounded_buffer.range.start + offset,
+            data_ptr,
+        ));
     }
 
     unsafe fn begin_render_pass<'a, T>(
         &mut self,
         render_pass: &n::RenderPass,
         framebuffer: &n::Framebuffer,
-        _render_area: pso::Rect,
+        render_area: pso::Rect,
         attachment_infos: T,
         _first_subpass: command::SubpassContents,
     ) where
         T: Iterator<Item = command::RenderAttachmentInfo<'a, Backend>>,
     {
-        // TODO: load ops: clearing strategy
-        //  1.  < GL 3.0 / GL ES 2.0: glClear, only single color attachment?
-        //  2.  = GL ES 2.0: glBindFramebuffer + glClear (no single draw buffer supported)
-        //  3. >= GL 3.0 / GL ES 3.0: glBindFramerbuffer + glClearBuffer
-        //
-        // Clearing when entering a subpass:
-        //    * Acquire channel information from renderpass description to
-        //      select correct ClearBuffer variant.
-        //    * Check for attachment loading clearing strategy
-
-        // TODO: store ops:
-        //   < GL 4.5: Ignore
-        //  >= GL 4.5: Invalidate framebuffer attachment when store op is `DONT_CARE`.
-
-        // 2./3.
-        let mut attachments = Vec::new();
-        let mut colors = ArrayVec::new();
-        let mut depth_stencil = None;
-
-        for info in attachment_infos {
-            let view = info.image_view.clone();
-            let aspects = view.aspects();
-            let color_index = if aspects.contains(Aspects::COLOR) {
-                colors.push(view);
-                Some(colors.len() as u8 - 1)
-            } else {
-                depth_stencil = Some(view);
-                None
-            };
-            attachments.push(AttachmentInfo {
-                color_index,
+        // Sub-passes are emulated by switching the framebuffer attachments and
+        // draw buffers on each of them, see `begin_subpass`. Load ops are
+        // applied by the first sub-pass using an attachment, and store ops
+        // and resolves at the end of the sub-passes, see `end_subpass`.
+        let attachments = attachment_infos
+            .map(|info| AttachmentInfo {
+                view: Some(info.image_view.clone()),
                 clear_value: info.clear_value,
             })
-        }
+            .collect();
 
         self.pass_cache = Some(RenderPassCache {
             render_pass: render_pass.clone(),
             attachments,
-        });
-
-        //Note: we currently only expect one sub-pass.
-
-        self.data.push_cmd(Command::BindFramebuffer {
-            target: glow::DRAW_FRAMEBUFFER,
-            framebuffer: framebuffer.raw,
-            colors,
-            depth_stencil,
+            framebuffer: Some((framebuffer.raw, render_area)),
         });
 
         // Enter first subpass
@@ -871,11 +1243,15 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn next_subpass(&mut self, _contents: command::SubpassContents) {
-        unimplemented!()
+        self.end_subpass();
+        self.cur_subpass += 1;
+        self.begin_subpass();
     }
 
     unsafe fn end_render_pass(&mut self) {
-        // TODO
+        self.end_subpass();
+        self.pass_cache = None;
+        self.cur_subpass = !0;
     }
 
     unsafe fn clear_image<T>(
@@ -883,7 +1259,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         image: &n::Image,
         _: image::Layout,
         value: command::ClearValue,
-        _subresource_ranges: T,
+        subresource_ranges: T,
     ) where
         T: Iterator<Item = image::SubresourceRange>,
     {
@@ -897,55 +1273,55 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             Some(fbo) => {
                 // TODO: reset color mask
                 // 2. ClearBuffer
-                let view = match image.object_type {
-                    n::ImageType::Renderbuffer { raw, .. } => n::ImageView::Renderbuffer {
-                        raw,
-                        aspects: image.format_desc.aspects,
-                    },
-                    n::ImageType::Texture {
-                        target,
-                        raw,
-                        level_count,
-                        layer_count,
-                        ..
-                    } => {
-                        let is_3d = layer_count == 1; //TODO?
-                        n::ImageView::Texture {
-                            target,
-                            raw,
-                            is_3d,
-                            sub: image::SubresourceRange {
-                                aspects: Aspects::COLOR,
-                                layer_start: 0,
-                                layer_count: Some(layer_count),
-                                level_start: 0,
-                                level_count: Some(level_count),
-                            },
-                        }
-                    }
-                };
-                self.data.push_cmd(Command::BindFramebuffer {
-                    target: glow::DRAW_FRAMEBUFFER,
-                    framebuffer: fbo,
-                    colors: iter::once(view).collect(),
-                    depth_stencil: None,
-                });
-                self.data
-                    .push_cmd(Command::SetDrawColorBuffers(iter::once(0).collect()));
                 self.data
                     .push_cmd(Command::SetColorMask(None, pso::ColorMask::ALL));
 
-                self.data.push_cmd(match image.channel {
-                    ChannelType::Unorm
-                    | ChannelType::Snorm
-                    | ChannelType::Ufloat
-                    | ChannelType::Sfloat
-                    | ChannelType::Srgb
-                    | ChannelType::Uscaled
-                    | ChannelType::Sscaled => Command::ClearBufferColorF(0, color.float32),
-                    ChannelType::Uint => Command::ClearBufferColorU(0, color.uint32),
-                    ChannelType::Sint => Command::ClearBufferColorI(0, color.sint32),
-                });
+                for range in subresource_ranges {
+                    let level_end = range
+                        .level_count
+                        .map_or(image.num_levels, |count| range.level_start + count);
+                    for level in range.level_start..level_end {
+                        // The slices of 3D images are cleared like layers.
+                        let num_slices = match image.kind {
+                            image::Kind::D3(..) => {
+                                image.kind.extent().at_level(level).depth as image::Layer
+                            }
+                            _ => image.num_layers,
+                        };
+                        let layer_end = range
+                            .layer_count
+                            .map_or(num_slices, |count| range.layer_start + count);
+                        for layer in range.layer_start..layer_end {
+                            self.data.push_cmd(Command::BindFramebuffer {
+                                target: glow::DRAW_FRAMEBUFFER,
+                                framebuffer: fbo,
+                                colors: iter::once(attachment_view(
+                                    image,
+                                    image.format_desc.aspects,
+                                    level,
+                                    layer,
+                                ))
+                                .collect(),
+                                depth_stencil: None,
+                            });
+                            self.data
+                                .push_cmd(Command::SetDrawColorBuffers(iter::once(0).collect()));
+                            self.data.push_cmd(match image.channel {
+                                ChannelType::Unorm
+                                | ChannelType::Snorm
+                                | ChannelType::Ufloat
+                                | ChannelType::Sfloat
+                                | ChannelType::Srgb
+                                | ChannelType::Uscaled
+                                | ChannelType::Sscaled => {
+                                    Command::ClearBufferColorF(0, color.float32)
+                                }
+                                ChannelType::Uint => Command::ClearBufferColorU(0, color.uint32),
+                                ChannelType::Sint => Command::ClearBufferColorI(0, color.sint32),
+                            });
+                        }
+                    }
+                }
 
                 //Note: color mask is not restored: we are outside of a render pass,
                 // and whatever needs to have the mask, including the pass, should set it.
@@ -963,39 +1339,154 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         }
     }
 
-    unsafe fn clear_attachments<T, U>(&mut self, _: T, _: U)
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
     where
         T: Iterator<Item = command::AttachmentClear>,
         U: Iterator<Item = pso::ClearRect>,
     {
-        unimplemented!()
+        let state = match self.pass_cache {
+            Some(ref state) => state,
+            None => {
+                log::error!("Attachments can only be cleared within a render pass");
+                self.cache.error_state = true;
+                return;
+            }
+        };
+        let subpass = &state.render_pass.subpasses[self.cur_subpass as usize];
+        let clears = clears.collect::<Vec<_>>();
+
+        for rect in rects {
+            //TODO: clear the layers separately
+            self.data.push_cmd(Command::SetClearRect(Some(rect.rect)));
+            // Write masks apply to the clears in GL, so they are lifted
+            // and then restored from the cache.
+            for clear in clears.iter() {
+                match *clear {
+                    command::AttachmentClear::Color { index, value } => {
+                        let attachment = subpass.color_attachments[index];
+                        let channel = state.render_pass.attachments[attachment]
+                            .format
+                            .map_or(ChannelType::Unorm, |format| format.base_format().1);
+                        let draw_buffer = index as DrawBuffer;
+                        self.data.push_cmd(Command::SetColorMask(
+                            Some(draw_buffer),
+                            pso::ColorMask::ALL,
+                        ));
+                        self.data.push_cmd(match channel {
+                            ChannelType::Uint => {
+                                Command::ClearBufferColorU(draw_buffer, value.uint32)
+                            }
+                            ChannelType::Sint => {
+                                Command::ClearBufferColorI(draw_buffer, value.sint32)
+                            }
+                            _ => Command::ClearBufferColorF(draw_buffer, value.float32),
+                        });
+                        if let Some(&Some(target)) = self.cache.blend_targets.get(index) {
+                            self.data
+                                .push_cmd(Command::SetColorMask(Some(draw_buffer), target.mask));
+                        }
+                    }
+                    command::AttachmentClear::DepthStencil { depth, stencil } => {
+                        if depth.is_none() && stencil.is_none() {
+                            continue;
+                        }
+                        self.data.push_cmd(Command::SetDepthMask(true));
+                        self.data.push_cmd(Command::SetStencilMask(!0));
+                        self.data
+                            .push_cmd(Command::ClearBufferDepthStencil(depth, stencil));
+                        self.data
+                            .push_cmd(Command::SetDepthMask(self.cache.depth_mask.unwrap_or(true)));
+                        if let Some(mask) = self.cache.stencil_mask {
+                            self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
+                        }
+                    }
+                }
+            }
+        }
+        self.data.push_cmd(Command::SetClearRect(None));
     }
 
     unsafe fn resolve_image<T>(
         &mut self,
-        _src: &n::Image,
+        src: &n::Image,
         _src_layout: image::Layout,
-        _dst: &n::Image,
+        dst: &n::Image,
         _dst_layout: image::Layout,
-        _regions: T,
+        regions: T,
     ) where
         T: Iterator<Item = command::ImageResolve>,
     {
-        unimplemented!()
+        for region in regions {
+            let num_layers =
+                region.src_subresource.layers.end - region.src_subresource.layers.start;
+            for i in 0..num_layers {
+                self.data.push_cmd(Command::BlitFramebuffer {
+                    src: attachment_view(
+                        src,
+                        region.src_subresource.aspects,
+                        region.src_subresource.level,
+                        region.src_subresource.layers.start + i,
+                    ),
+                    dst: attachment_view(
+                        dst,
+                        region.dst_subresource.aspects,
+                        region.dst_subresource.level,
+                        region.dst_subresource.layers.start + i,
+                    ),
+                    src_bounds: region.src_offset.into_bounds(&region.extent),
+                    dst_bounds: region.dst_offset.into_bounds(&region.extent),
+                    mask: blit_mask(region.src_subresource.aspects),
+                    filter: glow::NEAREST,
+                });
+            }
+        }
     }
 
     unsafe fn blit_image<T>(
         &mut self,
-        _src: &n::Image,
+        src: &n::Image,
         _src_layout: image::Layout,
-        _dst: &n::Image,
+        dst: &n::Image,
         _dst_layout: image::Layout,
-        _filter: image::Filter,
-        _regions: T,
+        filter: image::Filter,
+        regions: T,
     ) where
         T: Iterator<Item = command::ImageBlit>,
     {
-        log::error!("Blit is not implemented");
+        let filter = match filter {
+            image::Filter::Nearest => glow::NEAREST,
+            image::Filter::Linear => glow::LINEAR,
+        };
+        for region in regions {
+            let num_layers =
+                region.src_subresource.layers.end - region.src_subresource.layers.start;
+            let mask = blit_mask(region.src_subresource.aspects);
+            for i in 0..num_layers {
+                self.data.push_cmd(Command::BlitFramebuffer {
+                    src: attachment_view(
+                        src,
+                        region.src_subresource.aspects,
+                        region.src_subresource.level,
+                        region.src_subresource.layers.start + i,
+                    ),
+                    dst: attachment_view(
+                        dst,
+                        region.dst_subresource.aspects,
+                        region.dst_subresource.level,
+                        region.dst_subresource.layers.start + i,
+                    ),
+                    src_bounds: region.src_bounds.clone(),
+                    dst_bounds: region.dst_bounds.clone(),
+                    mask,
+                    // Depth and stencil can only be blitted without filtering.
+                    filter: if mask == glow::COLOR_BUFFER_BIT {
+                        filter
+                    } else {
+                        glow::NEAREST
+                    },
+                });
+            }
+        }
     }
 
     unsafe fn bind_index_buffer(
@@ -1127,19 +1618,44 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             back = value;
         }
 
-        // Only cache the stencil references values until
-        // we assembled all the pieces to set the stencil state
-        // from the pipeline.
+        // The references are set along with the comparison of the pipeline,
+        // or once a pipeline with a stencil test is bound.
         self.cache.stencil_ref = Some((front, back));
+        self.update_stencil_func();
     }
 
-    unsafe fn set_stencil_read_mask(&mut self, _faces: pso::Face, _value: pso::StencilValue) {
-        unimplemented!();
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        assert!(!faces.is_empty());
+
+        let mut mask = self.cache.stencil_read_mask.unwrap_or(pso::Sided::new(!0));
+
+        if faces.contains(pso::Face::FRONT) {
+            mask.front = value;
+        }
+
+        if faces.contains(pso::Face::BACK) {
+            mask.back = value;
+        }
+
+        self.cache.stencil_read_mask = Some(mask);
+        self.update_stencil_func();
     }
 
-    unsafe fn set_stencil_write_mask(&mut self, _faces: pso::Face, _value: pso::StencilValue) {
-        // set self.cache.stencil_mask once implemented
-        unimplemented!();
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        assert!(!faces.is_empty());
+
+        let mut mask = self.cache.stencil_mask.unwrap_or(pso::Sided::new(!0));
+
+        if faces.contains(pso::Face::FRONT) {
+            mask.front = value;
+        }
+
+        if faces.contains(pso::Face::BACK) {
+            mask.back = value;
+        }
+
+        self.cache.stencil_mask = Some(mask);
+        self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
     }
 
     unsafe fn set_blend_constants(&mut self, cv: pso::ColorValue) {
@@ -1153,12 +1669,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         log::warn!("Depth bounds test is not supported");
     }
 
-    unsafe fn set_line_width(&mut self, _width: f32) {
-        unimplemented!()
+    unsafe fn set_line_width(&mut self, width: f32) {
+        self.data.push_cmd(Command::SetLineWidth(width));
     }
 
-    unsafe fn set_depth_bias(&mut self, _depth_bias: pso::DepthBias) {
-        unimplemented!()
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.data.push_cmd(Command::SetDepthBias(depth_bias));
     }
 
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
@@ -1181,8 +1697,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         self.cache.attributes = pipeline.attributes.clone();
         self.cache.vertex_buffer_descs = pipeline.vertex_buffers.clone();
 
-        self.cache.uniforms = pipeline.uniforms.clone();
-
         self.update_blend_targets(&pipeline.blend_targets);
 
         self.data.push_cmd(Command::BindRasterizer {
@@ -1195,6 +1709,25 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         ));
         self.cache.depth_mask = pipeline.depth.map(|d| d.write);
 
+        // Static stencil values override the dynamic ones, which are otherwise
+        // kept from the previous commands.
+        self.cache.stencil_faces = pipeline.stencil.map(|s| s.faces);
+        self.data
+            .push_cmd(Command::BindStencil(self.cache.stencil_faces));
+        if let Some(stencil) = pipeline.stencil {
+            if let pso::State::Static(values) = stencil.reference_values {
+                self.cache.stencil_ref = Some((values.front, values.back));
+            }
+            if let pso::State::Static(masks) = stencil.read_masks {
+                self.cache.stencil_read_mask = Some(masks);
+            }
+            if let pso::State::Static(masks) = stencil.write_masks {
+                self.cache.stencil_mask = Some(masks);
+                self.data.push_cmd(Command::SetStencilMaskSeparate(masks));
+            }
+            self.update_stencil_func();
+        }
+
         if let Some(ref vp) = pipeline.baked_states.viewport {
             self.set_viewports(0, iter::once(vp.clone()));
         }
@@ -1310,18 +1843,11 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let old_size = self.data.buf.size;
 
         for r in regions {
-            let cmd = match dst.object_type {
-                n::ImageType::Renderbuffer { raw, format } => Command::CopyImageToRenderbuffer {
-                    src_image: src.object_type,
-                    dst_renderbuffer: raw,
-                    dst_format: format,
-                    data: r,
-                },
-                n::ImageType::Texture { raw, target, .. } => {
-                    Command::CopyImageToTexture(src.object_type, raw, target, r)
-                }
-            };
-            self.data.push_cmd(cmd);
+            self.data.push_cmd(Command::CopyImage {
+                src_image: src.object_type,
+                dst_image: dst.object_type,
+                data: r,
+            });
         }
 
         if self.data.buf.size == old_size {
@@ -1343,26 +1869,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let src_bounded_buffer = src.as_bound();
         for mut r in regions {
             r.buffer_offset += src_bounded_buffer.range.start;
-            let cmd = match dst.object_type {
-                n::ImageType::Renderbuffer { raw, .. } => {
-                    Command::CopyBufferToRenderbuffer(src_bounded_buffer.raw, raw, r)
-                }
-                n::ImageType::Texture {
-                    raw,
-                    target,
-                    format,
-                    pixel_type,
-                    ..
-                } => Command::CopyBufferToTexture {
-                    src_buffer: src_bounded_buffer.raw,
-                    dst_texture: raw,
-                    texture_target: target,
-                    texture_format: format,
-                    pixel_type,
-                    data: r,
-                },
-            };
-            self.data.push_cmd(cmd);
+            self.data.push_cmd(Command::CopyBufferToImage {
+                src_buffer: src_bounded_buffer.raw,
+                dst_image: dst.object_type,
+                format_desc: dst.format_desc,
+                data: r,
+            });
         }
 
         if self.data.buf.size == old_size {
@@ -1384,26 +1896,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         for mut r in regions {
             r.buffer_offset += dst_bounded_buffer.range.start;
-            let cmd = match src.object_type {
-                n::ImageType::Renderbuffer { raw, .. } => {
-                    Command::CopyRenderbufferToBuffer(raw, dst_bounded_buffer.raw, r)
-                }
-                n::ImageType::Texture {
-                    raw,
-                    target,
-                    format,
-                    pixel_type,
-                    ..
-                } => Command::CopyTextureToBuffer {
-                    src_texture: raw,
-                    texture_target: target,
-                    texture_format: format,
-                    pixel_type: pixel_type,
-                    dst_buffer: dst_bounded_buffer.raw,
-                    data: r,
-                },
-            };
-            self.data.push_cmd(cmd);
+            self.data.push_cmd(Command::CopyImageToBuffer {
+                src_image: src.object_type,
+                format_desc: src.format_desc,
+                dst_buffer: dst_bounded_buffer.raw,
+                data: r,
+            });
         }
 
         if self.data.buf.size == old_size {
@@ -1499,46 +1997,128 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn draw_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        self.bind_attributes(0);
+        let bounded_buffer = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndirect {
+                    primitive,
+                    index_type: None,
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indexed_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        self.bind_attributes(0);
+        let index_type = match self.indirect_index_type("draw_indexed_indirect") {
+            Some(index_type) => index_type,
+            None => return,
+        };
+        let bounded_buffer = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndirect {
+                    primitive,
+                    index_type: Some(index_type),
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indirect_count(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _count_buffer: &n::Buffer,
-        _count_buffer_offset: buffer::Offset,
-        _max_draw_count: u32,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        count_buffer: &n::Buffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        self.bind_attributes(0);
+        let bounded_buffer = buffer.as_bound();
+        let bounded_count_buffer = count_buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndirectCount {
+                    primitive,
+                    index_type: None,
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    count_buffer: bounded_count_buffer.raw,
+                    count_buffer_offset: bounded_count_buffer.range.start + count_buffer_offset,
+                    max_draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indirect_count`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indexed_indirect_count(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _count_buffer: &n::Buffer,
-        _count_buffer_offset: buffer::Offset,
-        _max_draw_count: u32,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        count_buffer: &n::Buffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        self.bind_attributes(0);
+        let index_type = match self.indirect_index_type("draw_indexed_indirect_count") {
+            Some(index_type) => index_type,
+            None => return,
+        };
+        let bounded_buffer = buffer.as_bound();
+        let bounded_count_buffer = count_buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndirectCount {
+                    primitive,
+                    index_type: Some(index_type),
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    count_buffer: bounded_count_buffer.raw,
+                    count_buffer_offset: bounded_count_buffer.range.start + count_buffer_offset,
+                    max_draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indexed_indirect_count`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_mesh_tasks(&mut self, _: u32, _: u32) {
@@ -1566,102 +2146,116 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     ) {
         unimplemented!()
     }
-    unsafe fn set_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
+    unsafe fn set_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.data.push_cmd(Command::SetEvent(event.clone()));
     }
 
-    unsafe fn reset_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
+    unsafe fn reset_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.data.push_cmd(Command::ResetEvent(event.clone()));
     }
 
-    unsafe fn wait_events<'a, I, J>(&mut self, _: I, _: Range<pso::PipelineStage>, _: J)
+    unsafe fn wait_events<'a, I, J>(&mut self, _: I, _: Range<pso::PipelineStage>, barriers: J)
     where
-        I: Iterator<Item = &'a ()>,
+        I: Iterator<Item = &'a n::Event>,
         J: Iterator<Item = memory::Barrier<'a, Backend>>,
     {
-        unimplemented!()
+        // Commands are executed in order by GL, so waiting on the events
+        // only needs to make the memory writes visible.
+        let mask = barrier_mask(barriers);
+        if mask != 0 {
+            self.data.push_cmd(Command::MemoryBarrier(mask));
+        }
     }
 
-    unsafe fn begin_query(&mut self, _query: query::Query<Backend>, _flags: query::ControlFlags) {
-        unimplemented!()
+    unsafe fn begin_query(&mut self, query: query::Query<Backend>, _flags: query::ControlFlags) {
+        let objects = query.pool.query_objects(query.id);
+        for (&target, &object) in query.pool.targets.iter().zip(objects) {
+            self.data.push_cmd(Command::BeginQuery(object, target));
+        }
     }
 
     unsafe fn copy_query_pool_results(
         &mut self,
-        _pool: &(),
-        _queries: Range<query::Id>,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _stride: buffer::Stride,
-        _flags: query::ResultFlags,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
     ) {
-        unimplemented!()
+        let counters = pool.targets.len();
+        let objects =
+            &pool.objects[queries.start as usize * counters..queries.end as usize * counters];
+        let bounded_buffer = buffer.as_bound();
+        self.data.push_cmd(Command::CopyQueryPoolResults {
+            objects: objects.to_vec(),
+            counters,
+            buffer: bounded_buffer.raw,
+            offset: bounded_buffer.range.start + offset,
+            stride,
+            flags,
+        });
     }
 
-    unsafe fn end_query(&mut self, _query: query::Query<Backend>) {
-        unimplemented!()
+    unsafe fn end_query(&mut self, query: query::Query<Backend>) {
+        for &target in query.pool.targets.iter() {
+            self.data.push_cmd(Command::EndQuery(target));
+        }
     }
 
-    unsafe fn reset_query_pool(&mut self, _pool: &(), _queries: Range<query::Id>) {
-        unimplemented!()
+    unsafe fn reset_query_pool(&mut self, _pool: &n::QueryPool, _queries: Range<query::Id>) {
+        // GL query objects don't need to be reset before reuse.
     }
 
-    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {
-        unimplemented!()
+    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, query: query::Query<Backend>) {
+        let object = query.pool.query_objects(query.id)[0];
+        self.data.push_cmd(Command::QueryCounter(object));
     }
 
     unsafe fn push_graphics_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _stages: pso::ShaderStageFlags,
+        layout: &n::PipelineLayout,
+        stages: pso::ShaderStageFlags,
         offset: u32,
         constants: &[u32],
     ) {
-        let buffer = self.data.add(constants);
-
-        let uniforms = &self.cache.uniforms;
-        if uniforms.is_empty() {
-            unimplemented!()
-        }
-
-        let uniform = if offset == 0 {
-            // If offset is zero, we can just return the first item
-            // in our uniform list
-            uniforms.get(0).unwrap()
-        } else {
-            match uniforms.binary_search_by(|uniform| uniform.offset.cmp(&offset as _)) {
-                Ok(index) => uniforms.get(index).unwrap(),
-                Err(_) => panic!("No uniform found at offset: {}", offset),
-            }
-        }
-        .clone();
-
-        self.data.push_cmd(Command::BindUniform { uniform, buffer });
+        self.push_constants(layout, stages, offset, constants);
     }
 
     unsafe fn push_compute_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _offset: u32,
-        _constants: &[u32],
+        layout: &n::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
     ) {
-        unimplemented!()
+        self.push_constants(layout, pso::ShaderStageFlags::COMPUTE, offset, constants);
     }
 
-    unsafe fn execute_commands<'a, T>(&mut self, _buffers: T)
+    unsafe fn execute_commands<'a, T>(&mut self, buffers: T)
     where
         T: Iterator<Item = &'a CommandBuffer>,
     {
-        unimplemented!()
+        for secondary in buffers {
+            self.data
+                .push_cmd(Command::ExecuteCommands(SecondaryCommands {
+                    memory: Arc::downgrade(&secondary.data.memory),
+                    buf: secondary.data.buf,
+                    id: secondary.data.id,
+                }));
+        }
+        // The state left by the secondary command buffers is unknown.
+        self.cache = Cache::new();
     }
 
-    unsafe fn insert_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn insert_debug_marker(&mut self, name: &str, _color: u32) {
+        let label = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::InsertDebugMarker(label));
     }
-    unsafe fn begin_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn begin_debug_marker(&mut self, name: &str, _color: u32) {
+        let label = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::PushDebugGroup(label));
     }
     unsafe fn end_debug_marker(&mut self) {
-        //TODO
+        self.data.push_cmd(Command::PopDebugGroup);
     }
 }
@@ -1,5 +1,5 @@
 use crate::native::VertexAttribFunction;
-use hal::{format::Format, image as i, pso};
+use hal::{format::Format, image as i, pso, query};
 
 /*
 pub fn _image_kind_to_gl(kind: i::Kind) -> t::GLenum {
@@ -44,6 +44,36 @@ pub fn wrap_to_gl(w: i::WrapMode) -> u32 {
     }
 }
 
+pub fn comparison_to_gl(cmp: pso::Comparison) -> u32 {
+    use hal::pso::Comparison::*;
+
+    match cmp {
+        Never => glow::NEVER,
+        Less => glow::LESS,
+        LessEqual => glow::LEQUAL,
+        Equal => glow::EQUAL,
+        GreaterEqual => glow::GEQUAL,
+        Greater => glow::GREATER,
+        NotEqual => glow::NOTEQUAL,
+        Always => glow::ALWAYS,
+    }
+}
+
+pub fn stencil_op_to_gl(op: pso::StencilOp) -> u32 {
+    use hal::pso::StencilOp::*;
+
+    match op {
+        Keep => glow::KEEP,
+        Zero => glow::ZERO,
+        Replace => glow::REPLACE,
+        IncrementClamp => glow::INCR,
+        DecrementClamp => glow::DECR,
+        Invert => glow::INVERT,
+        IncrementWrap => glow::INCR_WRAP,
+        DecrementWrap => glow::DECR_WRAP,
+    }
+}
+
 pub fn input_assember_to_gl_primitive(ia: &pso::InputAssemblerDesc) -> u32 {
     match (ia.primitive, ia.with_adjacency) {
         (pso::Primitive::PointList, false) => glow::POINTS,
@@ -85,15 +115,96 @@ impl FormatDescription {
             va_fun,
         }
     }
+
+    fn compressed(tex_internal: u32, num_components: u8) -> Self {
+        FormatDescription {
+            tex_internal,
+            tex_external: tex_internal,
+            data_type: glow::NONE,
+            num_components,
+            va_fun: VertexAttribFunction::Float,
+        }
+    }
 }
 
+// Enums of extensions, missing from the core registry.
+const SR8_EXT: u32 = 0x8FBD;
+const SRG8_EXT: u32 = 0x8FBE;
+const COMPRESSED_RGB_S3TC_DXT1_EXT: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1_EXT: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: u32 = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+// The ASTC formats are numbered consecutively, in the order of the block sizes in `Format`.
+const COMPRESSED_RGBA_ASTC_4X4_KHR: u32 = 0x93B0;
+const COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR: u32 = 0x93D0;
+
 pub fn describe_format(format: Format) -> Option<FormatDescription> {
     use crate::native::VertexAttribFunction::*;
     use hal::format::Format::*;
     let _ = Double; //mark as used
 
-    // TODO: Add more formats and error handling for `None`
     Some(match format {
+        // Formats without a GL counterpart are reported as unsupported.
+        Rg4Unorm => return None,
+        Rgba4Unorm => FormatDescription::new(
+            glow::RGBA4,
+            glow::RGBA,
+            glow::UNSIGNED_SHORT_4_4_4_4,
+            4,
+            Float,
+        ),
+        Bgra4Unorm => FormatDescription::new(
+            glow::RGBA4,
+            glow::BGRA,
+            glow::UNSIGNED_SHORT_4_4_4_4,
+            4,
+            Float,
+        ),
+        R5g6b5Unorm => FormatDescription::new(
+            glow::RGB565,
+            glow::RGB,
+            glow::UNSIGNED_SHORT_5_6_5,
+            3,
+            Float,
+        ),
+        B5g6r5Unorm => FormatDescription::new(
+            glow::RGB565,
+            glow::RGB,
+            glow::UNSIGNED_SHORT_5_6_5_REV,
+            3,
+            Float,
+        ),
+        R5g5b5a1Unorm => FormatDescription::new(
+            glow::RGB5_A1,
+            glow::RGBA,
+            glow::UNSIGNED_SHORT_5_5_5_1,
+            4,
+            Float,
+        ),
+        B5g5r5a1Unorm => FormatDescription::new(
+            glow::RGB5_A1,
+            glow::BGRA,
+            glow::UNSIGNED_SHORT_5_5_5_1,
+            4,
+            Float,
+        ),
+        A1r5g5b5Unorm => FormatDescription::new(
+            glow::RGB5_A1,
+            glow::BGRA,
+            glow::UNSIGNED_SHORT_1_5_5_5_REV,
+            4,
+            Float,
+        ),
+        R8Unorm => FormatDescription::new(glow::R8, glow::RED, glow::UNSIGNED_BYTE, 1, Float),
+        R8Snorm => FormatDescription::new(glow::R8_SNORM, glow::RED, glow::BYTE, 1, Float),
+        R8Uscaled => {
+            FormatDescription::new(glow::R8UI, glow::RED_INTEGER, glow::UNSIGNED_BYTE, 1, Float)
+        }
+        R8Sscaled => FormatDescription::new(glow::R8I, glow::RED_INTEGER, glow::BYTE, 1, Float),
         R8Uint => FormatDescription::new(
             glow::R8UI,
             glow::RED_INTEGER,
@@ -102,7 +213,13 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         R8Sint => FormatDescription::new(glow::R8I, glow::RED_INTEGER, glow::BYTE, 1, Integer),
-        R8Unorm => FormatDescription::new(glow::R8, glow::RED, glow::UNSIGNED_BYTE, 1, Float),
+        R8Srgb => FormatDescription::new(SR8_EXT, glow::RED, glow::UNSIGNED_BYTE, 1, Float),
+        Rg8Unorm => FormatDescription::new(glow::RG8, glow::RG, glow::UNSIGNED_BYTE, 2, Float),
+        Rg8Snorm => FormatDescription::new(glow::RG8_SNORM, glow::RG, glow::BYTE, 2, Float),
+        Rg8Uscaled => {
+            FormatDescription::new(glow::RG8UI, glow::RG_INTEGER, glow::UNSIGNED_BYTE, 2, Float)
+        }
+        Rg8Sscaled => FormatDescription::new(glow::RG8I, glow::RG_INTEGER, glow::BYTE, 2, Float),
         Rg8Uint => FormatDescription::new(
             glow::RG8UI,
             glow::RG_INTEGER,
@@ -111,6 +228,59 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         Rg8Sint => FormatDescription::new(glow::RG8I, glow::RG_INTEGER, glow::BYTE, 2, Integer),
+        Rg8Srgb => FormatDescription::new(SRG8_EXT, glow::RG, glow::UNSIGNED_BYTE, 2, Float),
+        Rgb8Unorm => FormatDescription::new(glow::RGB8, glow::RGB, glow::UNSIGNED_BYTE, 3, Float),
+        Rgb8Snorm => FormatDescription::new(glow::RGB8_SNORM, glow::RGB, glow::BYTE, 3, Float),
+        Rgb8Uscaled => FormatDescription::new(
+            glow::RGB8UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_BYTE,
+            3,
+            Float,
+        ),
+        Rgb8Sscaled => FormatDescription::new(glow::RGB8I, glow::RGB_INTEGER, glow::BYTE, 3, Float),
+        Rgb8Uint => FormatDescription::new(
+            glow::RGB8UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_BYTE,
+            3,
+            Integer,
+        ),
+        Rgb8Sint => FormatDescription::new(glow::RGB8I, glow::RGB_INTEGER, glow::BYTE, 3, Integer),
+        Rgb8Srgb => FormatDescription::new(glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE, 3, Float),
+        Bgr8Unorm => FormatDescription::new(glow::RGB8, glow::BGR, glow::UNSIGNED_BYTE, 3, Float),
+        Bgr8Snorm => FormatDescription::new(glow::RGB8_SNORM, glow::BGR, glow::BYTE, 3, Float),
+        Bgr8Uscaled => FormatDescription::new(
+            glow::RGB8UI,
+            glow::BGR_INTEGER,
+            glow::UNSIGNED_BYTE,
+            3,
+            Float,
+        ),
+        Bgr8Sscaled => FormatDescription::new(glow::RGB8I, glow::BGR_INTEGER, glow::BYTE, 3, Float),
+        Bgr8Uint => FormatDescription::new(
+            glow::RGB8UI,
+            glow::BGR_INTEGER,
+            glow::UNSIGNED_BYTE,
+            3,
+            Integer,
+        ),
+        Bgr8Sint => FormatDescription::new(glow::RGB8I, glow::BGR_INTEGER, glow::BYTE, 3, Integer),
+        Bgr8Srgb => FormatDescription::new(glow::SRGB8, glow::BGR, glow::UNSIGNED_BYTE, 3, Float),
+        Rgba8Unorm => {
+            FormatDescription::new(glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE, 4, Float)
+        }
+        Rgba8Snorm => FormatDescription::new(glow::RGBA8_SNORM, glow::RGBA, glow::BYTE, 4, Float),
+        Rgba8Uscaled => FormatDescription::new(
+            glow::RGBA8UI,
+            glow::RGBA_INTEGER,
+            glow::UNSIGNED_BYTE,
+            4,
+            Float,
+        ),
+        Rgba8Sscaled => {
+            FormatDescription::new(glow::RGBA8I, glow::RGBA_INTEGER, glow::BYTE, 4, Float)
+        }
         Rgba8Uint => FormatDescription::new(
             glow::RGBA8UI,
             glow::RGBA_INTEGER,
@@ -121,10 +291,6 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
         Rgba8Sint => {
             FormatDescription::new(glow::RGBA8I, glow::RGBA_INTEGER, glow::BYTE, 4, Integer)
         }
-        Rgba8Unorm => {
-            FormatDescription::new(glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE, 4, Float)
-        }
-        Rgb8Srgb => FormatDescription::new(glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE, 3, Float),
         Rgba8Srgb => FormatDescription::new(
             glow::SRGB8_ALPHA8,
             glow::RGBA,
@@ -133,6 +299,27 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Float,
         ),
         Bgra8Unorm => FormatDescription::new(glow::BGRA, glow::BGRA, glow::UNSIGNED_BYTE, 4, Float),
+        Bgra8Snorm => FormatDescription::new(glow::RGBA8_SNORM, glow::BGRA, glow::BYTE, 4, Float),
+        Bgra8Uscaled => FormatDescription::new(
+            glow::RGBA8UI,
+            glow::BGRA_INTEGER,
+            glow::UNSIGNED_BYTE,
+            4,
+            Float,
+        ),
+        Bgra8Sscaled => {
+            FormatDescription::new(glow::RGBA8I, glow::BGRA_INTEGER, glow::BYTE, 4, Float)
+        }
+        Bgra8Uint => FormatDescription::new(
+            glow::RGBA8UI,
+            glow::BGRA_INTEGER,
+            glow::UNSIGNED_BYTE,
+            4,
+            Integer,
+        ),
+        Bgra8Sint => {
+            FormatDescription::new(glow::RGBA8I, glow::BGRA_INTEGER, glow::BYTE, 4, Integer)
+        }
         Bgra8Srgb => FormatDescription::new(
             glow::SRGB8_ALPHA8,
             glow::BGRA,
@@ -140,6 +327,66 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             4,
             Float,
         ),
+        // Packed into 32 bits, these are laid out like `Rgba8` in little endian memory.
+        Abgr8Unorm => return describe_format(Rgba8Unorm),
+        Abgr8Snorm => return describe_format(Rgba8Snorm),
+        Abgr8Uscaled => return describe_format(Rgba8Uscaled),
+        Abgr8Sscaled => return describe_format(Rgba8Sscaled),
+        Abgr8Uint => return describe_format(Rgba8Uint),
+        Abgr8Sint => return describe_format(Rgba8Sint),
+        Abgr8Srgb => return describe_format(Rgba8Srgb),
+        A2r10g10b10Unorm => FormatDescription::new(
+            glow::RGB10_A2,
+            glow::BGRA,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Float,
+        ),
+        A2r10g10b10Uscaled => FormatDescription::new(
+            glow::RGB10_A2UI,
+            glow::BGRA_INTEGER,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Float,
+        ),
+        A2r10g10b10Uint => FormatDescription::new(
+            glow::RGB10_A2UI,
+            glow::BGRA_INTEGER,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Integer,
+        ),
+        A2b10g10r10Unorm => FormatDescription::new(
+            glow::RGB10_A2,
+            glow::RGBA,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Float,
+        ),
+        A2b10g10r10Uscaled => FormatDescription::new(
+            glow::RGB10_A2UI,
+            glow::RGBA_INTEGER,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Float,
+        ),
+        A2b10g10r10Uint => FormatDescription::new(
+            glow::RGB10_A2UI,
+            glow::RGBA_INTEGER,
+            glow::UNSIGNED_INT_2_10_10_10_REV,
+            4,
+            Integer,
+        ),
+        R16Unorm => FormatDescription::new(glow::R16, glow::RED, glow::UNSIGNED_SHORT, 1, Float),
+        R16Snorm => FormatDescription::new(glow::R16_SNORM, glow::RED, glow::SHORT, 1, Float),
+        R16Uscaled => FormatDescription::new(
+            glow::R16UI,
+            glow::RED_INTEGER,
+            glow::UNSIGNED_SHORT,
+            1,
+            Float,
+        ),
+        R16Sscaled => FormatDescription::new(glow::R16I, glow::RED_INTEGER, glow::SHORT, 1, Float),
         R16Uint => FormatDescription::new(
             glow::R16UI,
             glow::RED_INTEGER,
@@ -149,7 +396,16 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
         ),
         R16Sint => FormatDescription::new(glow::R16I, glow::RED_INTEGER, glow::SHORT, 1, Integer),
         R16Sfloat => FormatDescription::new(glow::R16F, glow::RED, glow::HALF_FLOAT, 1, Float),
-        R16Unorm => FormatDescription::new(glow::R16, glow::RED, glow::UNSIGNED_SHORT, 1, Float),
+        Rg16Unorm => FormatDescription::new(glow::RG16, glow::RG, glow::UNSIGNED_SHORT, 2, Float),
+        Rg16Snorm => FormatDescription::new(glow::RG16_SNORM, glow::RG, glow::SHORT, 2, Float),
+        Rg16Uscaled => FormatDescription::new(
+            glow::RG16UI,
+            glow::RG_INTEGER,
+            glow::UNSIGNED_SHORT,
+            2,
+            Float,
+        ),
+        Rg16Sscaled => FormatDescription::new(glow::RG16I, glow::RG_INTEGER, glow::SHORT, 2, Float),
         Rg16Uint => FormatDescription::new(
             glow::RG16UI,
             glow::RG_INTEGER,
@@ -158,8 +414,48 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         Rg16Sint => FormatDescription::new(glow::RG16I, glow::RG_INTEGER, glow::SHORT, 2, Integer),
-        Rg16Unorm => FormatDescription::new(glow::RG16, glow::RG, glow::UNSIGNED_SHORT, 2, Float),
         Rg16Sfloat => FormatDescription::new(glow::RG16F, glow::RG, glow::HALF_FLOAT, 2, Float),
+        Rgb16Unorm => {
+            FormatDescription::new(glow::RGB16, glow::RGB, glow::UNSIGNED_SHORT, 3, Float)
+        }
+        Rgb16Snorm => FormatDescription::new(glow::RGB16_SNORM, glow::RGB, glow::SHORT, 3, Float),
+        Rgb16Uscaled => FormatDescription::new(
+            glow::RGB16UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_SHORT,
+            3,
+            Float,
+        ),
+        Rgb16Sscaled => {
+            FormatDescription::new(glow::RGB16I, glow::RGB_INTEGER, glow::SHORT, 3, Float)
+        }
+        Rgb16Uint => FormatDescription::new(
+            glow::RGB16UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_SHORT,
+            3,
+            Integer,
+        ),
+        Rgb16Sint => {
+            FormatDescription::new(glow::RGB16I, glow::RGB_INTEGER, glow::SHORT, 3, Integer)
+        }
+        Rgb16Sfloat => FormatDescription::new(glow::RGB16F, glow::RGB, glow::HALF_FLOAT, 3, Float),
+        Rgba16Unorm => {
+            FormatDescription::new(glow::RGBA16, glow::RGBA, glow::UNSIGNED_SHORT, 4, Float)
+        }
+        Rgba16Snorm => {
+            FormatDescription::new(glow::RGBA16_SNORM, glow::RGBA, glow::SHORT, 4, Float)
+        }
+        Rgba16Uscaled => FormatDescription::new(
+            glow::RGBA16UI,
+            glow::RGBA_INTEGER,
+            glow::UNSIGNED_SHORT,
+            4,
+            Float,
+        ),
+        Rgba16Sscaled => {
+            FormatDescription::new(glow::RGBA16I, glow::RGBA_INTEGER, glow::SHORT, 4, Float)
+        }
         Rgba16Uint => FormatDescription::new(
             glow::RGBA16UI,
             glow::RGBA_INTEGER,
@@ -173,9 +469,6 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
         Rgba16Sfloat => {
             FormatDescription::new(glow::RGBA16F, glow::RGBA, glow::HALF_FLOAT, 4, Float)
         }
-        Rgba16Unorm => {
-            FormatDescription::new(glow::RGBA16, glow::RGBA, glow::UNSIGNED_SHORT, 4, Float)
-        }
         R32Uint => FormatDescription::new(
             glow::R32UI,
             glow::RED_INTEGER,
@@ -192,7 +485,7 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             2,
             Integer,
         ),
-        Rg32Sint => FormatDescription::new(glow::R32I, glow::RG_INTEGER, glow::INT, 2, Integer),
+        Rg32Sint => FormatDescription::new(glow::RG32I, glow::RG_INTEGER, glow::INT, 2, Integer),
         Rg32Sfloat => FormatDescription::new(glow::RG32F, glow::RG, glow::FLOAT, 2, Float),
         Rgb32Uint => FormatDescription::new(
             glow::RGB32UI,
@@ -214,19 +507,32 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             FormatDescription::new(glow::RGBA32I, glow::RGBA_INTEGER, glow::INT, 4, Integer)
         }
         Rgba32Sfloat => FormatDescription::new(glow::RGBA32F, glow::RGBA, glow::FLOAT, 4, Float),
-        S8Uint => FormatDescription::new(glow::R8, glow::RED, glow::UNSIGNED_BYTE, 1, Integer),
+        B10g11r11Ufloat => FormatDescription::new(
+            glow::R11F_G11F_B10F,
+            glow::RGB,
+            glow::UNSIGNED_INT_10F_11F_11F_REV,
+            3,
+            Float,
+        ),
+        E5b9g9r9Ufloat => FormatDescription::new(
+            glow::RGB9_E5,
+            glow::RGB,
+            glow::UNSIGNED_INT_5_9_9_9_REV,
+            3,
+            Float,
+        ),
         D16Unorm => FormatDescription::new(
             glow::DEPTH_COMPONENT16,
             glow::DEPTH_COMPONENT,
-            glow::UNSIGNED_NORMALIZED,
+            glow::UNSIGNED_SHORT,
             1,
             Float,
         ),
-        D24UnormS8Uint => FormatDescription::new(
-            glow::DEPTH24_STENCIL8,
-            glow::DEPTH_STENCIL,
+        X8D24Unorm => FormatDescription::new(
+            glow::DEPTH_COMPONENT24,
+            glow::DEPTH_COMPONENT,
             glow::UNSIGNED_INT,
-            2,
+            1,
             Float,
         ),
         D32Sfloat => FormatDescription::new(
@@ -236,25 +542,168 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             1,
             Float,
         ),
-        D32SfloatS8Uint => FormatDescription::new(
-            glow::DEPTH32F_STENCIL8,
-            glow::DEPTH_STENCIL,
-            glow::UNSIGNED_INT,
+        S8Uint => FormatDescription::new(
+            glow::STENCIL_INDEX8,
+            glow::STENCIL_INDEX,
+            glow::UNSIGNED_BYTE,
             1,
+            Integer,
+        ),
+        D24UnormS8Uint => FormatDescription::new(
+            glow::DEPTH24_STENCIL8,
+            glow::DEPTH_STENCIL,
+            glow::UNSIGNED_INT_24_8,
+            2,
             Float,
         ),
-        X8D24Unorm => FormatDescription::new(
-            glow::DEPTH_COMPONENT24,
+        D32SfloatS8Uint => FormatDescription::new(
+            glow::DEPTH32F_STENCIL8,
             glow::DEPTH_STENCIL,
-            glow::UNSIGNED_NORMALIZED,
+            glow::FLOAT_32_UNSIGNED_INT_24_8_REV,
             2,
             Float,
         ),
-
+        // Compressed formats are uploaded with their internal format.
+        Bc1RgbUnorm => FormatDescription::compressed(COMPRESSED_RGB_S3TC_DXT1_EXT, 3),
+        Bc1RgbSrgb => FormatDescription::compressed(COMPRESSED_SRGB_S3TC_DXT1_EXT, 3),
+        Bc1RgbaUnorm => FormatDescription::compressed(COMPRESSED_RGBA_S3TC_DXT1_EXT, 4),
+        Bc1RgbaSrgb => FormatDescription::compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 4),
+        Bc2Unorm => FormatDescription::compressed(COMPRESSED_RGBA_S3TC_DXT3_EXT, 4),
+        Bc2Srgb => FormatDescription::compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 4),
+        Bc3Unorm => FormatDescription::compressed(COMPRESSED_RGBA_S3TC_DXT5_EXT, 4),
+        Bc3Srgb => FormatDescription::compressed(COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 4),
+        Bc4Unorm => FormatDescription::compressed(glow::COMPRESSED_RED_RGTC1, 1),
+        Bc4Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RED_RGTC1, 1),
+        Bc5Unorm => FormatDescription::compressed(glow::COMPRESSED_RG_RGTC2, 2),
+        Bc5Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RG_RGTC2, 2),
+        Bc6hUfloat => FormatDescription::compressed(glow::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 3),
+        Bc6hSfloat => FormatDescription::compressed(glow::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 3),
+        Bc7Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_BPTC_UNORM, 4),
+        Bc7Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 4),
+        Etc2R8g8b8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGB8_ETC2, 3),
+        Etc2R8g8b8Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ETC2, 3),
+        Etc2R8g8b8a1Unorm => {
+            FormatDescription::compressed(glow::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2, 4)
+        }
+        Etc2R8g8b8a1Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2, 4)
+        }
+        Etc2R8g8b8a8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA8_ETC2_EAC, 4),
+        Etc2R8g8b8a8Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, 4)
+        }
+        EacR11Unorm => FormatDescription::compressed(glow::COMPRESSED_R11_EAC, 1),
+        EacR11Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_R11_EAC, 1),
+        EacR11g11Unorm => FormatDescription::compressed(glow::COMPRESSED_RG11_EAC, 2),
+        EacR11g11Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RG11_EAC, 2),
+        Astc4x4Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR, 4),
+        Astc4x4Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR, 4),
+        Astc5x4Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 1, 4),
+        Astc5x4Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 1, 4),
+        Astc5x5Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 2, 4),
+        Astc5x5Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 2, 4),
+        Astc6x5Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 3, 4),
+        Astc6x5Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 3, 4),
+        Astc6x6Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 4, 4),
+        Astc6x6Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 4, 4),
+        Astc8x5Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 5, 4),
+        Astc8x5Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 5, 4),
+        Astc8x6Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 6, 4),
+        Astc8x6Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 6, 4),
+        Astc8x8Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 7, 4),
+        Astc8x8Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 7, 4),
+        Astc10x5Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 8, 4),
+        Astc10x5Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 8, 4),
+        Astc10x6Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 9, 4),
+        Astc10x6Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 9, 4),
+        Astc10x8Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 10, 4),
+        Astc10x8Srgb => FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 10, 4),
+        Astc10x10Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 11, 4),
+        Astc10x10Srgb => {
+            FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 11, 4)
+        }
+        Astc12x10Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 12, 4),
+        Astc12x10Srgb => {
+            FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 12, 4)
+        }
+        Astc12x12Unorm => FormatDescription::compressed(COMPRESSED_RGBA_ASTC_4X4_KHR + 13, 4),
+        Astc12x12Srgb => {
+            FormatDescription::compressed(COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + 13, 4)
+        }
+        // Signed 10-bit, 64-bit and 16-bit depth with stencil formats can't be textures in GL.
+        A2r10g10b10Snorm | A2r10g10b10Sscaled | A2r10g10b10Sint | A2b10g10r10Snorm
+        | A2b10g10r10Sscaled | A2b10g10r10Sint => return None,
+        R64Uint | R64Sint | R64Sfloat | Rg64Uint | Rg64Sint | Rg64Sfloat | Rgb64Uint
+        | Rgb64Sint | Rgb64Sfloat | Rgba64Uint | Rgba64Sint | Rgba64Sfloat => return None,
+        D16UnormS8Uint => return None,
         _ => return None,
     })
 }
 
+/// Whether the format is in the internal formats of buffer textures,
+/// which both GL 3.1 and ES 3.2 accept.
+pub fn is_buffer_texture_format(format: Format) -> bool {
+    use hal::format::Format::*;
+    match format {
+        R8Unorm | R8Uint | R8Sint | R16Uint | R16Sint | R16Sfloat | R32Uint | R32Sint
+        | R32Sfloat | Rg8Unorm | Rg8Uint | Rg8Sint | Rg16Uint | Rg16Sint | Rg16Sfloat
+        | Rg32Uint | Rg32Sint | Rg32Sfloat | Rgba8Unorm | Rgba8Uint | Rgba8Sint | Rgba16Uint
+        | Rgba16Sint | Rgba16Sfloat | Rgba32Uint | Rgba32Sint | Rgba32Sfloat => true,
+        _ => false,
+    }
+}
+
+/// Checks if a format of the buffer textures can also be bound as an image,
+/// restricted to the image formats that GLES 3.1 guarantees.
+pub fn is_image_buffer_format(format: Format) -> bool {
+    use hal::format::Format::*;
+    match format {
+        R32Uint | R32Sint | R32Sfloat | Rgba8Unorm | Rgba8Uint | Rgba8Sint | Rgba16Uint
+        | Rgba16Sint | Rgba16Sfloat | Rgba32Uint | Rgba32Sint | Rgba32Sfloat => true,
+        _ => false,
+    }
+}
+
+/// Returns the GL query targets for the statistics, in the order of the results.
+pub fn pipeline_statistics_to_gl(statistics: query::PipelineStatistic) -> Vec<u32> {
+    use hal::query::PipelineStatistic as Ps;
+    [
+        (Ps::INPUT_ASSEMBLY_VERTICES, glow::VERTICES_SUBMITTED),
+        (Ps::INPUT_ASSEMBLY_PRIMITIVES, glow::PRIMITIVES_SUBMITTED),
+        (
+            Ps::VERTEX_SHADER_INVOCATIONS,
+            glow::VERTEX_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::GEOMETRY_SHADER_INVOCATIONS,
+            glow::GEOMETRY_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::GEOMETRY_SHADER_PRIMITIVES,
+            glow::GEOMETRY_SHADER_PRIMITIVES_EMITTED,
+        ),
+        (Ps::CLIPPING_INVOCATIONS, glow::CLIPPING_INPUT_PRIMITIVES),
+        (Ps::CLIPPING_PRIMITIVES, glow::CLIPPING_OUTPUT_PRIMITIVES),
+        (
+            Ps::FRAGMENT_SHADER_INVOCATIONS,
+            glow::FRAGMENT_SHADER_INVOCATIONS,
+        ),
+        (Ps::HULL_SHADER_PATCHES, glow::TESS_CONTROL_SHADER_PATCHES),
+        (
+            Ps::DOMAIN_SHADER_INVOCATIONS,
+            glow::TESS_EVALUATION_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::COMPUTE_SHADER_INVOCATIONS,
+            glow::COMPUTE_SHADER_INVOCATIONS,
+        ),
+    ]
+    .iter()
+    .filter(|&&(statistic, _)| statistics.contains(statistic))
+    .map(|&(_, target)| target)
+    .collect()
+}
+
 #[cfg(feature = "cross")]
 pub fn map_naga_stage_to_cross(stage: naga::ShaderStage) -> spirv_cross::spirv::ExecutionModel {
     use spirv_cross::spirv::ExecutionModel as Em;
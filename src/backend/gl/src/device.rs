@@ -18,11 +18,55 @@ use hal::{
 use glow::HasContext;
 use parking_lot::Mutex;
 
-use std::{ops::Range, slice, sync::Arc};
+use std::{
+    mem,
+    ops::Range,
+    slice,
+    sync::{atomic::Ordering, Arc},
+};
 
 #[cfg(feature = "cross")]
 type CrossAst = spirv_cross::spirv::Ast<spirv_cross::glsl::Target>;
 
+/// Reads the results of a query, made of one GL query object per counter,
+/// into `data` according to the flags. Returns `true` if they were available.
+///
+/// Values are left untouched if the results are not available, unless partial
+/// results are requested, in which case zeros are written.
+pub(crate) unsafe fn read_query_results(
+    gl: &GlContainer,
+    objects: &[n::Query],
+    flags: query::ResultFlags,
+    data: &mut [u8],
+) -> bool {
+    let available = flags.contains(query::ResultFlags::WAIT)
+        || objects
+            .iter()
+            .all(|&query| gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) != 0);
+    let mut write = |index: usize, value: u64| {
+        if flags.contains(query::ResultFlags::BITS_64) {
+            data[index * 8..index * 8 + 8].copy_from_slice(&value.to_ne_bytes());
+        } else {
+            data[index * 4..index * 4 + 4].copy_from_slice(&(value as u32).to_ne_bytes());
+        }
+    };
+
+    if available || flags.contains(query::ResultFlags::PARTIAL) {
+        for (index, &query) in objects.iter().enumerate() {
+            let value = if available {
+                gl.get_query_parameter_u64(query, glow::QUERY_RESULT)
+            } else {
+                0
+            };
+            write(index, value);
+        }
+    }
+    if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+        write(objects.len(), available as u64);
+    }
+    available
+}
+
 fn create_fbo_internal(
     share: &Starc<Share>,
 ) -> Option<<GlContext as glow::HasContext>::Framebuffer> {
@@ -36,10 +80,25 @@ fn create_fbo_internal(
     }
 }
 
+/// Advance the counter of a timeline semaphore past the pending submissions that completed.
+unsafe fn poll_timeline(gl: &GlContext, timeline: &mut n::Timeline) -> u64 {
+    let completed = timeline
+        .pending
+        .iter()
+        .take_while(|&&(_, sync)| gl.get_sync_status(sync) == glow::SIGNALED)
+        .count();
+    for (value, sync) in timeline.pending.drain(..completed) {
+        gl.delete_sync(sync);
+        timeline.value = timeline.value.max(value);
+    }
+    timeline.value
+}
+
 struct CompilationContext<'a> {
     layout: &'a n::PipelineLayout,
     sampler_map: &'a mut n::SamplerBindMap,
     name_binding_map: &'a mut FastHashMap<String, (n::BindingRegister, u8)>,
+    push_constants_slot: Option<u8>,
 }
 
 impl<'a> CompilationContext<'a> {
@@ -48,6 +107,7 @@ impl<'a> CompilationContext<'a> {
             layout: self.layout,
             sampler_map: self.sampler_map,
             name_binding_map: self.name_binding_map,
+            push_constants_slot: self.push_constants_slot,
         }
     }
 }
@@ -140,16 +200,225 @@ impl Device {
         }
     }
 
+    /// Returns the key of a program in the pipeline caches,
+    /// or `None` if one of the shader modules can't be identified.
+    ///
+    /// Modules created from naga have no SPIR-V to hash, so the programs
+    /// using them are always built from scratch and never cached.
+    fn program_cache_key(
+        &self,
+        shaders: &[(naga::ShaderStage, Option<&pso::EntryPoint<B>>)],
+        layout: &n::PipelineLayout,
+    ) -> Option<u64> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = fxhash::FxHasher64::default();
+        self.features.bits().hash(&mut hasher);
+        for &(stage, point_maybe) in shaders {
+            if let Some(point) = point_maybe {
+                stage.hash(&mut hasher);
+                point.module.hash?.hash(&mut hasher);
+                point.module.prefer_naga.hash(&mut hasher);
+                point.entry.hash(&mut hasher);
+                point.specialization.constants.hash(&mut hasher);
+                point.specialization.data.hash(&mut hasher);
+            }
+        }
+        for set in layout.sets.iter() {
+            set.bindings.hash(&mut hasher);
+            // the generated shaders depend on the binding types as well
+            for binding in set.layout.bindings.iter() {
+                binding.binding.hash(&mut hasher);
+                binding.ty.hash(&mut hasher);
+                binding.count.hash(&mut hasher);
+                binding.immutable_samplers.hash(&mut hasher);
+            }
+        }
+        layout.sampler_map.hash(&mut hasher);
+        layout.push_constant_ranges.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Identifies the driver that produced the program binaries of a pipeline cache.
+    fn pipeline_cache_driver(&self) -> String {
+        let info = &self.share.info;
+        format!(
+            "{}; {}; {:?}",
+            info.platform_name.vendor, info.platform_name.renderer, info.version
+        )
+    }
+
+    /// Returns the binary of a linked program, if the driver supports it.
+    fn get_program_binary(&self, program: glow::Program) -> Option<(u32, Vec<u8>)> {
+        if !self.share.private_caps.program_binary {
+            return None;
+        }
+        let (format, binary) = unsafe { self.share.context.get_program_binary(program) };
+        if binary.is_empty() {
+            return None;
+        }
+        Some((format, binary))
+    }
+
+    fn link_program(&self, shaders: &[n::Shader]) -> Result<glow::Program, pso::CreationError> {
+        let gl = &self.share.context;
+        let program = unsafe { gl.create_program().unwrap() };
+
+        unsafe {
+            for &shader in shaders {
+                gl.attach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+            if self.share.private_caps.program_binary {
+                gl.program_parameter_i32(
+                    program,
+                    glow::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    glow::TRUE as i32,
+                );
+            }
+            gl.link_program(program);
+        }
+        log::info!("\tLinked program {:?}", program);
+        if let Err(err) = self.share.check() {
+            panic!("Error linking program: {:?}", err);
+        }
+
+        let linked_ok = unsafe { gl.get_program_link_status(program) };
+        let log = unsafe { gl.get_program_info_log(program) };
+        if !linked_ok {
+            let error = format!("Program {:?} linking error:{}", program, log);
+            return Err(pso::CreationError::ShaderCreationError(
+                pso::ShaderStageFlags::GRAPHICS,
+                error,
+            ));
+        }
+        if !log.is_empty() {
+            log::warn!("\tLog: {}", log);
+        }
+        Ok(program)
+    }
+
+    /// Assigns the bindings of the resources by name, unless they are explicit in the shaders.
+    fn bind_program_names(
+        &self,
+        program: glow::Program,
+        name_bindings: &[(String, n::BindingRegister, u8)],
+    ) {
+        let gl = &self.share.context;
+        if self
+            .share
+            .legacy_features
+            .contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER)
+        {
+            return;
+        }
+        unsafe {
+            gl.use_program(Some(program));
+        }
+        // Arrays of textures take consecutive texture units.
+        let array_sizes = unsafe {
+            (0..gl.get_active_uniforms(program))
+                .filter_map(|index| gl.get_active_uniform(program, index))
+                .filter_map(|uniform| {
+                    let name = uniform.name.strip_suffix("[0]")?.to_string();
+                    Some((name, uniform.size))
+                })
+                .collect::<FastHashMap<_, _>>()
+        };
+        for &(ref name, register, slot) in name_bindings.iter() {
+            log::trace!("Get binding {:?} from program {:?}", name, program);
+            match register {
+                n::BindingRegister::Textures => unsafe {
+                    let loc = gl.get_uniform_location(program, name).unwrap();
+                    match array_sizes.get(name) {
+                        Some(&size) => {
+                            let slots = (0..size).map(|i| slot as i32 + i).collect::<Vec<_>>();
+                            gl.uniform_1_i32_slice(Some(&loc), &slots);
+                        }
+                        None => gl.uniform_1_i32(Some(&loc), slot as _),
+                    }
+                },
+                n::BindingRegister::UniformBuffers => unsafe {
+                    let index = gl.get_uniform_block_index(program, name).unwrap();
+                    gl.uniform_block_binding(program, index, slot as _);
+                },
+                n::BindingRegister::StorageBuffers => unsafe {
+                    let index = gl.get_shader_storage_block_index(program, name).unwrap();
+                    gl.shader_storage_block_binding(program, index, slot as _);
+                },
+            }
+        }
+    }
+
+    /// Creates a program from a pipeline cache entry, preferably from its binary.
+    /// Falls back to compiling the cached GLSL, which skips the translation of
+    /// the SPIR-V, and then updates the binary of the entry.
+    fn restore_program(&self, cached: &mut n::CachedProgram) -> Option<glow::Program> {
+        let gl = &self.share.context;
+        if let Some((format, ref buffer)) = cached.binary {
+            if self.share.private_caps.program_binary {
+                let program = unsafe { gl.create_program().unwrap() };
+                unsafe {
+                    gl.program_binary(program, format, buffer);
+                }
+                if unsafe { gl.get_program_link_status(program) } {
+                    log::info!("\tRestored program {:?} from its binary", program);
+                    self.bind_program_names(program, &cached.name_bindings);
+                    return Some(program);
+                }
+                log::info!("\tProgram binary of format {:x} was rejected", format);
+                unsafe {
+                    gl.delete_program(program);
+                }
+            }
+        }
+
+        let mut shaders = Vec::with_capacity(cached.sources.len());
+        for &(stage, ref source) in cached.sources.iter() {
+            match Self::create_shader_module_raw(gl, source, stage) {
+                Ok(shader) => shaders.push(shader),
+                Err(err) => {
+                    log::warn!("Cached {:?} shader failed to compile: {:?}", stage, err);
+                    for shader in shaders {
+                        unsafe { gl.delete_shader(shader) };
+                    }
+                    return None;
+                }
+            }
+        }
+        let program = match self.link_program(&shaders) {
+            Ok(program) => program,
+            Err(err) => {
+                log::warn!("Cached program failed to link: {:?}", err);
+                return None;
+            }
+        };
+        self.bind_program_names(program, &cached.name_bindings);
+        cached.binary = self.get_program_binary(program);
+        Some(program)
+    }
+
     fn create_shader_program(
         &self,
         shaders: &[(naga::ShaderStage, Option<&pso::EntryPoint<B>>)],
         layout: &n::PipelineLayout,
+        cache: Option<&n::PipelineCache>,
     ) -> Result<(glow::Program, n::SamplerBindMap), pso::CreationError> {
         let gl = &self.share.context;
-        let program = unsafe { gl.create_program().unwrap() };
+        let cache_key = match cache {
+            Some(_) => self.program_cache_key(shaders, layout),
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            if let Some(cached) = cache.programs.lock().get_mut(&key) {
+                if let Some(program) = self.restore_program(cached) {
+                    return Ok((program, cached.sampler_map));
+                }
+            }
+        }
 
         let mut name_binding_map = FastHashMap::<String, (n::BindingRegister, u8)>::default();
-        let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
+        let mut sampler_map = layout.sampler_map;
 
         let mut has_vertex_stage = false;
         let mut has_fragment_stage = false;
@@ -157,7 +426,10 @@ impl Device {
             layout,
             sampler_map: &mut sampler_map,
             name_binding_map: &mut name_binding_map,
+            push_constants_slot: self.share.private_caps.push_constants_slot,
         };
+        let mut compiled = Vec::with_capacity(shaders.len() + 1);
+        let mut sources = Vec::with_capacity(shaders.len() + 1);
 
         for &(stage, point_maybe) in shaders {
             if let Some(point) = point_maybe {
@@ -167,15 +439,18 @@ impl Device {
                     naga::ShaderStage::Compute => (),
                 }
 
-                let shader = self
-                    .compile_shader(point, stage, context.reborrow())
-                    .map_err(|err| {
+                match self.compile_shader(point, stage, context.reborrow()) {
+                    Ok((shader, source)) => {
+                        compiled.push(shader);
+                        sources.push((stage, source));
+                    }
+                    Err(err) => {
+                        for shader in compiled {
+                            unsafe { gl.delete_shader(shader) };
+                        }
                         let error = format!("{} shader compilation failed: {:?}", err, stage);
-                        pso::CreationError::ShaderCreationError(stage.into(), error)
-                    })?;
-                unsafe {
-                    gl.attach_shader(program, shader);
-                    gl.delete_shader(shader);
+                        return Err(pso::CreationError::ShaderCreationError(stage.into(), error));
+                    }
                 }
             }
         }
@@ -200,58 +475,25 @@ impl Device {
                 naga::ShaderStage::Fragment,
             )
             .unwrap();
-            unsafe {
-                gl.attach_shader(program, shader);
-                gl.delete_shader(shader);
-            }
-        }
-
-        unsafe {
-            gl.link_program(program);
-        }
-        log::info!("\tLinked program {:?}", program);
-        if let Err(err) = self.share.check() {
-            panic!("Error linking program: {:?}", err);
+            compiled.push(shader);
+            sources.push((naga::ShaderStage::Fragment, shader_src));
         }
 
-        let linked_ok = unsafe { gl.get_program_link_status(program) };
-        let log = unsafe { gl.get_program_info_log(program) };
-        if !linked_ok {
-            let error = format!("Program {:?} linking error:{}", program, log);
-            return Err(pso::CreationError::ShaderCreationError(
-                pso::ShaderStageFlags::GRAPHICS,
-                error,
-            ));
-        }
-        if !log.is_empty() {
-            log::warn!("\tLog: {}", log);
-        }
-
-        if !self
-            .share
-            .legacy_features
-            .contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER)
-        {
-            unsafe {
-                gl.use_program(Some(program));
-            }
-            for (name, &(register, slot)) in name_binding_map.iter() {
-                log::trace!("Get binding {:?} from program {:?}", name, program);
-                match register {
-                    n::BindingRegister::Textures => unsafe {
-                        let loc = gl.get_uniform_location(program, name).unwrap();
-                        gl.uniform_1_i32(Some(&loc), slot as _);
-                    },
-                    n::BindingRegister::UniformBuffers => unsafe {
-                        let index = gl.get_uniform_block_index(program, name).unwrap();
-                        gl.uniform_block_binding(program, index, slot as _);
-                    },
-                    n::BindingRegister::StorageBuffers => unsafe {
-                        let index = gl.get_shader_storage_block_index(program, name).unwrap();
-                        gl.shader_storage_block_binding(program, index, slot as _);
-                    },
-                }
-            }
+        let program = self.link_program(&compiled)?;
+        let name_bindings = name_binding_map
+            .into_iter()
+            .map(|(name, (register, slot))| (name, register, slot))
+            .collect::<Vec<_>>();
+        self.bind_program_names(program, &name_bindings);
+
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            let cached = n::CachedProgram {
+                sources,
+                binary: self.get_program_binary(program),
+                sampler_map,
+                name_bindings,
+            };
+            cache.programs.lock().insert(key, cached);
         }
 
         Ok((program, sampler_map))
@@ -296,6 +538,20 @@ impl Device {
         }
     }
 
+    /// Label a GL object for the debugging tools, with `KHR_debug`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_object_label(&self, identifier: u32, name: u32, label: &str) {
+        let gl = &self.share.context;
+        if gl.supports_debug() {
+            unsafe { gl.object_label(identifier, name, Some(label)) };
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn set_object_label<T>(&self, _identifier: u32, _name: T, _label: &str) {
+        // WebGL doesn't expose `KHR_debug`.
+    }
+
     pub(crate) fn bind_target(gl: &GlContainer, point: u32, attachment: u32, view: &n::ImageView) {
         match *view {
             n::ImageView::Renderbuffer { raw: rb, .. } => unsafe {
@@ -315,6 +571,21 @@ impl Device {
                     sub.level_start as _,
                 );
             },
+            n::ImageView::Texture {
+                target: glow::TEXTURE_CUBE_MAP,
+                raw,
+                ref sub,
+                is_3d: true,
+            } => unsafe {
+                // Faces of cube maps are only attachable as layers on recent versions.
+                gl.framebuffer_texture_2d(
+                    point,
+                    attachment,
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + sub.layer_start as u32,
+                    Some(raw),
+                    sub.level_start as _,
+                );
+            },
             n::ImageView::Texture {
                 target: _,
                 raw,
@@ -346,7 +617,40 @@ impl Device {
     }
 
     #[cfg(feature = "cross")]
-    fn set_push_const_layout(&self, _ast: &mut CrossAst) -> Result<(), d::ShaderError> {
+    fn set_push_const_layout(
+        &self,
+        ast: &mut CrossAst,
+        nb_map: &mut FastHashMap<String, (n::BindingRegister, u8)>,
+    ) -> Result<(), d::ShaderError> {
+        use spirv_cross::spirv::Decoration;
+
+        let res = ast.get_shader_resources().unwrap();
+        if res.push_constant_buffers.is_empty() {
+            return Ok(());
+        }
+        let slot = match self.share.private_caps.push_constants_slot {
+            Some(slot) => slot,
+            None => {
+                return Err(d::ShaderError::CompilationFailed(
+                    "Push constants require uniform buffers".into(),
+                ))
+            }
+        };
+
+        // Push constants are emitted as a uniform block, and the stages
+        // declaring the same block share it after linking.
+        for res in res.push_constant_buffers.iter() {
+            if self
+                .share
+                .legacy_features
+                .contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER)
+            {
+                ast.set_decoration(res.id, Decoration::Binding, slot as u32)
+                    .unwrap();
+            } else {
+                nb_map.insert(res.name.clone(), (n::BindingRegister::UniformBuffers, slot));
+            }
+        }
         Ok(())
     }
 
@@ -393,6 +697,7 @@ impl Device {
         };
         compile_options.vertex.invert_y = !self.features.contains(hal::Features::NDC_Y_UP);
         compile_options.force_zero_initialized_variables = true;
+        compile_options.emit_push_constant_as_uniform_buffer = true;
         compile_options.entry_point = Some((
             entry_point.to_string(),
             conv::map_naga_stage_to_cross(stage),
@@ -543,6 +848,17 @@ impl Device {
             let register = match var.class {
                 naga::StorageClass::Uniform => n::BindingRegister::UniformBuffers,
                 naga::StorageClass::Storage => n::BindingRegister::StorageBuffers,
+                naga::StorageClass::PushConstant => {
+                    if let (Some(slot), Some(name)) = (
+                        context.push_constants_slot,
+                        reflection_info.uniforms.get(&handle),
+                    ) {
+                        context
+                            .name_binding_map
+                            .insert(name.clone(), (n::BindingRegister::UniformBuffers, slot));
+                    }
+                    continue;
+                }
                 _ => continue,
             };
             //TODO: make Naga reflect all the names, not just textures
@@ -559,12 +875,20 @@ impl Device {
         }
 
         for (name, mapping) in reflection_info.texture_mapping {
-            let texture_linear_index = match module.global_variables[mapping.texture].binding {
-                Some(ref br) => {
-                    context.layout.sets[br.group as usize].bindings[br.binding as usize]
-                }
-                ref other => panic!("Unexpected texture binding {:?}", other),
-            };
+            let (texture_linear_index, count) =
+                match module.global_variables[mapping.texture].binding {
+                    Some(ref br) => {
+                        let set = &context.layout.sets[br.group as usize];
+                        let count = set
+                            .layout
+                            .bindings
+                            .iter()
+                            .find(|b| b.binding == br.binding)
+                            .map_or(1, |b| b.count);
+                        (set.bindings[br.binding as usize], count)
+                    }
+                    ref other => panic!("Unexpected texture binding {:?}", other),
+                };
             context
                 .name_binding_map
                 .insert(name, (n::BindingRegister::Textures, texture_linear_index));
@@ -575,7 +899,11 @@ impl Device {
                     }
                     ref other => panic!("Unexpected sampler binding {:?}", other),
                 };
-                context.sampler_map[texture_linear_index as usize] = Some(sampler_linear_index);
+                // all the textures of an array are sampled with the same sampler
+                for i in 0..count {
+                    context.sampler_map[texture_linear_index as usize + i] =
+                        Some(sampler_linear_index);
+                }
             }
         }
     }
@@ -602,7 +930,7 @@ impl Device {
         shader: &d::NagaShader,
         options: &naga::back::glsl::Options,
         context: CompilationContext,
-    ) -> Result<n::Shader, d::ShaderError> {
+    ) -> Result<(n::Shader, String), d::ShaderError> {
         let mut output = Vec::new();
         let mut writer =
             naga::back::glsl::Writer::new(&mut output, &shader.module, &shader.info, options)
@@ -630,6 +958,7 @@ impl Device {
                 let source = String::from_utf8(output).unwrap();
                 log::debug!("Naga generated shader:\n{}", source);
                 Self::create_shader_module_raw(gl, &source, options.shader_stage)
+                    .map(|shader| (shader, source))
             }
             Err(e) => {
                 log::warn!("Naga GLSL write: {}", e);
@@ -643,7 +972,7 @@ impl Device {
         ep: &pso::EntryPoint<B>,
         stage: naga::ShaderStage,
         mut context: CompilationContext,
-    ) -> Result<n::Shader, d::ShaderError> {
+    ) -> Result<(n::Shader, String), d::ShaderError> {
         let naga_options = naga::back::glsl::Options {
             version: {
                 use naga::back::glsl::Version;
@@ -676,13 +1005,14 @@ impl Device {
             auxil::spirv_cross_specialize_ast(&mut ast, &ep.specialization).unwrap();
             self.remap_bindings(&mut ast, context.layout, context.name_binding_map);
             self.combine_separate_images_and_samplers(&mut ast, context.reborrow());
-            self.set_push_const_layout(&mut ast).unwrap();
+            self.set_push_const_layout(&mut ast, context.name_binding_map)?;
 
             let glsl = self
                 .translate_spirv_cross(&mut ast, stage, ep.entry)
                 .unwrap();
             log::debug!("SPIRV-Cross generated shader:\n{}", glsl);
-            result = Self::create_shader_module_raw(&self.share.context, &glsl, stage);
+            result = Self::create_shader_module_raw(&self.share.context, &glsl, stage)
+                .map(|shader| (shader, glsl));
         }
         if result.is_err() && !ep.module.prefer_naga {
             if let Some(ref shader) = ep.module.naga {
@@ -772,7 +1102,7 @@ impl d::Device<B> for Device {
             .properties
             .contains(memory::Properties::CPU_CACHED);
 
-        match memory_role {
+        let memory = match memory_role {
             MemoryUsage::Buffer(buffer_usage) => {
                 let gl = &self.share.context;
                 let target = if buffer_usage.contains(buffer::Usage::INDEX)
@@ -836,26 +1166,31 @@ impl d::Device<B> for Device {
                     panic!("Error allocating memory buffer {:?}", err);
                 }
 
-                Ok(n::Memory {
+                n::Memory {
                     properties: memory_type.properties,
+                    heap_index: memory_type.heap_index,
                     buffer: Some((raw, target)),
                     size,
                     map_flags,
                     emulate_map_allocation: None,
-                })
+                }
             }
 
             MemoryUsage::Image => {
                 assert!(is_device_local_memory);
-                Ok(n::Memory {
+                n::Memory {
                     properties: memory::Properties::DEVICE_LOCAL,
+                    heap_index: memory_type.heap_index,
                     buffer: None,
                     size,
                     map_flags: 0,
                     emulate_map_allocation: None,
-                })
+                }
             }
-        }
+        };
+
+        self.share.heap_usage[memory.heap_index].fetch_add(size, Ordering::Relaxed);
+        Ok(memory)
     }
 
     unsafe fn create_command_pool(
@@ -910,10 +1245,15 @@ impl d::Device<B> for Device {
                 let color_attachments = subpass.colors.iter().map(|&(index, _)| index).collect();
 
                 let depth_stencil = subpass.depth_stencil.map(|ds| ds.0);
+                let resolve_attachments =
+                    subpass.resolves.iter().map(|&(index, _)| index).collect();
+                let input_attachments = subpass.inputs.iter().map(|&(index, _)| index).collect();
 
                 n::SubpassDesc {
                     color_attachments,
                     depth_stencil,
+                    resolve_attachments,
+                    input_attachments,
                 }
             })
             .collect();
@@ -927,10 +1267,11 @@ impl d::Device<B> for Device {
     unsafe fn create_pipeline_layout<'a, Is, Ic>(
         &self,
         layouts: Is,
-        _: Ic,
+        push_constant_ranges: Ic,
     ) -> Result<n::PipelineLayout, d::OutOfMemory>
     where
         Is: Iterator<Item = &'a n::DescriptorSetLayout>,
+        Ic: Iterator<Item = (pso::ShaderStageFlags, Range<u32>)>,
     {
         use std::convert::TryInto;
         let mut sets = Vec::new();
@@ -938,23 +1279,43 @@ impl d::Device<B> for Device {
         let mut num_textures = 0usize;
         let mut num_uniform_buffers = 0usize;
         let mut num_storage_buffers = 0usize;
+        let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
 
-        for layout_bindings in layouts {
+        for set_layout in layouts {
             // create a vector with the size enough to hold all the bindings, filled with `!0`
-            let mut bindings =
-                vec![!0; layout_bindings.last().map_or(0, |b| b.binding as usize + 1)];
-
-            for binding in layout_bindings.iter() {
-                assert!(!binding.immutable_samplers); //TODO
+            let num_bindings = set_layout
+                .bindings
+                .last()
+                .map_or(0, |b| b.binding as usize + 1);
+            let mut bindings = vec![!0; num_bindings];
+            let mut combined_samplers = vec![!0; num_bindings];
+
+            for binding in set_layout.bindings.iter() {
+                // combined image samplers also take a sampler index, paired with the texture
+                if let pso::DescriptorType::Image {
+                    ty: pso::ImageDescriptorType::Sampled { with_sampler: true },
+                } = binding.ty
+                {
+                    for i in 0..binding.count {
+                        if let Some(slot) = sampler_map.get_mut(num_textures + i) {
+                            *slot = Some((num_samplers + i).try_into().unwrap());
+                        }
+                    }
+                    combined_samplers[binding.binding as usize] = num_samplers.try_into().unwrap();
+                    num_samplers += binding.count;
+                }
                 let counter = match binding.ty {
                     pso::DescriptorType::Sampler => &mut num_samplers,
                     pso::DescriptorType::InputAttachment | pso::DescriptorType::Image { .. } => {
                         &mut num_textures
                     }
+                    // texel buffers are bound as buffer textures, or as images
+                    // for storage ones, so they take texture slots
                     pso::DescriptorType::Buffer {
-                        ty,
-                        format: _, //TODO
-                    } => match ty {
+                        format: pso::BufferDescriptorFormat::Texel,
+                        ..
+                    } => &mut num_textures,
+                    pso::DescriptorType::Buffer { ty, .. } => match ty {
                         pso::BufferDescriptorType::Uniform => &mut num_uniform_buffers,
                         pso::BufferDescriptorType::Storage { .. } => &mut num_storage_buffers,
                     },
@@ -965,39 +1326,74 @@ impl d::Device<B> for Device {
             }
 
             sets.push(n::PipelineLayoutSet {
-                layout: Arc::clone(layout_bindings),
+                layout: Arc::clone(set_layout),
                 bindings,
+                combined_samplers,
             });
         }
 
-        Ok(n::PipelineLayout { sets })
+        if let Some(slot) = self.share.private_caps.push_constants_slot {
+            assert!(
+                num_uniform_buffers <= slot as usize,
+                "Uniform buffers of the layout collide with the push constants at binding {}",
+                slot
+            );
+        }
+
+        Ok(n::PipelineLayout {
+            sets,
+            sampler_map,
+            push_constant_ranges: push_constant_ranges.collect(),
+        })
     }
 
-    unsafe fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> Result<(), d::OutOfMemory> {
-        Ok(())
+    unsafe fn create_pipeline_cache(
+        &self,
+        data: Option<&[u8]>,
+    ) -> Result<n::PipelineCache, d::OutOfMemory> {
+        let driver = self.pipeline_cache_driver();
+        Ok(match data {
+            Some(data) if !data.is_empty() => n::PipelineCache::from_data(data, &driver)
+                .unwrap_or_else(|| {
+                    log::info!("Ignoring pipeline cache data, invalid or from another driver");
+                    n::PipelineCache::default()
+                }),
+            _ => n::PipelineCache::default(),
+        })
     }
 
-    unsafe fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, d::OutOfMemory> {
-        //empty
-        Ok(Vec::new())
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        cache: &n::PipelineCache,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        Ok(cache.to_data(&self.pipeline_cache_driver()))
     }
 
-    unsafe fn destroy_pipeline_cache(&self, _: ()) {
+    unsafe fn destroy_pipeline_cache(&self, _: n::PipelineCache) {
         //empty
     }
 
-    unsafe fn merge_pipeline_caches<'a, I>(&self, _: &mut (), _: I) -> Result<(), d::OutOfMemory>
+    unsafe fn merge_pipeline_caches<'a, I>(
+        &self,
+        target: &mut n::PipelineCache,
+        sources: I,
+    ) -> Result<(), d::OutOfMemory>
     where
-        I: Iterator<Item = &'a ()>,
+        I: Iterator<Item = &'a n::PipelineCache>,
     {
-        //empty
+        let programs = target.programs.get_mut();
+        for source in sources {
+            for (&key, program) in source.programs.lock().iter() {
+                programs.entry(key).or_insert_with(|| program.clone());
+            }
+        }
         Ok(())
     }
 
     unsafe fn create_graphics_pipeline<'a>(
         &self,
         desc: &pso::GraphicsPipelineDesc<'a, B>,
-        _cache: Option<&()>,
+        cache: Option<&n::PipelineCache>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
         let (vertex_buffers, desc_attributes, input_assembler, vs) = match desc.primitive_assembler
         {
@@ -1032,38 +1428,14 @@ impl d::Device<B> for Device {
             (naga::ShaderStage::Vertex, Some(vs)),
             (naga::ShaderStage::Fragment, desc.fragment.as_ref()),
         ];
-        let (program, sampler_map) = self.create_shader_program(&shaders[..], &desc.layout)?;
+        let (program, sampler_map) =
+            self.create_shader_program(&shaders[..], &desc.layout, cache)?;
 
         let patch_size = match input_assembler.primitive {
             pso::Primitive::PatchList(size) => Some(size as _),
             _ => None,
         };
 
-        let mut uniforms = Vec::new();
-        {
-            let gl = &self.share.context;
-            let count = gl.get_active_uniforms(program);
-
-            let mut offset = 0;
-
-            for uniform in 0..count {
-                let glow::ActiveUniform { size, utype, name } =
-                    gl.get_active_uniform(program, uniform).unwrap();
-
-                if let Some(location) = gl.get_uniform_location(program, &name) {
-                    // Sampler2D won't show up in UniformLocation and the only other uniforms
-                    // should be push constants
-                    uniforms.push(n::UniformDesc {
-                        location: Starc::new(location),
-                        offset,
-                        utype,
-                    });
-
-                    offset += size as u32;
-                }
-            }
-        }
-
         Ok(n::GraphicsPipeline {
             program,
             primitive: conv::input_assember_to_gl_primitive(input_assembler),
@@ -1084,9 +1456,9 @@ impl d::Device<B> for Device {
                     }
                 })
                 .collect(),
-            uniforms,
             rasterizer: desc.rasterizer,
             depth: desc.depth_stencil.depth,
+            stencil: desc.depth_stencil.stencil,
             baked_states: desc.baked_states.clone(),
             sampler_map,
         })
@@ -1095,13 +1467,13 @@ impl d::Device<B> for Device {
     unsafe fn create_compute_pipeline<'a>(
         &self,
         desc: &pso::ComputePipelineDesc<'a, B>,
-        _cache: Option<&()>,
+        cache: Option<&n::PipelineCache>,
     ) -> Result<n::ComputePipeline, pso::CreationError> {
         if self.share.public_caps.limits.max_compute_work_group_count[0] == 0 {
             return Err(pso::CreationError::UnsupportedPipeline);
         }
         let shader = (naga::ShaderStage::Compute, Some(&desc.shader));
-        let (program, sampler_map) = self.create_shader_program(&[shader], &desc.layout)?;
+        let (program, sampler_map) = self.create_shader_program(&[shader], &desc.layout, cache)?;
         Ok(n::ComputePipeline {
             program,
             sampler_map,
@@ -1197,8 +1569,15 @@ impl d::Device<B> for Device {
         &self,
         raw_data: &[u32],
     ) -> Result<n::ShaderModule, d::ShaderError> {
+        let hash = {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = fxhash::FxHasher64::default();
+            raw_data.hash(&mut hasher);
+            hasher.finish()
+        };
         Ok(n::ShaderModule {
             prefer_naga: self.always_prefer_naga,
+            hash: Some(hash),
             #[cfg(feature = "cross")]
             spv: raw_data.to_vec(),
             naga: {
@@ -1232,6 +1611,8 @@ impl d::Device<B> for Device {
     ) -> Result<n::ShaderModule, (d::ShaderError, d::NagaShader)> {
         Ok(n::ShaderModule {
             prefer_naga: true,
+            // Without the SPIR-V, programs using the module are not cached.
+            hash: None,
             #[cfg(feature = "cross")]
             spv: match naga::back::spv::write_vec(&shader.module, &shader.info, &self.spv_options) {
                 Ok(spv) => spv,
@@ -1459,11 +1840,63 @@ impl d::Device<B> for Device {
 
     unsafe fn create_buffer_view(
         &self,
-        _: &n::Buffer,
-        _: Option<Format>,
-        _: buffer::SubRange,
+        buffer: &n::Buffer,
+        format: Option<Format>,
+        sub: buffer::SubRange,
     ) -> Result<n::BufferView, buffer::ViewCreationError> {
-        unimplemented!()
+        let caps = &self.share.private_caps;
+        let desc = match format {
+            Some(format) if caps.texture_buffer && conv::is_buffer_texture_format(format) => {
+                conv::describe_format(format)
+            }
+            _ => None,
+        }
+        .ok_or(buffer::ViewCreationError::UnsupportedFormat(format))?;
+
+        let gl = &self.share.context;
+        let bounded_buffer = buffer.as_bound();
+        // The range is relative to the GL buffer of the memory, not to the hal buffer.
+        let range = crate::resolve_sub_range(&sub, bounded_buffer.range);
+        if !caps.texture_buffer_range {
+            // `glTexBuffer` views the whole GL buffer, so the view has to cover
+            // all of the memory the buffer is bound to.
+            gl.bind_buffer(glow::TEXTURE_BUFFER, Some(bounded_buffer.raw));
+            let memory_size = gl.get_buffer_parameter_i32(glow::TEXTURE_BUFFER, glow::BUFFER_SIZE);
+            gl.bind_buffer(glow::TEXTURE_BUFFER, None);
+            if range != (0..memory_size as buffer::Offset) {
+                log::warn!(
+                    "Texel buffer view of {:?} doesn't cover the {} bytes of its memory",
+                    range,
+                    memory_size
+                );
+                return Err(buffer::ViewCreationError::UnsupportedFormat(format));
+            }
+        }
+
+        let raw = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_BUFFER, Some(raw));
+        if caps.texture_buffer_range {
+            gl.tex_buffer_range(
+                glow::TEXTURE_BUFFER,
+                desc.tex_internal,
+                bounded_buffer.raw,
+                range.start as i32,
+                (range.end - range.start) as i32,
+            );
+        } else {
+            gl.tex_buffer(glow::TEXTURE_BUFFER, desc.tex_internal, bounded_buffer.raw);
+        }
+        gl.bind_texture(glow::TEXTURE_BUFFER, None);
+
+        if let Err(err) = self.share.check() {
+            gl.delete_texture(raw);
+            log::error!("Texel buffer view creation failed: {:?}", err);
+            return Err(buffer::ViewCreationError::UnsupportedFormat(format));
+        }
+        Ok(n::BufferView {
+            raw,
+            format: desc.tex_internal,
+        })
     }
 
     unsafe fn create_image(
@@ -1474,97 +1907,125 @@ impl d::Device<B> for Device {
         _tiling: i::Tiling,
         usage: i::Usage,
         _sparse: memory::SparseFlags,
-        _view_caps: i::ViewCapabilities,
+        view_caps: i::ViewCapabilities,
     ) -> Result<n::Image, i::CreationError> {
         let gl = &self.share.context;
 
         let desc = conv::describe_format(format).ok_or(i::CreationError::Format(format))?;
         let channel = format.base_format().1;
 
+        // Renderbuffers only back single layer attachments, which are never
+        // copied from or to buffers.
+        let is_renderbuffer = match kind {
+            i::Kind::D2(_, _, 1, samples) => {
+                num_levels == 1
+                    && !usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED)
+                    && (samples > 1
+                        || !usage.intersects(i::Usage::TRANSFER_SRC | i::Usage::TRANSFER_DST))
+            }
+            _ => false,
+        };
+
         let mut pixel_count: u64 = 0;
-        let image = if num_levels > 1 || usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED) {
+        let image = if !is_renderbuffer {
+            let (target, num_slices) = match kind {
+                i::Kind::D1(_, 1) | i::Kind::D2(_, _, 1, 1) => (glow::TEXTURE_2D, 1),
+                i::Kind::D1(_, l) => (glow::TEXTURE_2D_ARRAY, l as u32),
+                i::Kind::D2(_, _, 6, 1) if view_caps.contains(i::ViewCapabilities::KIND_CUBE) => {
+                    (glow::TEXTURE_CUBE_MAP, 6)
+                }
+                i::Kind::D2(_, _, l, 1) if view_caps.contains(i::ViewCapabilities::KIND_CUBE) => {
+                    (glow::TEXTURE_CUBE_MAP_ARRAY, l as u32)
+                }
+                i::Kind::D2(_, _, l, 1) => (glow::TEXTURE_2D_ARRAY, l as u32),
+                i::Kind::D3(_, _, d) => (glow::TEXTURE_3D, d),
+                _ => unimplemented!(),
+            };
+            let extent = kind.extent();
             let name = gl.create_texture().unwrap();
-            let target = match kind {
-                i::Kind::D2(w, h, 1, 1) => {
-                    gl.bind_texture(glow::TEXTURE_2D, Some(name));
-                    if self.share.private_caps.image_storage {
-                        gl.tex_storage_2d(
-                            glow::TEXTURE_2D,
-                            num_levels as _,
-                            desc.tex_internal,
-                            w as _,
-                            h as _,
-                        );
-                        pixel_count += (w * h) as u64 * num_levels as u64;
-                    } else {
-                        gl.tex_parameter_i32(
-                            glow::TEXTURE_2D,
-                            glow::TEXTURE_MAX_LEVEL,
-                            (num_levels - 1) as _,
-                        );
-                        let mut w = w;
-                        let mut h = h;
-                        for i in 0..num_levels {
-                            gl.tex_image_2d(
-                                glow::TEXTURE_2D,
-                                i as _,
-                                desc.tex_internal as i32,
-                                w as _,
-                                h as _,
-                                0,
-                                desc.tex_external,
-                                desc.data_type,
-                                None,
-                            );
-                            pixel_count += (w * h) as u64;
-                            w = std::cmp::max(w / 2, 1);
-                            h = std::cmp::max(h / 2, 1);
-                        }
-                    }
-                    glow::TEXTURE_2D
+            gl.bind_texture(target, Some(name));
+
+            if self.share.private_caps.image_storage {
+                match target {
+                    glow::TEXTURE_2D | glow::TEXTURE_CUBE_MAP => gl.tex_storage_2d(
+                        target,
+                        num_levels as _,
+                        desc.tex_internal,
+                        extent.width as _,
+                        extent.height as _,
+                    ),
+                    _ => gl.tex_storage_3d(
+                        target,
+                        num_levels as _,
+                        desc.tex_internal,
+                        extent.width as _,
+                        extent.height as _,
+                        num_slices as _,
+                    ),
                 }
-                i::Kind::D2(w, h, l, 1) => {
-                    gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(name));
-                    if self.share.private_caps.image_storage {
-                        gl.tex_storage_3d(
-                            glow::TEXTURE_2D_ARRAY,
-                            num_levels as _,
-                            desc.tex_internal,
-                            w as _,
-                            h as _,
-                            l as _,
-                        );
-                        pixel_count += (w * h) as u64 * l as u64 * num_levels as u64;
-                    } else {
-                        gl.tex_parameter_i32(
-                            glow::TEXTURE_2D_ARRAY,
-                            glow::TEXTURE_MAX_LEVEL,
-                            (num_levels - 1) as _,
-                        );
-                        let mut w = w;
-                        let mut h = h;
-                        for i in 0..num_levels {
-                            gl.tex_image_3d(
-                                glow::TEXTURE_2D_ARRAY,
-                                i as _,
-                                desc.tex_internal as i32,
-                                w as _,
-                                h as _,
-                                l as _,
-                                0,
-                                desc.tex_external,
-                                desc.data_type,
-                                None,
-                            );
-                            pixel_count += (w * h) as u64 * l as u64;
-                            w = std::cmp::max(w / 2, 1);
-                            h = std::cmp::max(h / 2, 1);
+            } else if desc.data_type == glow::NONE {
+                // Compressed images can't be specified without data.
+                gl.delete_texture(name);
+                return Err(i::CreationError::Format(format));
+            } else {
+                gl.tex_parameter_i32(target, glow::TEXTURE_MAX_LEVEL, (num_levels - 1) as _);
+                for level in 0..num_levels {
+                    let level_extent = extent.at_level(level);
+                    match target {
+                        glow::TEXTURE_2D => gl.tex_image_2d(
+                            target,
+                            level as _,
+                            desc.tex_internal as i32,
+                            level_extent.width as _,
+                            level_extent.height as _,
+                            0,
+                            desc.tex_external,
+                            desc.data_type,
+                            None,
+                        ),
+                        glow::TEXTURE_CUBE_MAP => {
+                            for face in 0..6 {
+                                gl.tex_image_2d(
+                                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                                    level as _,
+                                    desc.tex_internal as i32,
+                                    level_extent.width as _,
+                                    level_extent.height as _,
+                                    0,
+                                    desc.tex_external,
+                                    desc.data_type,
+                                    None,
+                                );
+                            }
                         }
+                        _ => gl.tex_image_3d(
+                            target,
+                            level as _,
+                            desc.tex_internal as i32,
+                            level_extent.width as _,
+                            level_extent.height as _,
+                            if target == glow::TEXTURE_3D {
+                                level_extent.depth
+                            } else {
+                                num_slices
+                            } as _,
+                            0,
+                            desc.tex_external,
+                            desc.data_type,
+                            None,
+                        ),
                     }
-                    glow::TEXTURE_2D_ARRAY
                 }
-                _ => unimplemented!(),
-            };
+            }
+
+            for level in 0..num_levels {
+                let level_extent = extent.at_level(level);
+                pixel_count += level_extent.width as u64
+                    * level_extent.height as u64
+                    * level_extent.depth as u64
+                    * kind.num_layers() as u64;
+            }
+
             n::ImageType::Texture {
                 target,
                 raw: name,
@@ -1662,7 +2123,7 @@ impl d::Device<B> for Device {
     unsafe fn create_image_view(
         &self,
         image: &n::Image,
-        kind: i::ViewKind,
+        _kind: i::ViewKind,
         view_format: Format,
         swizzle: Swizzle,
         range: i::SubresourceRange,
@@ -1688,10 +2149,8 @@ impl d::Device<B> for Device {
                 format,
                 ..
             } => {
-                let is_3d = match kind {
-                    i::ViewKind::D1 | i::ViewKind::D2 => false,
-                    _ => true,
-                };
+                // Any layer of an array, cube or 3D texture is attached as a layer.
+                let is_3d = image.num_layers > 1 || image.kind.extent().depth > 1;
                 match conv::describe_format(view_format) {
                     Some(description) => {
                         let raw_view_format = description.tex_internal;
@@ -1732,31 +2191,63 @@ impl d::Device<B> for Device {
     unsafe fn create_descriptor_set_layout<'a, I, J>(
         &self,
         layout: I,
-        _immutable_samplers: J,
+        mut immutable_samplers: J,
     ) -> Result<n::DescriptorSetLayout, d::OutOfMemory>
     where
         I: Iterator<Item = pso::DescriptorSetLayoutBinding>,
         J: Iterator<Item = &'a n::FatSampler>,
     {
-        let mut bindings = layout.collect::<Vec<_>>();
+        // immutable samplers are provided in the order of the bindings
+        let mut bindings = layout
+            .map(|binding| {
+                let samplers = if binding.immutable_samplers {
+                    (&mut immutable_samplers)
+                        .take(binding.count)
+                        .cloned()
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                (binding, samplers)
+            })
+            .collect::<Vec<_>>();
         // all operations rely on the ascending bindings order
-        bindings.sort_by_key(|b| b.binding);
-        Ok(Arc::new(bindings))
+        bindings.sort_by_key(|&(ref b, _)| b.binding);
+
+        let mut offsets = Vec::with_capacity(bindings.len());
+        let mut num_descriptors = 0;
+        for &(ref binding, _) in bindings.iter() {
+            offsets.push(num_descriptors);
+            num_descriptors += binding.count;
+        }
+        let (bindings, immutable_samplers) = bindings.into_iter().unzip();
+
+        Ok(Arc::new(n::DescSetLayout {
+            bindings,
+            immutable_samplers,
+            offsets,
+            num_descriptors,
+        }))
     }
 
     unsafe fn write_descriptor_set<'a, I>(&self, op: pso::DescriptorSetWrite<'a, B, I>)
     where
         I: Iterator<Item = pso::Descriptor<'a, B>>,
     {
-        let mut layout_index = op
-            .set
-            .layout
+        let layout = Arc::clone(&op.set.layout);
+        let mut layout_index = layout
+            .bindings
             .binary_search_by_key(&op.binding, |b| b.binding)
             .unwrap();
         let mut array_offset = op.array_offset;
 
         for descriptor in op.descriptors {
-            let binding_layout = &op.set.layout[layout_index];
+            // skip the bindings which are already full, or reserved
+            while array_offset >= layout.bindings[layout_index].count {
+                array_offset -= layout.bindings[layout_index].count;
+                layout_index += 1;
+            }
+            let binding_layout = &layout.bindings[layout_index];
             let binding = match descriptor {
                 pso::Descriptor::Buffer(buffer, ref sub) => {
                     let bounded_buffer = buffer.as_bound();
@@ -1783,23 +2274,14 @@ impl d::Device<B> for Device {
                         size: (range.end - range.start) as i32,
                     }
                 }
-                pso::Descriptor::CombinedImageSampler(view, _layout, sampler) => {
-                    match *view {
-                        n::ImageView::Texture { target, raw, .. } => op
-                            .set
-                            .bindings
-                            .push(n::DescSetBindings::Texture(raw, target)),
-                        n::ImageView::Renderbuffer { .. } => {
-                            panic!("Texture doesn't support shader binding")
-                        }
+                pso::Descriptor::CombinedImageSampler(view, _layout, sampler) => match *view {
+                    n::ImageView::Texture { target, raw, .. } => {
+                        n::DescSetBindings::TextureSampler(raw, target, sampler.clone())
                     }
-                    match *sampler {
-                        n::FatSampler::Sampler(sampler) => n::DescSetBindings::Sampler(sampler),
-                        n::FatSampler::Info(ref info) => {
-                            n::DescSetBindings::SamplerDesc(info.clone())
-                        }
+                    n::ImageView::Renderbuffer { .. } => {
+                        panic!("Texture doesn't support shader binding")
                     }
-                }
+                },
                 pso::Descriptor::Image(view, _layout) => match *view {
                     n::ImageView::Texture { target, raw, .. } => {
                         n::DescSetBindings::Texture(raw, target)
@@ -1808,21 +2290,25 @@ impl d::Device<B> for Device {
                         panic!("Texture doesn't support shader binding")
                     }
                 },
-                pso::Descriptor::Sampler(sampler) => match *sampler {
-                    n::FatSampler::Sampler(sampler) => n::DescSetBindings::Sampler(sampler),
-                    n::FatSampler::Info(ref info) => n::DescSetBindings::SamplerDesc(info.clone()),
+                pso::Descriptor::Sampler(sampler) => n::DescSetBindings::Sampler(sampler.clone()),
+                pso::Descriptor::TexelBuffer(view) => match binding_layout.ty {
+                    pso::DescriptorType::Buffer {
+                        ty: pso::BufferDescriptorType::Storage { read_only },
+                        format: pso::BufferDescriptorFormat::Texel,
+                    } if self.share.private_caps.image_load_store => {
+                        let access = if read_only {
+                            glow::READ_ONLY
+                        } else {
+                            glow::READ_WRITE
+                        };
+                        n::DescSetBindings::Image(view.raw, access, view.format)
+                    }
+                    _ => n::DescSetBindings::Texture(view.raw, glow::TEXTURE_BUFFER),
                 },
-                pso::Descriptor::TexelBuffer(_view) => unimplemented!(),
             };
 
-            //TODO: overwrite instead of pushing on top
-            op.set.bindings.push(binding);
-
+            op.set.bindings[layout.offsets[layout_index] + array_offset] = Some(binding);
             array_offset += 1;
-            if array_offset == binding_layout.count {
-                array_offset = 0;
-                layout_index += 1;
-            }
         }
     }
 
@@ -1831,14 +2317,20 @@ impl d::Device<B> for Device {
             panic!("copying within same descriptor set is not currently supported");
         }
 
-        // TODO: add support for array bindings when the OpenGL backend gets them
-        let src_start = op.src_binding as usize;
+        // descriptors spilling onto the next bindings are contiguous in the sets
+        let src_start = op
+            .src_set
+            .layout
+            .descriptor_index(op.src_binding, op.src_array_offset);
         let src_end = src_start + op.count;
         assert!(src_end <= op.src_set.bindings.len());
 
         let src_slice = &op.src_set.bindings[src_start..src_end];
 
-        let dst_start = op.dst_binding as usize;
+        let dst_start = op
+            .dst_set
+            .layout
+            .descriptor_index(op.dst_binding, op.dst_array_offset);
         let dst_end = dst_start + op.count;
         assert!(dst_end <= op.dst_set.bindings.len());
 
@@ -1846,7 +2338,59 @@ impl d::Device<B> for Device {
     }
 
     fn create_semaphore(&self) -> Result<n::Semaphore, d::OutOfMemory> {
-        Ok(n::Semaphore)
+        Ok(n::Semaphore { timeline: None })
+    }
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::Semaphore, d::OutOfMemory> {
+        Ok(n::Semaphore {
+            timeline: Some(Mutex::new(n::Timeline {
+                value: initial_value,
+                pending: Vec::new(),
+            })),
+        })
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &n::Semaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        Ok(poll_timeline(
+            &self.share.context,
+            &mut semaphore.timeline().lock(),
+        ))
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        semaphore.timeline().lock().value = value;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    unsafe fn wait_for_semaphores<'a, I>(
+        &self,
+        semaphores: I,
+        wait: d::WaitFor,
+        _timeout_ns: u64,
+    ) -> Result<bool, d::WaitError>
+    where
+        I: Iterator<Item = (&'a n::Semaphore, u64)>,
+    {
+        // Sync objects are only updated between tasks on the web, so blocking here
+        // can't make any progress. Only check whether the values are reached.
+        let gl = &self.share.context;
+        let mut reached = semaphores
+            .map(|(semaphore, value)| poll_timeline(gl, &mut semaphore.timeline().lock()) >= value);
+        Ok(match wait {
+            d::WaitFor::All => reached.all(|r| r),
+            d::WaitFor::Any => reached.any(|r| r),
+        })
     }
 
     fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
@@ -1949,49 +2493,119 @@ impl d::Device<B> for Device {
         })
     }
 
-    fn create_event(&self) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    fn create_event(&self) -> Result<n::Event, d::OutOfMemory> {
+        Ok(n::Event(Arc::new(Mutex::new(n::EventState::Reset))))
     }
 
-    unsafe fn get_event_status(&self, _event: &()) -> Result<bool, d::WaitError> {
-        unimplemented!()
+    unsafe fn get_event_status(&self, event: &n::Event) -> Result<bool, d::WaitError> {
+        let mut state = event.0.lock();
+        Ok(match *state {
+            n::EventState::Reset => false,
+            n::EventState::Signaled => true,
+            n::EventState::Pending(sync) => {
+                let gl = &self.share.context;
+                if gl.get_sync_status(sync) == glow::SIGNALED {
+                    gl.delete_sync(sync);
+                    *state = n::EventState::Signaled;
+                    true
+                } else {
+                    false
+                }
+            }
+        })
     }
 
-    unsafe fn set_event(&self, _event: &mut ()) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    unsafe fn set_event(&self, event: &mut n::Event) -> Result<(), d::OutOfMemory> {
+        let old = mem::replace(&mut *event.0.lock(), n::EventState::Signaled);
+        if let n::EventState::Pending(sync) = old {
+            self.share.context.delete_sync(sync);
+        }
+        Ok(())
     }
 
-    unsafe fn reset_event(&self, _event: &mut ()) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    unsafe fn reset_event(&self, event: &mut n::Event) -> Result<(), d::OutOfMemory> {
+        let old = mem::replace(&mut *event.0.lock(), n::EventState::Reset);
+        if let n::EventState::Pending(sync) = old {
+            self.share.context.delete_sync(sync);
+        }
+        Ok(())
     }
 
     unsafe fn free_memory(&self, memory: n::Memory) {
         if let Some((buffer, _)) = memory.buffer {
             self.share.context.delete_buffer(buffer);
         }
+        self.share.heap_usage[memory.heap_index].fetch_sub(memory.size, Ordering::Relaxed);
     }
 
     unsafe fn create_query_pool(
         &self,
         ty: query::Type,
-        _count: query::Id,
-    ) -> Result<(), query::CreationError> {
-        Err(query::CreationError::Unsupported(ty))
+        count: query::Id,
+    ) -> Result<n::QueryPool, query::CreationError> {
+        let caps = &self.share.private_caps;
+        let targets = match ty {
+            query::Type::Occlusion if caps.occlusion_query => {
+                if self
+                    .share
+                    .supported_features
+                    .contains(hal::Features::PRECISE_OCCLUSION_QUERY)
+                {
+                    vec![glow::SAMPLES_PASSED]
+                } else {
+                    vec![glow::ANY_SAMPLES_PASSED]
+                }
+            }
+            query::Type::PipelineStatistics(statistics)
+                if self
+                    .features
+                    .contains(hal::Features::PIPELINE_STATISTICS_QUERY) =>
+            {
+                conv::pipeline_statistics_to_gl(statistics)
+            }
+            query::Type::Timestamp if caps.timestamp_query => vec![glow::TIMESTAMP],
+            _ => return Err(query::CreationError::Unsupported(ty)),
+        };
+
+        let gl = &self.share.context;
+        let num_objects = count as usize * targets.len();
+        let mut objects = Vec::with_capacity(num_objects);
+        for _ in 0..num_objects {
+            match gl.create_query() {
+                Ok(query) => objects.push(query),
+                Err(_) => {
+                    for query in objects {
+                        gl.delete_query(query);
+                    }
+                    return Err(d::OutOfMemory::Host.into());
+                }
+            }
+        }
+        Ok(n::QueryPool { targets, objects })
     }
 
-    unsafe fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
+    unsafe fn destroy_query_pool(&self, pool: n::QueryPool) {
+        let gl = &self.share.context;
+        for query in pool.objects {
+            gl.delete_query(query);
+        }
     }
 
     unsafe fn get_query_pool_results(
         &self,
-        _pool: &(),
-        _queries: Range<query::Id>,
-        _data: &mut [u8],
-        _stride: buffer::Stride,
-        _flags: query::ResultFlags,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
     ) -> Result<bool, d::WaitError> {
-        unimplemented!()
+        let gl = &self.share.context;
+        let mut all_available = true;
+        for (i, id) in queries.enumerate() {
+            let out = &mut data[i * stride as usize..];
+            all_available &= read_query_results(gl, pool.query_objects(id), flags, out);
+        }
+        Ok(all_available)
     }
 
     unsafe fn destroy_shader_module(&self, _: n::ShaderModule) {
@@ -2022,8 +2636,8 @@ impl d::Device<B> for Device {
         // Nothing to do
     }
 
-    unsafe fn destroy_buffer_view(&self, _: n::BufferView) {
-        // Nothing to do
+    unsafe fn destroy_buffer_view(&self, view: n::BufferView) {
+        self.share.context.delete_texture(view.raw);
     }
 
     unsafe fn destroy_image(&self, image: n::Image) {
@@ -2063,12 +2677,18 @@ impl d::Device<B> for Device {
         }
     }
 
-    unsafe fn destroy_semaphore(&self, _: n::Semaphore) {
-        // Nothing to do
+    unsafe fn destroy_semaphore(&self, semaphore: n::Semaphore) {
+        if let Some(timeline) = semaphore.timeline {
+            for (_, sync) in timeline.into_inner().pending {
+                self.share.context.delete_sync(sync);
+            }
+        }
     }
 
-    unsafe fn destroy_event(&self, _event: ()) {
-        unimplemented!()
+    unsafe fn destroy_event(&self, event: n::Event) {
+        if let n::EventState::Pending(sync) = *event.0.lock() {
+            self.share.context.delete_sync(sync);
+        }
     }
 
     fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
@@ -2078,12 +2698,20 @@ impl d::Device<B> for Device {
         Ok(())
     }
 
-    unsafe fn set_image_name(&self, _image: &mut n::Image, _name: &str) {
-        // TODO
+    unsafe fn set_image_name(&self, image: &mut n::Image, name: &str) {
+        match image.object_type {
+            n::ImageType::Renderbuffer { raw, .. } => {
+                self.set_object_label(glow::RENDERBUFFER, raw, name)
+            }
+            n::ImageType::Texture { raw, .. } => self.set_object_label(glow::TEXTURE, raw, name),
+        }
     }
 
-    unsafe fn set_buffer_name(&self, _buffer: &mut n::Buffer, _name: &str) {
-        // TODO
+    unsafe fn set_buffer_name(&self, buffer: &mut n::Buffer, name: &str) {
+        // The GL buffer is the one of the bound memory, which may back other buffers.
+        if let n::Buffer::Bound { buffer, .. } = *buffer {
+            self.set_object_label(glow::BUFFER, buffer, name);
+        }
     }
 
     unsafe fn set_command_buffer_name(
@@ -2091,27 +2719,27 @@ impl d::Device<B> for Device {
         _command_buffer: &mut cmd::CommandBuffer,
         _name: &str,
     ) {
-        // TODO
+        // Not backed by a GL object.
     }
 
     unsafe fn set_semaphore_name(&self, _semaphore: &mut n::Semaphore, _name: &str) {
-        // TODO
+        // Not backed by a GL object.
     }
 
     unsafe fn set_fence_name(&self, _fence: &mut n::Fence, _name: &str) {
-        // TODO
+        // TODO: label the sync objects with `glObjectPtrLabel`
     }
 
-    unsafe fn set_framebuffer_name(&self, _framebuffer: &mut n::Framebuffer, _name: &str) {
-        // TODO
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut n::Framebuffer, name: &str) {
+        self.set_object_label(glow::FRAMEBUFFER, framebuffer.raw, name);
     }
 
     unsafe fn set_render_pass_name(&self, _render_pass: &mut n::RenderPass, _name: &str) {
-        // TODO
+        // Not backed by a GL object.
     }
 
     unsafe fn set_descriptor_set_name(&self, _descriptor_set: &mut n::DescriptorSet, _name: &str) {
-        // TODO
+        // Not backed by a GL object.
     }
 
     unsafe fn set_descriptor_set_layout_name(
@@ -2119,7 +2747,7 @@ impl d::Device<B> for Device {
         _descriptor_set_layout: &mut n::DescriptorSetLayout,
         _name: &str,
     ) {
-        // TODO
+        // Not backed by a GL object.
     }
 
     unsafe fn set_pipeline_layout_name(
@@ -2127,6 +2755,6 @@ impl d::Device<B> for Device {
         _pipeline_layout: &mut n::PipelineLayout,
         _name: &str,
     ) {
-        // TODO
+        // Not backed by a GL object.
     }
 }
@@ -0,0 +1,496 @@
+//! GL entry points that `glow` doesn't expose.
+//!
+//! They are loaded with the same loader as the `glow` context, and stay
+//! `None` on WebGL or when the driver doesn't have them. Callers check the
+//! matching `PrivateCaps` or features first, so a missing entry point
+//! panics instead of silently skipping the call.
+
+use crate::{native as n, GlContainer};
+
+use glow::HasContext;
+
+use std::{mem, os::raw::c_void};
+
+type DrawArraysIndirect = unsafe extern "system" fn(mode: u32, indirect: *const c_void);
+type DrawElementsIndirect = unsafe extern "system" fn(mode: u32, ty: u32, indirect: *const c_void);
+type MultiDrawArraysIndirect =
+    unsafe extern "system" fn(mode: u32, indirect: *const c_void, draw_count: i32, stride: i32);
+type MultiDrawElementsIndirect = unsafe extern "system" fn(
+    mode: u32,
+    ty: u32,
+    indirect: *const c_void,
+    draw_count: i32,
+    stride: i32,
+);
+type MultiDrawArraysIndirectCount = unsafe extern "system" fn(
+    mode: u32,
+    indirect: *const c_void,
+    draw_count: isize,
+    max_draw_count: i32,
+    stride: i32,
+);
+type MultiDrawElementsIndirectCount = unsafe extern "system" fn(
+    mode: u32,
+    ty: u32,
+    indirect: *const c_void,
+    draw_count: isize,
+    max_draw_count: i32,
+    stride: i32,
+);
+type QueryCounter = unsafe extern "system" fn(query: u32, target: u32);
+type GetQueryObjectU64 = unsafe extern "system" fn(query: u32, parameter: u32, value: *mut u64);
+type GetProgramI32 = unsafe extern "system" fn(program: u32, parameter: u32, value: *mut i32);
+type GetProgramBinary = unsafe extern "system" fn(
+    program: u32,
+    buf_size: i32,
+    length: *mut i32,
+    format: *mut u32,
+    binary: *mut c_void,
+);
+type ProgramBinary =
+    unsafe extern "system" fn(program: u32, format: u32, binary: *const c_void, length: i32);
+type ProgramParameterI32 = unsafe extern "system" fn(program: u32, parameter: u32, value: i32);
+type TexBuffer = unsafe extern "system" fn(target: u32, internal_format: u32, buffer: u32);
+type TexBufferRange = unsafe extern "system" fn(
+    target: u32,
+    internal_format: u32,
+    buffer: u32,
+    offset: isize,
+    size: isize,
+);
+type CompressedTexSubImage2D = unsafe extern "system" fn(
+    target: u32,
+    level: i32,
+    x_offset: i32,
+    y_offset: i32,
+    width: i32,
+    height: i32,
+    format: u32,
+    image_size: i32,
+    data: *const c_void,
+);
+type CompressedTexSubImage3D = unsafe extern "system" fn(
+    target: u32,
+    level: i32,
+    x_offset: i32,
+    y_offset: i32,
+    z_offset: i32,
+    width: i32,
+    height: i32,
+    depth: i32,
+    format: u32,
+    image_size: i32,
+    data: *const c_void,
+);
+type CopyImageSubData = unsafe extern "system" fn(
+    src_name: u32,
+    src_target: u32,
+    src_level: i32,
+    src_x: i32,
+    src_y: i32,
+    src_z: i32,
+    dst_name: u32,
+    dst_target: u32,
+    dst_level: i32,
+    dst_x: i32,
+    dst_y: i32,
+    dst_z: i32,
+    width: i32,
+    height: i32,
+    depth: i32,
+);
+type PolygonOffsetClamp = unsafe extern "system" fn(factor: f32, units: f32, clamp: f32);
+type BindImageTexture = unsafe extern "system" fn(
+    unit: u32,
+    texture: u32,
+    level: i32,
+    layered: u8,
+    layer: i32,
+    access: u32,
+    format: u32,
+);
+
+#[derive(Default)]
+pub(crate) struct ExtFns {
+    draw_arrays_indirect: Option<DrawArraysIndirect>,
+    draw_elements_indirect: Option<DrawElementsIndirect>,
+    multi_draw_arrays_indirect: Option<MultiDrawArraysIndirect>,
+    multi_draw_elements_indirect: Option<MultiDrawElementsIndirect>,
+    multi_draw_arrays_indirect_count: Option<MultiDrawArraysIndirectCount>,
+    multi_draw_elements_indirect_count: Option<MultiDrawElementsIndirectCount>,
+    query_counter: Option<QueryCounter>,
+    get_query_object_u64: Option<GetQueryObjectU64>,
+    get_program_i32: Option<GetProgramI32>,
+    get_program_binary: Option<GetProgramBinary>,
+    program_binary: Option<ProgramBinary>,
+    program_parameter_i32: Option<ProgramParameterI32>,
+    tex_buffer: Option<TexBuffer>,
+    tex_buffer_range: Option<TexBufferRange>,
+    compressed_tex_sub_image_2d: Option<CompressedTexSubImage2D>,
+    compressed_tex_sub_image_3d: Option<CompressedTexSubImage3D>,
+    copy_image_sub_data: Option<CopyImageSubData>,
+    bind_image_texture: Option<BindImageTexture>,
+    polygon_offset_clamp: Option<PolygonOffsetClamp>,
+}
+
+impl ExtFns {
+    /// Loads the entry points, trying the core name first and then
+    /// the names of the extensions that provide it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub unsafe fn load<F>(mut loader: F) -> Self
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        macro_rules! load {
+            ($($name:expr),+) => {
+                [$($name),+]
+                    .iter()
+                    .map(|name| loader(name))
+                    .find(|address| !address.is_null())
+                    .map(|address| mem::transmute(address))
+            };
+        }
+        ExtFns {
+            draw_arrays_indirect: load!("glDrawArraysIndirect"),
+            draw_elements_indirect: load!("glDrawElementsIndirect"),
+            multi_draw_arrays_indirect: load!(
+                "glMultiDrawArraysIndirect",
+                "glMultiDrawArraysIndirectEXT"
+            ),
+            multi_draw_elements_indirect: load!(
+                "glMultiDrawElementsIndirect",
+                "glMultiDrawElementsIndirectEXT"
+            ),
+            multi_draw_arrays_indirect_count: load!(
+                "glMultiDrawArraysIndirectCount",
+                "glMultiDrawArraysIndirectCountARB"
+            ),
+            multi_draw_elements_indirect_count: load!(
+                "glMultiDrawElementsIndirectCount",
+                "glMultiDrawElementsIndirectCountARB"
+            ),
+            query_counter: load!("glQueryCounter", "glQueryCounterEXT"),
+            get_query_object_u64: load!("glGetQueryObjectui64v", "glGetQueryObjectui64vEXT"),
+            get_program_i32: load!("glGetProgramiv"),
+            get_program_binary: load!("glGetProgramBinary", "glGetProgramBinaryOES"),
+            program_binary: load!("glProgramBinary", "glProgramBinaryOES"),
+            program_parameter_i32: load!("glProgramParameteri"),
+            tex_buffer: load!("glTexBuffer", "glTexBufferOES", "glTexBufferEXT"),
+            tex_buffer_range: load!(
+                "glTexBufferRange",
+                "glTexBufferRangeOES",
+                "glTexBufferRangeEXT"
+            ),
+            compressed_tex_sub_image_2d: load!("glCompressedTexSubImage2D"),
+            compressed_tex_sub_image_3d: load!("glCompressedTexSubImage3D"),
+            copy_image_sub_data: load!(
+                "glCopyImageSubData",
+                "glCopyImageSubDataOES",
+                "glCopyImageSubDataEXT"
+            ),
+            bind_image_texture: load!("glBindImageTexture"),
+            polygon_offset_clamp: load!("glPolygonOffsetClamp", "glPolygonOffsetClampEXT"),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn raw_name(name: u32) -> u32 {
+    name
+}
+
+#[cfg(target_arch = "wasm32")]
+fn raw_name<T>(_name: T) -> u32 {
+    unreachable!("WebGL doesn't load the extension entry points")
+}
+
+fn get<T>(function: Option<T>, name: &str) -> T {
+    match function {
+        Some(function) => function,
+        None => panic!("{} is not loaded", name),
+    }
+}
+
+impl GlContainer {
+    pub unsafe fn draw_arrays_indirect_offset(&self, mode: u32, offset: i32) {
+        get(self.ext.draw_arrays_indirect, "glDrawArraysIndirect")(mode, offset as usize as _)
+    }
+
+    pub unsafe fn draw_elements_indirect_offset(&self, mode: u32, ty: u32, offset: i32) {
+        get(self.ext.draw_elements_indirect, "glDrawElementsIndirect")(
+            mode,
+            ty,
+            offset as usize as _,
+        )
+    }
+
+    pub unsafe fn multi_draw_arrays_indirect_offset(
+        &self,
+        mode: u32,
+        offset: i32,
+        draw_count: i32,
+        stride: i32,
+    ) {
+        get(
+            self.ext.multi_draw_arrays_indirect,
+            "glMultiDrawArraysIndirect",
+        )(mode, offset as usize as _, draw_count, stride)
+    }
+
+    pub unsafe fn multi_draw_elements_indirect_offset(
+        &self,
+        mode: u32,
+        ty: u32,
+        offset: i32,
+        draw_count: i32,
+        stride: i32,
+    ) {
+        get(
+            self.ext.multi_draw_elements_indirect,
+            "glMultiDrawElementsIndirect",
+        )(mode, ty, offset as usize as _, draw_count, stride)
+    }
+
+    pub unsafe fn multi_draw_arrays_indirect_count_offset(
+        &self,
+        mode: u32,
+        offset: i32,
+        count_offset: i32,
+        max_draw_count: i32,
+        stride: i32,
+    ) {
+        get(
+            self.ext.multi_draw_arrays_indirect_count,
+            "glMultiDrawArraysIndirectCount",
+        )(
+            mode,
+            offset as usize as _,
+            count_offset as _,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    pub unsafe fn multi_draw_elements_indirect_count_offset(
+        &self,
+        mode: u32,
+        ty: u32,
+        offset: i32,
+        count_offset: i32,
+        max_draw_count: i32,
+        stride: i32,
+    ) {
+        get(
+            self.ext.multi_draw_elements_indirect_count,
+            "glMultiDrawElementsIndirectCount",
+        )(
+            mode,
+            ty,
+            offset as usize as _,
+            count_offset as _,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    pub unsafe fn query_counter(&self, query: n::Query, target: u32) {
+        get(self.ext.query_counter, "glQueryCounter")(raw_name(query), target)
+    }
+
+    /// Falls back to the 32-bit result when the 64-bit getter is missing,
+    /// as it is on WebGL and on ES without `EXT_disjoint_timer_query`.
+    pub unsafe fn get_query_parameter_u64(&self, query: n::Query, parameter: u32) -> u64 {
+        match self.ext.get_query_object_u64 {
+            Some(get_query_object_u64) => {
+                let mut value = 0;
+                get_query_object_u64(raw_name(query), parameter, &mut value);
+                value
+            }
+            None => self.get_query_parameter_u32(query, parameter) as u64,
+        }
+    }
+
+    /// Returns the binary format and the contents of a linked program.
+    pub unsafe fn get_program_binary(&self, program: n::Program) -> (u32, Vec<u8>) {
+        let mut length = 0;
+        get(self.ext.get_program_i32, "glGetProgramiv")(
+            raw_name(program),
+            glow::PROGRAM_BINARY_LENGTH,
+            &mut length,
+        );
+        let mut binary = vec![0u8; length.max(0) as usize];
+        let mut written = 0;
+        let mut format = 0;
+        get(self.ext.get_program_binary, "glGetProgramBinary")(
+            raw_name(program),
+            binary.len() as i32,
+            &mut written,
+            &mut format,
+            binary.as_mut_ptr() as *mut _,
+        );
+        binary.truncate(written.max(0) as usize);
+        (format, binary)
+    }
+
+    pub unsafe fn program_binary(&self, program: n::Program, format: u32, binary: &[u8]) {
+        get(self.ext.program_binary, "glProgramBinary")(
+            raw_name(program),
+            format,
+            binary.as_ptr() as *const _,
+            binary.len() as i32,
+        )
+    }
+
+    pub unsafe fn program_parameter_i32(&self, program: n::Program, parameter: u32, value: i32) {
+        get(self.ext.program_parameter_i32, "glProgramParameteri")(
+            raw_name(program),
+            parameter,
+            value,
+        )
+    }
+
+    pub unsafe fn tex_buffer(&self, target: u32, internal_format: u32, buffer: n::RawBuffer) {
+        get(self.ext.tex_buffer, "glTexBuffer")(target, internal_format, raw_name(buffer))
+    }
+
+    pub unsafe fn tex_buffer_range(
+        &self,
+        target: u32,
+        internal_format: u32,
+        buffer: n::RawBuffer,
+        offset: i32,
+        size: i32,
+    ) {
+        get(self.ext.tex_buffer_range, "glTexBufferRange")(
+            target,
+            internal_format,
+            raw_name(buffer),
+            offset as _,
+            size as _,
+        )
+    }
+
+    /// Uploads `size` bytes of compressed data at `offset` in the bound
+    /// `PIXEL_UNPACK_BUFFER`.
+    pub unsafe fn compressed_tex_sub_image_2d_offset(
+        &self,
+        target: u32,
+        level: i32,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        offset: i32,
+        size: i32,
+    ) {
+        get(
+            self.ext.compressed_tex_sub_image_2d,
+            "glCompressedTexSubImage2D",
+        )(
+            target,
+            level,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            format,
+            size,
+            offset as usize as _,
+        )
+    }
+
+    pub unsafe fn compressed_tex_sub_image_3d_offset(
+        &self,
+        target: u32,
+        level: i32,
+        x_offset: i32,
+        y_offset: i32,
+        z_offset: i32,
+        width: i32,
+        height: i32,
+        depth: i32,
+        format: u32,
+        offset: i32,
+        size: i32,
+    ) {
+        get(
+            self.ext.compressed_tex_sub_image_3d,
+            "glCompressedTexSubImage3D",
+        )(
+            target,
+            level,
+            x_offset,
+            y_offset,
+            z_offset,
+            width,
+            height,
+            depth,
+            format,
+            size,
+            offset as usize as _,
+        )
+    }
+
+    pub unsafe fn copy_image_sub_data(
+        &self,
+        src_name: n::Texture,
+        src_target: u32,
+        src_level: i32,
+        src_x: i32,
+        src_y: i32,
+        src_z: i32,
+        dst_name: n::Texture,
+        dst_target: u32,
+        dst_level: i32,
+        dst_x: i32,
+        dst_y: i32,
+        dst_z: i32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    ) {
+        get(self.ext.copy_image_sub_data, "glCopyImageSubData")(
+            raw_name(src_name),
+            src_target,
+            src_level,
+            src_x,
+            src_y,
+            src_z,
+            raw_name(dst_name),
+            dst_target,
+            dst_level,
+            dst_x,
+            dst_y,
+            dst_z,
+            width,
+            height,
+            depth,
+        )
+    }
+
+    pub unsafe fn bind_image_texture(
+        &self,
+        unit: u32,
+        texture: n::Texture,
+        level: i32,
+        layered: bool,
+        layer: i32,
+        access: u32,
+        format: u32,
+    ) {
+        get(self.ext.bind_image_texture, "glBindImageTexture")(
+            unit,
+            raw_name(texture),
+            level,
+            layered as u8,
+            layer,
+            access,
+            format,
+        )
+    }
+
+    pub unsafe fn polygon_offset_clamp(&self, factor: f32, units: f32, clamp: f32) {
+        get(self.ext.polygon_offset_clamp, "glPolygonOffsetClamp")(factor, units, clamp)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Error, GlContainer, MAX_COLOR_ATTACHMENTS};
+use crate::{Error, GlContainer, MAX_COLOR_ATTACHMENTS, MAX_PUSH_CONSTANTS_SIZE};
 use glow::HasContext;
 use hal::{DynamicStates, Features, Limits, PerformanceCaveats, PhysicalDeviceProperties};
 use std::{collections::HashSet, fmt, str};
@@ -227,8 +227,31 @@ pub struct PrivateCaps {
     pub per_slot_color_mask: bool,
     /// Reading from textures into CPU memory is supported.
     pub get_tex_image: bool,
+    /// Copying between textures directly with `glCopyImageSubData`.
+    pub copy_image: bool,
     /// Inserting memory barriers.
     pub memory_barrier: bool,
+    /// Occlusion queries, counting the samples on desktop GL.
+    pub occlusion_query: bool,
+    /// Timestamp queries with `glQueryCounter`.
+    pub timestamp_query: bool,
+    /// Saving and restoring linked programs with `glGetProgramBinary`
+    /// and `glProgramBinary`.
+    pub program_binary: bool,
+    /// Buffer textures of the `TEXTURE_BUFFER` target, backing the texel buffer views.
+    pub texture_buffer: bool,
+    /// Buffer textures viewing a part of a buffer with `glTexBufferRange`.
+    pub texture_buffer_range: bool,
+    /// Binding texture levels, including buffer textures, as images
+    /// with `glBindImageTexture`.
+    pub image_load_store: bool,
+    /// Clamping the depth bias with `glPolygonOffsetClamp`.
+    pub polygon_offset_clamp: bool,
+    /// Discarding framebuffer attachments with `glInvalidateFramebuffer`.
+    pub invalidate_framebuffer: bool,
+    /// Uniform buffer binding reserved for the block emulating push constants,
+    /// if uniform buffers are supported.
+    pub push_constants_slot: Option<u8>,
 }
 
 /// OpenGL implementation information
@@ -652,15 +675,58 @@ pub(crate) fn query_all(
     if info.is_supported(&[Core(4, 4), Ext("ARB_texture_mirror_clamp_to_edge")]) {
         features |= Features::SAMPLER_MIRROR_CLAMP_EDGE;
     }
+    if info.is_supported(&[Ext("GL_EXT_texture_compression_s3tc")])
+        && info.is_supported(&[
+            Core(3, 0),
+            Ext("GL_ARB_texture_compression_rgtc"),
+            Ext("GL_EXT_texture_compression_rgtc"),
+        ])
+        && info.is_supported(&[
+            Core(4, 2),
+            Ext("GL_ARB_texture_compression_bptc"),
+            Ext("GL_EXT_texture_compression_bptc"),
+        ])
+    {
+        features |= Features::FORMAT_BC;
+    }
+    if info.is_supported(&[Core(4, 3), Es(3, 0), Ext("GL_ARB_ES3_compatibility")]) {
+        features |= Features::FORMAT_ETC2;
+    }
+    if info.is_supported(&[Es(3, 2), Ext("GL_KHR_texture_compression_astc_ldr")]) {
+        features |= Features::FORMAT_ASTC_LDR;
+    }
     if info.is_supported(&[Core(4, 0), Es(3, 2), Ext("GL_EXT_draw_buffers2")]) && !crate::is_webgl()
     {
         features |= Features::INDEPENDENT_BLENDING;
     }
 
-    // TODO
-    if false && info.is_supported(&[Core(4, 3), Es(3, 1)]) {
-        // TODO: extension
+    if info.is_supported(&[Core(4, 0), Es(3, 1), Ext("GL_ARB_draw_indirect")]) {
         legacy |= LegacyFeatures::INDIRECT_EXECUTION;
+        if info.is_supported(&[
+            Core(4, 3),
+            Ext("GL_ARB_multi_draw_indirect"),
+            Ext("GL_EXT_multi_draw_indirect"),
+        ]) {
+            features |= Features::MULTI_DRAW_INDIRECT;
+        }
+        if info.is_supported(&[Core(4, 6), Ext("GL_ARB_indirect_parameters")]) {
+            features |= Features::DRAW_INDIRECT_COUNT;
+        }
+    }
+    if !info.version.is_embedded {
+        features |= Features::PRECISE_OCCLUSION_QUERY;
+    }
+    let polygon_offset_clamp = !crate::is_webgl()
+        && info.is_supported(&[
+            Core(4, 6),
+            Ext("GL_ARB_polygon_offset_clamp"),
+            Ext("GL_EXT_polygon_offset_clamp"),
+        ]);
+    if polygon_offset_clamp {
+        features |= Features::DEPTH_BIAS_CLAMP;
+    }
+    if info.is_supported(&[Core(4, 6), Ext("GL_ARB_pipeline_statistics_query")]) {
+        features |= Features::PIPELINE_STATISTICS_QUERY;
     }
     if info.is_supported(&[Core(3, 1), Es(3, 0), Ext("GL_ARB_draw_instanced")]) {
         legacy |= LegacyFeatures::DRAW_INSTANCED;
@@ -717,18 +783,18 @@ pub(crate) fn query_all(
         legacy |= LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING;
     }
 
+    let timestamp_query = info.is_supported(&[
+        Core(3, 3),
+        Ext("GL_ARB_timer_query"),
+        Ext("GL_EXT_disjoint_timer_query"),
+    ]);
+    limits.timestamp_compute_and_graphics = timestamp_query;
+
     let mut performance_caveats = PerformanceCaveats::empty();
     //TODO: extension
     if !info.is_supported(&[Core(4, 2)]) {
         performance_caveats |= PerformanceCaveats::BASE_VERTEX_INSTANCE_DRAWING;
     }
-    let properties = PhysicalDeviceProperties {
-        limits,
-        performance_caveats,
-        dynamic_pipeline_states: DynamicStates::all(),
-        ..PhysicalDeviceProperties::default()
-    };
-
     let buffer_storage = info.is_supported(&[
         Core(4, 4),
         Ext("GL_ARB_buffer_storage"),
@@ -737,6 +803,57 @@ pub(crate) fn query_all(
     // See https://github.com/gfx-rs/gfx/issues/3453
     let emulate_map = crate::is_webgl() || !buffer_storage;
 
+    // Some drivers expose the entry points without any binary format.
+    let program_binary =
+        info.is_supported(&[Core(4, 1), Es(3, 0), Ext("GL_ARB_get_program_binary")])
+            && !crate::is_webgl()
+            && get_usize(gl, glow::NUM_PROGRAM_BINARY_FORMATS).unwrap_or(0) != 0;
+
+    let texture_buffer = !crate::is_webgl()
+        && info.is_supported(&[
+            Core(3, 1),
+            Es(3, 2),
+            Ext("GL_ARB_texture_buffer_object"),
+            Ext("GL_EXT_texture_buffer"),
+        ]);
+    let texture_buffer_range = texture_buffer
+        && info.is_supported(&[
+            Core(4, 3),
+            Es(3, 2),
+            Ext("GL_ARB_texture_buffer_range"),
+            Ext("GL_EXT_texture_buffer"),
+        ]);
+    if texture_buffer_range {
+        limits.min_texel_buffer_offset_alignment =
+            get_u64(gl, glow::TEXTURE_BUFFER_OFFSET_ALIGNMENT).unwrap_or(256);
+    }
+
+    // Push constants are emulated with a uniform block at the last binding,
+    // which is then taken away from the uniform buffer descriptors.
+    let push_constants_slot = if legacy.contains(LegacyFeatures::CONSTANT_BUFFER) {
+        let num_bindings = get_usize(gl, glow::MAX_UNIFORM_BUFFER_BINDINGS).unwrap_or(0);
+        match num_bindings.min(0x100) {
+            0 => None,
+            n => Some((n - 1) as u8),
+        }
+    } else {
+        None
+    };
+    if let Some(slot) = push_constants_slot {
+        limits.max_push_constants_size = MAX_PUSH_CONSTANTS_SIZE;
+        limits
+            .descriptor_limits
+            .max_per_stage_descriptor_uniform_buffers = slot as u32;
+        limits.descriptor_limits.max_descriptor_set_uniform_buffers = slot as u32;
+    }
+
+    let properties = PhysicalDeviceProperties {
+        limits,
+        performance_caveats,
+        dynamic_pipeline_states: DynamicStates::all(),
+        ..PhysicalDeviceProperties::default()
+    };
+
     let private = PrivateCaps {
         vertex_array: info.is_supported(&[Core(3, 0), Es(3, 0), Ext("GL_ARB_vertex_array_object")]),
         // TODO && gl.GenVertexArrays.is_loaded(),
@@ -756,7 +873,35 @@ pub(crate) fn query_all(
         draw_buffers: info.is_supported(&[Core(2, 0), Es(3, 0)]),
         per_slot_color_mask: info.is_supported(&[Core(3, 0)]),
         get_tex_image: !info.version.is_embedded,
+        copy_image: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 2),
+            Ext("GL_ARB_copy_image"),
+            Ext("GL_EXT_copy_image"),
+            Ext("GL_OES_copy_image"),
+        ]),
         memory_barrier: info.is_supported(&[Core(4, 2), Es(3, 1)]),
+        occlusion_query: info.is_supported(&[
+            Core(1, 5),
+            Es(3, 0),
+            Ext("GL_EXT_occlusion_query_boolean"),
+        ]),
+        timestamp_query,
+        program_binary,
+        texture_buffer,
+        texture_buffer_range,
+        image_load_store: info.is_supported(&[
+            Core(4, 2),
+            Es(3, 1),
+            Ext("GL_ARB_shader_image_load_store"),
+        ]),
+        polygon_offset_clamp,
+        invalidate_framebuffer: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 0),
+            Ext("GL_ARB_invalidate_subdata"),
+        ]),
+        push_constants_slot,
     };
 
     let filter = if info.is_supported(&[Es(3, 0)]) {
@@ -28,7 +28,10 @@ use std::{
     fmt,
     hash::BuildHasherDefault,
     ops::{Deref, Range},
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
     thread,
 };
 
@@ -40,6 +43,7 @@ pub use self::info::{Info, PlatformName, Version};
 mod command;
 mod conv;
 mod device;
+mod ext;
 mod info;
 mod native;
 mod pool;
@@ -50,9 +54,13 @@ mod window;
 // Web implementation
 #[cfg(target_arch = "wasm32")]
 pub use window::web::{Instance, Surface, Swapchain};
+#[cfg(target_arch = "wasm32")]
+use window::web::AdapterContext;
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use window::egl::{Instance, Surface, Swapchain};
+pub use window::egl::{HeadlessPlatform, Instance, Surface, Swapchain};
+#[cfg(not(target_arch = "wasm32"))]
+use window::egl::AdapterContext;
 
 pub use glow::Context as GlContext;
 use glow::HasContext;
@@ -66,9 +74,12 @@ const MAX_SAMPLERS: usize = 16;
 //TODO: has to be within glow::MAX_COMBINED_TEXTURE_IMAGE_UNITS
 const MAX_TEXTURE_SLOTS: usize = 16;
 const MAX_COLOR_ATTACHMENTS: usize = 16;
+const MAX_PUSH_CONSTANTS_SIZE: usize = 128;
 
 struct GlContainer {
     context: GlContext,
+    /// Entry points that `glow` doesn't expose.
+    ext: ext::ExtFns,
 }
 
 impl Deref for GlContainer {
@@ -108,15 +119,15 @@ impl hal::Backend for Backend {
     type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = native::GraphicsPipeline;
     type PipelineLayout = native::PipelineLayout;
-    type PipelineCache = ();
+    type PipelineCache = native::PipelineCache;
     type DescriptorSetLayout = native::DescriptorSetLayout;
     type DescriptorPool = native::DescriptorPool;
     type DescriptorSet = native::DescriptorSet;
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
-    type Event = ();
-    type QueryPool = ();
+    type Event = native::Event;
+    type QueryPool = native::QueryPool;
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -207,7 +218,12 @@ struct Share {
     private_caps: info::PrivateCaps,
     // Indicates if there is an active logical device.
     open: Cell<bool>,
+    // Context to make current when opening the logical device,
+    // if the instance exposes several adapters.
+    adapter_context: Option<AdapterContext>,
     memory_types: Vec<(adapter::MemoryType, MemoryUsage)>,
+    // Bytes allocated from each memory heap by the logical device.
+    heap_usage: [AtomicU64; 2],
     texture_format_filter: info::TextureFormatFilter,
 }
 
@@ -351,8 +367,12 @@ unsafe impl<T: ?Sized> Sync for Wstarc<T> {}
 pub struct PhysicalDevice(Starc<Share>);
 
 impl PhysicalDevice {
-    fn new_adapter(context: GlContext) -> adapter::Adapter<Backend> {
-        let gl = GlContainer { context };
+    fn new_adapter(
+        context: GlContext,
+        ext: ext::ExtFns,
+        adapter_context: Option<AdapterContext>,
+    ) -> adapter::Adapter<Backend> {
+        let gl = GlContainer { context, ext };
         // query information
         let (info, supported_features, legacy_features, public_caps, private_caps, texture_format_filter) =
             info::query_all(&gl);
@@ -440,7 +460,9 @@ impl PhysicalDevice {
             texture_format_filter,
             private_caps,
             open: Cell::new(false),
+            adapter_context,
             memory_types,
+            heap_usage: Default::default(),
         };
         if let Err(err) = share.check() {
             panic!("Error querying info: {:?}", err);
@@ -543,6 +565,13 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             return Err(hal::device::CreationError::MissingFeature);
         }
 
+        if let Some(ref context) = self.0.adapter_context {
+            if let Err(e) = context.make_current() {
+                log::error!("Unable to make the adapter context current: {:?}", e);
+                return Err(hal::device::CreationError::InitializationFailed);
+            }
+        }
+
         // initialize permanent states
         let gl = &self.0.context;
 
@@ -591,14 +620,24 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         })
     }
 
-    fn format_properties(&self, _: Option<hal::format::Format>) -> hal::format::Properties {
+    fn format_properties(&self, format: Option<hal::format::Format>) -> hal::format::Properties {
         use hal::format::{BufferFeature as Bf, ImageFeature as If};
 
+        let caps = &self.0.private_caps;
+        let mut buffer_features = Bf::VERTEX;
+        if caps.texture_buffer && format.map_or(false, conv::is_buffer_texture_format) {
+            buffer_features |= Bf::UNIFORM_TEXEL;
+            // Storage texel buffers bind the buffer textures as images.
+            if caps.image_load_store && format.map_or(false, conv::is_image_buffer_format) {
+                buffer_features |= Bf::STORAGE_TEXEL;
+            }
+        }
+
         // TODO: These are for show
         hal::format::Properties {
             linear_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::empty(),
             optimal_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::SAMPLED,
-            buffer_features: Bf::VERTEX,
+            buffer_features,
         }
     }
 
@@ -617,7 +656,42 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             ..
         } = conv::describe_format(format)?;
 
-        if !self.0.texture_format_filter.check(
+        if format.surface_desc().is_compressed() {
+            use hal::format::Format as F;
+            let required = match format {
+                F::Bc1RgbUnorm
+                | F::Bc1RgbSrgb
+                | F::Bc1RgbaUnorm
+                | F::Bc1RgbaSrgb
+                | F::Bc2Unorm
+                | F::Bc2Srgb
+                | F::Bc3Unorm
+                | F::Bc3Srgb
+                | F::Bc4Unorm
+                | F::Bc4Snorm
+                | F::Bc5Unorm
+                | F::Bc5Snorm
+                | F::Bc6hUfloat
+                | F::Bc6hSfloat
+                | F::Bc7Unorm
+                | F::Bc7Srgb => hal::Features::FORMAT_BC,
+                F::Etc2R8g8b8Unorm
+                | F::Etc2R8g8b8Srgb
+                | F::Etc2R8g8b8a1Unorm
+                | F::Etc2R8g8b8a1Srgb
+                | F::Etc2R8g8b8a8Unorm
+                | F::Etc2R8g8b8a8Srgb
+                | F::EacR11Unorm
+                | F::EacR11Snorm
+                | F::EacR11g11Unorm
+                | F::EacR11g11Snorm => hal::Features::FORMAT_ETC2,
+                // The remaining compressed formats are ASTC.
+                _ => hal::Features::FORMAT_ASTC_LDR,
+            };
+            if !self.0.supported_features.contains(required) {
+                return None;
+            }
+        } else if !self.0.texture_format_filter.check(
             tex_internal,
             tex_external,
             data_type) {
@@ -660,6 +734,20 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         }
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        // There is no portable way to query the available memory, so the budget is
+        // the unknown heap size of `u64::MAX`, and only the allocations of this device are known.
+        self.memory_properties()
+            .memory_heaps
+            .iter()
+            .zip(&self.0.heap_usage)
+            .map(|(heap, usage)| adapter::MemoryHeapBudget {
+                budget: heap.size,
+                usage: usage.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
     fn features(&self) -> hal::Features {
         self.0.supported_features
     }
@@ -1,11 +1,13 @@
-use crate::{Backend, GlContext, MAX_TEXTURE_SLOTS};
+use crate::{Backend, FastHashMap, GlContext, MAX_TEXTURE_SLOTS};
 
 use hal::{
     buffer, format, image as i,
     memory::{Properties, Requirements},
-    pass, pso, window as w,
+    pass, pso, query, window as w,
 };
 
+use parking_lot::Mutex;
+
 use std::{borrow::Borrow, fmt, ops::Range, sync::Arc};
 
 pub type TextureTarget = u32;
@@ -21,10 +23,11 @@ pub type Renderbuffer = <GlContext as glow::HasContext>::Renderbuffer;
 pub type RawFramebuffer = <GlContext as glow::HasContext>::Framebuffer;
 pub type Texture = <GlContext as glow::HasContext>::Texture;
 pub type Sampler = <GlContext as glow::HasContext>::Sampler;
+pub type Query = <GlContext as glow::HasContext>::Query;
 // TODO: UniformLocation was copy in glow 0.3, but in 0.4 it isn't. Wrap it in a Starc for now
 // to make it `Sync + Send` instead.
 pub type UniformLocation = crate::Starc<<GlContext as glow::HasContext>::UniformLocation>;
-pub type DescriptorSetLayout = Arc<Vec<pso::DescriptorSetLayoutBinding>>;
+pub type DescriptorSetLayout = Arc<DescSetLayout>;
 
 #[derive(Clone, Debug)]
 pub struct Framebuffer {
@@ -69,7 +72,12 @@ impl Buffer {
 }
 
 #[derive(Debug)]
-pub struct BufferView;
+pub struct BufferView {
+    /// Texture of the `TEXTURE_BUFFER` target, viewing the buffer range.
+    pub(crate) raw: Texture,
+    /// Internal format of the texture, used when binding it as an image.
+    pub(crate) format: u32,
+}
 
 #[derive(Debug)]
 pub enum Fence {
@@ -80,6 +88,39 @@ pub enum Fence {
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+#[derive(Debug)]
+pub enum EventState {
+    Reset,
+    Signaled,
+    /// Set by a command buffer, signaled once the sync object is.
+    Pending(<GlContext as glow::HasContext>::Fence),
+}
+
+/// Event shared with the command buffers that set or reset it.
+#[derive(Clone, Debug)]
+pub struct Event(pub(crate) Arc<Mutex<EventState>>);
+
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+#[derive(Debug)]
+pub struct QueryPool {
+    /// Query targets, with one GL query object per target for each query.
+    pub(crate) targets: Vec<u32>,
+    pub(crate) objects: Vec<Query>,
+}
+
+impl QueryPool {
+    /// Returns the GL query objects of a query, one per target.
+    pub(crate) fn query_objects(&self, id: query::Id) -> &[Query] {
+        let count = self.targets.len();
+        &self.objects[id as usize * count..(id as usize + 1) * count]
+    }
+}
+
+unsafe impl Send for QueryPool {}
+unsafe impl Sync for QueryPool {}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum BindingRegister {
     Textures,
@@ -91,6 +132,196 @@ pub enum BindingRegister {
 /// sampler (in this layout) that the texture is used with.    
 pub(crate) type SamplerBindMap = [Option<u8>; MAX_TEXTURE_SLOTS];
 
+/// Linked program stored in a `PipelineCache`.
+#[derive(Clone, Debug)]
+pub(crate) struct CachedProgram {
+    /// Generated GLSL of each stage, compiled again if the binary is rejected.
+    pub(crate) sources: Vec<(naga::ShaderStage, String)>,
+    /// Format and contents of the program binary, if the driver provided one.
+    pub(crate) binary: Option<(u32, Vec<u8>)>,
+    pub(crate) sampler_map: SamplerBindMap,
+    /// Bindings assigned by name when they are not explicit in the shaders.
+    pub(crate) name_bindings: Vec<(String, BindingRegister, u8)>,
+}
+
+/// Cache of linked programs, keyed by the hash of their shaders
+/// and pipeline layout.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    pub(crate) programs: Mutex<FastHashMap<u64, CachedProgram>>,
+}
+
+const PIPELINE_CACHE_MAGIC: &[u8; 8] = b"GFXGLPC1";
+
+/// Reads the serialized pipeline cache data.
+struct CacheReader<'a>(&'a [u8]);
+
+impl<'a> CacheReader<'a> {
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.0.len() < count {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(count);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut raw = [0; 4];
+        raw.copy_from_slice(self.bytes(4)?);
+        Some(u32::from_le_bytes(raw))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(self.bytes(8)?);
+        Some(u64::from_le_bytes(raw))
+    }
+
+    fn blob(&mut self) -> Option<&'a [u8]> {
+        let count = self.u32()? as usize;
+        self.bytes(count)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.blob()?.to_vec()).ok()
+    }
+}
+
+fn write_blob(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn stage_to_u8(stage: naga::ShaderStage) -> u8 {
+    match stage {
+        naga::ShaderStage::Vertex => 0,
+        naga::ShaderStage::Fragment => 1,
+        naga::ShaderStage::Compute => 2,
+    }
+}
+
+fn stage_from_u8(value: u8) -> Option<naga::ShaderStage> {
+    match value {
+        0 => Some(naga::ShaderStage::Vertex),
+        1 => Some(naga::ShaderStage::Fragment),
+        2 => Some(naga::ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+fn register_to_u8(register: BindingRegister) -> u8 {
+    match register {
+        BindingRegister::Textures => 0,
+        BindingRegister::UniformBuffers => 1,
+        BindingRegister::StorageBuffers => 2,
+    }
+}
+
+fn register_from_u8(value: u8) -> Option<BindingRegister> {
+    match value {
+        0 => Some(BindingRegister::Textures),
+        1 => Some(BindingRegister::UniformBuffers),
+        2 => Some(BindingRegister::StorageBuffers),
+        _ => None,
+    }
+}
+
+impl PipelineCache {
+    /// Serializes the cache, tagged with the driver that produced the binaries.
+    pub(crate) fn to_data(&self, driver: &str) -> Vec<u8> {
+        let programs = self.programs.lock();
+        let mut out = PIPELINE_CACHE_MAGIC.to_vec();
+        write_blob(&mut out, driver.as_bytes());
+        out.extend_from_slice(&(programs.len() as u32).to_le_bytes());
+        for (key, program) in programs.iter() {
+            out.extend_from_slice(&key.to_le_bytes());
+            out.extend_from_slice(&(program.sources.len() as u32).to_le_bytes());
+            for &(stage, ref source) in program.sources.iter() {
+                out.push(stage_to_u8(stage));
+                write_blob(&mut out, source.as_bytes());
+            }
+            match program.binary {
+                Some((format, ref data)) => {
+                    out.push(1);
+                    out.extend_from_slice(&format.to_le_bytes());
+                    write_blob(&mut out, data);
+                }
+                None => out.push(0),
+            }
+            for slot in program.sampler_map.iter() {
+                out.push(slot.unwrap_or(!0));
+            }
+            out.extend_from_slice(&(program.name_bindings.len() as u32).to_le_bytes());
+            for &(ref name, register, slot) in program.name_bindings.iter() {
+                write_blob(&mut out, name.as_bytes());
+                out.push(register_to_u8(register));
+                out.push(slot);
+            }
+        }
+        out
+    }
+
+    /// Parses the data produced by `to_data`. Returns `None` if the data is
+    /// malformed or was produced by a different driver.
+    pub(crate) fn from_data(data: &[u8], driver: &str) -> Option<Self> {
+        let mut reader = CacheReader(data);
+        if reader.bytes(PIPELINE_CACHE_MAGIC.len())? != PIPELINE_CACHE_MAGIC
+            || reader.blob()? != driver.as_bytes()
+        {
+            return None;
+        }
+        let mut programs = FastHashMap::default();
+        for _ in 0..reader.u32()? {
+            let key = reader.u64()?;
+            let mut sources = Vec::new();
+            for _ in 0..reader.u32()? {
+                let stage = stage_from_u8(reader.u8()?)?;
+                sources.push((stage, reader.string()?));
+            }
+            let binary = match reader.u8()? {
+                0 => None,
+                _ => {
+                    let format = reader.u32()?;
+                    Some((format, reader.blob()?.to_vec()))
+                }
+            };
+            let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
+            for slot in sampler_map.iter_mut() {
+                *slot = match reader.u8()? {
+                    0xFF => None,
+                    value => Some(value),
+                };
+            }
+            let mut name_bindings = Vec::new();
+            for _ in 0..reader.u32()? {
+                let name = reader.string()?;
+                let register = register_from_u8(reader.u8()?)?;
+                name_bindings.push((name, register, reader.u8()?));
+            }
+            programs.insert(
+                key,
+                CachedProgram {
+                    sources,
+                    binary,
+                    sampler_map,
+                    name_bindings,
+                },
+            );
+        }
+        if !reader.0.is_empty() {
+            return None;
+        }
+        Some(PipelineCache {
+            programs: Mutex::new(programs),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct GraphicsPipeline {
     pub(crate) program: Program,
@@ -99,9 +330,9 @@ pub struct GraphicsPipeline {
     pub(crate) blend_targets: Vec<pso::ColorBlendDesc>,
     pub(crate) attributes: Vec<AttributeDesc>,
     pub(crate) vertex_buffers: Vec<Option<pso::VertexBufferDesc>>,
-    pub(crate) uniforms: Vec<UniformDesc>,
     pub(crate) rasterizer: pso::Rasterizer,
     pub(crate) depth: Option<pso::DepthTest>,
+    pub(crate) stencil: Option<pso::StencilTest>,
     pub(crate) baked_states: pso::BakedStates,
     pub(crate) sampler_map: SamplerBindMap,
 }
@@ -156,6 +387,34 @@ pub enum ImageType {
     },
 }
 
+impl ImageType {
+    /// Returns a view of a single level and slice of the image, to be
+    /// attached to a framebuffer. Slices are the layers of arrays and cube
+    /// maps, or the depth of 3D textures.
+    pub(crate) fn slice_view(
+        &self,
+        aspects: format::Aspects,
+        level: i::Level,
+        slice: i::Layer,
+    ) -> ImageView {
+        match *self {
+            ImageType::Renderbuffer { raw, .. } => ImageView::Renderbuffer { raw, aspects },
+            ImageType::Texture { target, raw, .. } => ImageView::Texture {
+                target,
+                raw,
+                is_3d: target != glow::TEXTURE_2D,
+                sub: i::SubresourceRange {
+                    aspects,
+                    level_start: level,
+                    level_count: Some(1),
+                    layer_start: slice,
+                    layer_count: Some(1),
+                },
+            },
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 /// Additionally storing the `SamplerDesc` for older OpenGL versions, which
 /// don't support separate sampler objects.
@@ -243,6 +502,33 @@ impl SwapchainImage {
     }
 }
 
+#[derive(Debug)]
+pub struct DescSetLayout {
+    /// Bindings in ascending order.
+    pub(crate) bindings: Vec<pso::DescriptorSetLayoutBinding>,
+    /// Immutable samplers of each binding, empty if it has none.
+    pub(crate) immutable_samplers: Vec<Vec<FatSampler>>,
+    /// Index of the first descriptor of each binding in the set.
+    pub(crate) offsets: Vec<usize>,
+    /// Total number of descriptors in the set.
+    pub(crate) num_descriptors: usize,
+}
+
+impl DescSetLayout {
+    /// Returns the index of the descriptor of a binding array element in the set.
+    pub(crate) fn descriptor_index(
+        &self,
+        binding: pso::DescriptorBinding,
+        array_index: pso::DescriptorArrayIndex,
+    ) -> usize {
+        let index = self
+            .bindings
+            .binary_search_by_key(&binding, |b| b.binding)
+            .unwrap();
+        self.offsets[index] + array_index
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub(crate) enum DescSetBindings {
     Buffer {
@@ -252,15 +538,19 @@ pub(crate) enum DescSetBindings {
         size: i32,
     },
     Texture(Texture, TextureTarget),
-    Sampler(Sampler),
-    SamplerDesc(i::SamplerDesc),
+    /// Texture of a combined image sampler, along with its sampler.
+    TextureSampler(Texture, TextureTarget, FatSampler),
+    Sampler(FatSampler),
+    /// Buffer texture bound as an image, with its access and internal format.
+    Image(Texture, u32, u32),
 }
 
 #[derive(Clone, Debug)]
 pub struct DescriptorSet {
     pub(crate) layout: DescriptorSetLayout,
     //TODO: use `UnsafeCell` instead
-    pub(crate) bindings: Vec<DescSetBindings>,
+    /// Written descriptors, indexed like in `DescSetLayout::descriptor_index`.
+    pub(crate) bindings: Vec<Option<DescSetBindings>>,
 }
 
 #[derive(Debug)]
@@ -273,7 +563,7 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
     ) -> Result<DescriptorSet, pso::AllocationError> {
         Ok(DescriptorSet {
             layout: Arc::clone(layout),
-            bindings: Vec::new(),
+            bindings: vec![None; layout.num_descriptors],
         })
     }
 
@@ -293,6 +583,9 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
 
 pub struct ShaderModule {
     pub(crate) prefer_naga: bool,
+    /// Hash of the SPIR-V, identifying the module in pipeline caches.
+    /// `None` for modules created from naga, which are not cached.
+    pub(crate) hash: Option<u64>,
     #[cfg(feature = "cross")]
     pub(crate) spv: Vec<u32>,
     pub(crate) naga: Option<hal::device::NagaShader>,
@@ -307,6 +600,7 @@ impl fmt::Debug for ShaderModule {
 #[derive(Debug)]
 pub struct Memory {
     pub(crate) properties: Properties,
+    pub(crate) heap_index: usize,
     /// Gl buffer and the target that should be used for map operations.  Image memory is faked and
     /// has no associated buffer, so this will be None for image memory.
     pub(crate) buffer: Option<(RawBuffer, u32)>,
@@ -325,15 +619,31 @@ pub struct RenderPass {
     pub(crate) subpasses: Vec<SubpassDesc>,
 }
 
+impl RenderPass {
+    /// Returns the range of sub-passes using an attachment, from the first one
+    /// (where it gets loaded) to the last one (where it gets stored).
+    pub(crate) fn subpass_range(
+        &self,
+        at_id: pass::AttachmentId,
+    ) -> Option<Range<pass::SubpassId>> {
+        let first = self.subpasses.iter().position(|sp| sp.is_using(at_id))?;
+        let last = self.subpasses.iter().rposition(|sp| sp.is_using(at_id))?;
+        Some(first as pass::SubpassId..last as pass::SubpassId + 1)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SubpassDesc {
     pub(crate) color_attachments: Vec<usize>,
     pub(crate) depth_stencil: Option<usize>,
+    /// Resolve targets, one per color attachment when non-empty.
+    pub(crate) resolve_attachments: Vec<usize>,
+    pub(crate) input_attachments: Vec<usize>,
 }
 
 impl SubpassDesc {
     /// Check if an attachment is used by this sub-pass.
-    pub(crate) fn _attachment_using(&self, at_id: pass::AttachmentId) -> Option<u32> {
+    pub(crate) fn attachment_using(&self, at_id: pass::AttachmentId) -> Option<u32> {
         if self.depth_stencil == Some(at_id) {
             Some(glow::DEPTH_STENCIL_ATTACHMENT)
         } else {
@@ -343,6 +653,13 @@ impl SubpassDesc {
                 .map(|p| glow::COLOR_ATTACHMENT0 + p as u32)
         }
     }
+
+    /// Check if an attachment is referenced in any way by this sub-pass.
+    pub(crate) fn is_using(&self, at_id: pass::AttachmentId) -> bool {
+        self.attachment_using(at_id).is_some()
+            || self.resolve_attachments.contains(&at_id)
+            || self.input_attachments.contains(&at_id)
+    }
 }
 
 #[derive(Debug)]
@@ -354,19 +671,47 @@ pub(crate) struct PipelineLayoutSet {
     /// For buffers, the value is the uniform or storage slot index.
     /// For unused bindings, the value is `!0`
     pub(crate) bindings: Vec<u8>,
+    /// Index of the samplers of combined image samplers in the whole layout,
+    /// indexed by `pso::DescriptorBinding`. The value is `!0` for other bindings.
+    pub(crate) combined_samplers: Vec<u8>,
 }
 
 #[derive(Debug)]
 pub struct PipelineLayout {
     /// Resource mapping for descriptor sets.
     pub(crate) sets: Vec<PipelineLayoutSet>,
+    /// Samplers of the combined image samplers, paired with their textures by the layout.
+    pub(crate) sampler_map: SamplerBindMap,
+    /// Push constant ranges, all read from the same uniform block.
+    pub(crate) push_constant_ranges: Vec<(pso::ShaderStageFlags, Range<u32>)>,
 }
 
+/// Counter of a timeline semaphore, along with the values that pending
+/// submissions set once their sync object is signaled, in submission order.
 #[derive(Debug)]
-// No inter-queue synchronization required for GL.
-pub struct Semaphore;
+pub struct Timeline {
+    pub(crate) value: u64,
+    pub(crate) pending: Vec<(u64, <GlContext as glow::HasContext>::Fence)>,
+}
 
-#[derive(Clone, Debug)]
+// No inter-queue synchronization required for GL, binary semaphores carry no state.
+#[derive(Debug)]
+pub struct Semaphore {
+    pub(crate) timeline: Option<Mutex<Timeline>>,
+}
+
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+impl Semaphore {
+    pub(crate) fn timeline(&self) -> &Mutex<Timeline> {
+        self.timeline
+            .as_ref()
+            .expect("Semaphore is not a timeline semaphore")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct AttributeDesc {
     pub(crate) location: u32,
     pub(crate) offset: u32,
@@ -376,16 +721,104 @@ pub struct AttributeDesc {
     pub(crate) vertex_attrib_fn: VertexAttribFunction,
 }
 
-#[derive(Clone, Debug)]
-pub struct UniformDesc {
-    pub(crate) location: UniformLocation,
-    pub(crate) offset: u32,
-    pub(crate) utype: u32,
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VertexAttribFunction {
     Float,   // glVertexAttribPointer
     Integer, // glVertexAttribIPointer
     Double,  // glVertexAttribLPointer
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BindingRegister, CachedProgram, PipelineCache};
+    use crate::MAX_TEXTURE_SLOTS;
+
+    const DRIVER: &str = "Vendor; Renderer; 4.6";
+
+    fn sample_cache() -> PipelineCache {
+        let cache = PipelineCache::default();
+        let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
+        sampler_map[1] = Some(3);
+        cache.programs.lock().insert(
+            0x0123_4567_89AB_CDEF,
+            CachedProgram {
+                sources: vec![
+                    (naga::ShaderStage::Vertex, "void main() {}".to_string()),
+                    (naga::ShaderStage::Fragment, "void main() { }".to_string()),
+                ],
+                binary: Some((0x8741, vec![1, 2, 3, 4, 5])),
+                sampler_map,
+                name_bindings: vec![
+                    ("tex".to_string(), BindingRegister::Textures, 1),
+                    ("Globals".to_string(), BindingRegister::UniformBuffers, 0),
+                ],
+            },
+        );
+        cache.programs.lock().insert(
+            7,
+            CachedProgram {
+                sources: vec![(naga::ShaderStage::Compute, "void main() {}".to_string())],
+                binary: None,
+                sampler_map: [None; MAX_TEXTURE_SLOTS],
+                name_bindings: vec![("Data".to_string(), BindingRegister::StorageBuffers, 2)],
+            },
+        );
+        cache
+    }
+
+    #[test]
+    fn test_pipeline_cache_round_trip() {
+        let cache = sample_cache();
+        let data = cache.to_data(DRIVER);
+        let restored = PipelineCache::from_data(&data, DRIVER).unwrap();
+
+        let programs = cache.programs.lock();
+        let restored_programs = restored.programs.lock();
+        assert_eq!(restored_programs.len(), programs.len());
+        for (key, program) in programs.iter() {
+            let restored_program = &restored_programs[key];
+            assert_eq!(restored_program.sources, program.sources);
+            assert_eq!(restored_program.binary, program.binary);
+            assert_eq!(restored_program.sampler_map, program.sampler_map);
+            assert_eq!(restored_program.name_bindings, program.name_bindings);
+        }
+        drop(restored_programs);
+        assert_eq!(restored.to_data(DRIVER).len(), data.len());
+    }
+
+    #[test]
+    fn test_pipeline_cache_empty() {
+        let data = PipelineCache::default().to_data(DRIVER);
+        let restored = PipelineCache::from_data(&data, DRIVER).unwrap();
+        assert!(restored.programs.lock().is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_cache_other_driver() {
+        let data = sample_cache().to_data(DRIVER);
+        assert!(PipelineCache::from_data(&data, "Vendor; Renderer; 4.5").is_none());
+    }
+
+    #[test]
+    fn test_pipeline_cache_corrupt() {
+        let data = sample_cache().to_data(DRIVER);
+        assert!(PipelineCache::from_data(&[], DRIVER).is_none());
+        // Every truncation is rejected, without reading past the end.
+        for length in 0..data.len() {
+            assert!(PipelineCache::from_data(&data[..length], DRIVER).is_none());
+        }
+        // So is trailing garbage.
+        let mut extended = data.clone();
+        extended.push(0);
+        assert!(PipelineCache::from_data(&extended, DRIVER).is_none());
+        // And a wrong magic.
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] ^= 0xFF;
+        assert!(PipelineCache::from_data(&wrong_magic, DRIVER).is_none());
+        // As is a program count past the end of the data.
+        let mut huge_count = data;
+        let count_offset = super::PIPELINE_CACHE_MAGIC.len() + 4 + DRIVER.len();
+        huge_count[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(PipelineCache::from_data(&huge_count, DRIVER).is_none());
+    }
+}
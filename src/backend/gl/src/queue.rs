@@ -1,12 +1,16 @@
 use crate::{
-    command as com, device, info::LegacyFeatures, native, state, Backend, Device, GlContext, Share,
-    Starc, Surface, MAX_COLOR_ATTACHMENTS,
+    command as com, conv, device, info::LegacyFeatures, native, pool::BufferMemory, state, Backend,
+    Device, FastHashMap, GlContext, Share, Starc, Surface, MAX_COLOR_ATTACHMENTS,
+    MAX_PUSH_CONSTANTS_SIZE, MAX_TEXTURE_SLOTS,
 };
 
+use hal::query;
+
 use arrayvec::ArrayVec;
 use glow::HasContext;
+use parking_lot::Mutex;
 
-use std::{mem, slice};
+use std::{iter, mem, ops::Range, slice, str, sync::Arc};
 
 // State caching system for command queue.
 //
@@ -27,6 +31,40 @@ struct State {
     num_viewports: usize,
     // Currently set scissor rects.
     num_scissors: usize,
+    // Currently set first scissor rect, restored after scissored clears.
+    scissor: [i32; 4],
+
+    // Shadow copies of the GL state, used to skip redundant calls.
+    // None denotes that we don't know what is currently set.
+    program: Option<native::Program>,
+    active_texture: Option<u32>,
+    // Texture target and texture bound to each unit.
+    textures: [Option<(native::TextureTarget, native::Texture)>; MAX_TEXTURE_SLOTS],
+    samplers: [Option<native::Sampler>; MAX_TEXTURE_SLOTS],
+    // Indexed buffer bindings, keyed by target and index.
+    buffer_ranges: FastHashMap<(u32, u32), (native::RawBuffer, i32, i32)>,
+    // Vertex attributes of the bound VAO, keyed by location.
+    attributes: FastHashMap<u32, (native::AttributeDesc, native::RawBuffer, i32, u32)>,
+    blend: Option<Option<hal::pso::BlendState>>,
+    blend_slots: [Option<Option<hal::pso::BlendState>>; MAX_COLOR_ATTACHMENTS],
+    rasterizer: Option<hal::pso::Rasterizer>,
+    depth: Option<Option<hal::pso::Comparison>>,
+    depth_mask: Option<bool>,
+    stencil: Option<Option<hal::pso::Sided<hal::pso::StencilFace>>>,
+    // Comparison, reference and read mask of the front and back faces.
+    stencil_funcs: [Option<(
+        hal::pso::Comparison,
+        hal::pso::StencilValue,
+        hal::pso::StencilValue,
+    )>; 2],
+    stencil_mask: Option<hal::pso::Sided<hal::pso::StencilValue>>,
+    // Single viewport and depth range covering all the viewports.
+    viewport: Option<([f32; 4], [f64; 2])>,
+    // Single scissor rect covering all the viewports.
+    scissor_rect: Option<[i32; 4]>,
+
+    // Number of GL calls skipped because the state was already set.
+    elided_calls: u64,
 }
 
 impl State {
@@ -38,20 +76,94 @@ impl State {
             index_buffer: None,
             num_viewports: 0,
             num_scissors: 0,
+            scissor: [0; 4],
+            program: None,
+            active_texture: None,
+            textures: [None; MAX_TEXTURE_SLOTS],
+            samplers: [None; MAX_TEXTURE_SLOTS],
+            buffer_ranges: FastHashMap::default(),
+            attributes: FastHashMap::default(),
+            blend: None,
+            blend_slots: [None; MAX_COLOR_ATTACHMENTS],
+            rasterizer: None,
+            depth: None,
+            depth_mask: None,
+            stencil: None,
+            stencil_funcs: [None; 2],
+            stencil_mask: None,
+            viewport: None,
+            scissor_rect: None,
+            elided_calls: 0,
         }
     }
 
     // Invalidate the current state, forcing a complete reset.
     // Required if we allow users to manually inject OpenGL calls.
     fn flush(&mut self) {
-        self.vao = false;
-        self.index_buffer = None;
+        let elided_calls = self.elided_calls;
+        *self = State::new();
+        self.elided_calls = elided_calls;
+    }
+
+    // Record a new value of a piece of state, returning `false` if the
+    // value was already set and the GL call can be skipped.
+    fn update<T: PartialEq>(cache: &mut Option<T>, value: T, elided_calls: &mut u64) -> bool {
+        if cache.as_ref() == Some(&value) {
+            *elided_calls += 1;
+            false
+        } else {
+            *cache = Some(value);
+            true
+        }
+    }
+}
 
-        // TOOD: reset viewports and scissors
-        //       do we need to clear everything from 0..MAX_VIEWPORTS?
+/// Returns the framebuffer attachment point of a view with the aspects,
+/// using the first one for colors.
+fn attachment_point(aspects: hal::format::Aspects) -> u32 {
+    if aspects.contains(hal::format::Aspects::COLOR) {
+        glow::COLOR_ATTACHMENT0
+    } else if aspects == hal::format::Aspects::DEPTH {
+        glow::DEPTH_ATTACHMENT
+    } else if aspects == hal::format::Aspects::STENCIL {
+        glow::STENCIL_ATTACHMENT
+    } else {
+        glow::DEPTH_STENCIL_ATTACHMENT
     }
 }
 
+/// Returns the first slice and the number of slices of an image region.
+/// Slices are the layers of arrays and cube maps, or the depth of 3D textures.
+fn region_slices(
+    image: &native::ImageType,
+    offset_z: i32,
+    depth: u32,
+    layers: &Range<hal::image::Layer>,
+) -> (i32, i32) {
+    match *image {
+        native::ImageType::Texture {
+            target: glow::TEXTURE_3D,
+            ..
+        } => (offset_z, depth as i32),
+        _ => (layers.start as i32, (layers.end - layers.start) as i32),
+    }
+}
+
+/// Returns the number of bytes between the slices of a buffer region.
+fn buffer_slice_pitch(desc: &hal::format::FormatDesc, data: &hal::command::BufferImageCopy) -> u32 {
+    let (block_width, block_height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+    let width = match data.buffer_width {
+        0 => data.image_extent.width,
+        width => width,
+    };
+    let height = match data.buffer_height {
+        0 => data.image_extent.height,
+        height => height,
+    };
+    let row_pitch = (width + block_width - 1) / block_width * (desc.bits as u32 / 8);
+    row_pitch * ((height + block_height - 1) / block_height)
+}
+
 #[derive(Debug)]
 pub struct Queue {
     pub(crate) share: Starc<Share>,
@@ -60,6 +172,8 @@ pub struct Queue {
     state: State,
     fill_buffer: native::RawBuffer,
     fill_data: Box<[u32]>,
+    /// Uniform buffer backing the push constants.
+    push_constants: Option<native::RawBuffer>,
 }
 
 const FILL_DATA_WORDS: usize = 16 << 10;
@@ -83,6 +197,17 @@ impl Queue {
             gl.bind_buffer(glow::COPY_READ_BUFFER, None);
             buffer
         };
+        let push_constants = share.private_caps.push_constants_slot.map(|_| unsafe {
+            let buffer = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::UNIFORM_BUFFER, Some(buffer));
+            gl.buffer_data_size(
+                glow::UNIFORM_BUFFER,
+                MAX_PUSH_CONSTANTS_SIZE as i32,
+                glow::DYNAMIC_DRAW,
+            );
+            gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+            buffer
+        });
         Queue {
             share: share.clone(),
             features,
@@ -90,6 +215,7 @@ impl Queue {
             state: State::new(),
             fill_buffer,
             fill_data: vec![0; FILL_DATA_WORDS].into_boxed_slice(),
+            push_constants,
         }
     }
 
@@ -171,14 +297,34 @@ impl Queue {
     }
     */
 
+    /// The clamp is only applied with `DEPTH_BIAS_CLAMP`, and ignored otherwise.
+    fn set_depth_bias(&self, bias: hal::pso::DepthBias) {
+        let gl = &self.share.context;
+        if bias.clamp != 0.0 && self.share.private_caps.polygon_offset_clamp {
+            unsafe { gl.polygon_offset_clamp(bias.slope_factor, bias.const_factor, bias.clamp) };
+        } else {
+            unsafe { gl.polygon_offset(bias.slope_factor, bias.const_factor) };
+        }
+    }
+
+    fn set_active_texture(&mut self, index: u32) {
+        if State::update(
+            &mut self.state.active_texture,
+            index,
+            &mut self.state.elided_calls,
+        ) {
+            unsafe { self.share.context.active_texture(glow::TEXTURE0 + index) };
+        }
+    }
+
     fn bind_target(&mut self, point: u32, attachment: u32, view: &native::ImageView) {
         Device::bind_target(&self.share.context, point, attachment, view)
     }
 
-    fn _unbind_target(&mut self, point: u32, attachment: u32) {
+    fn unbind_target(&mut self, point: u32, attachment: u32) {
         let gl = &self.share.context;
-        // TODO: Find workaround or use explicit `textarget` with the other `framebuffer_texture`
-        unsafe { gl.framebuffer_texture(point, attachment, None, 0) };
+        // Detaching through the renderbuffer entry point works for any kind of attachment.
+        unsafe { gl.framebuffer_renderbuffer(point, attachment, glow::RENDERBUFFER, None) };
     }
 
     /// Return a reference to a stored data object.
@@ -214,7 +360,10 @@ impl Queue {
             .legacy_features
             .contains(LegacyFeatures::INDIRECT_EXECUTION)
         {
-            unsafe { gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, None) };
+            unsafe {
+                gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, None);
+                gl.bind_buffer(glow::DISPATCH_INDIRECT_BUFFER, None);
+            }
         }
 
         // Unbind index buffers
@@ -228,14 +377,66 @@ impl Queue {
             gl.depth_range_f32(0.0, 1.0);
             gl.scissor(0, 0, 0, 0);
         };
+        self.state.viewport = Some(([0.0; 4], [0.0, 1.0]));
+        self.state.scissor = [0; 4];
+        self.state.scissor_rect = Some([0; 4]);
+    }
+
+    /// Number of GL calls skipped so far, because the state they would
+    /// set was already current.
+    pub fn elided_calls(&self) -> u64 {
+        self.state.elided_calls
+    }
+
+    /// Replays the commands of a command buffer stored in the locked memory
+    /// of its pool, executing the secondary command buffers in place.
+    fn execute(
+        &mut self,
+        owner: &Arc<Mutex<BufferMemory>>,
+        memory: &BufferMemory,
+        buf: com::BufferSlice,
+        id: u64,
+    ) {
+        let buffer = match *memory {
+            BufferMemory::Linear(ref buffer) => buffer,
+            BufferMemory::Individual { ref storage, .. } => storage.get(&id).unwrap(),
+        };
+
+        assert!(buffer.commands.len() >= (buf.offset + buf.size) as usize);
+        let commands = &buffer.commands[buf.offset as usize..(buf.offset + buf.size) as usize];
+        for com in commands {
+            log::trace!("Execute command:{:?}", com);
+            match *com {
+                com::Command::ExecuteCommands(ref secondary) => {
+                    let secondary_memory = secondary
+                        .memory
+                        .upgrade()
+                        .expect("Secondary command buffer pool was destroyed");
+                    if Arc::ptr_eq(&secondary_memory, owner) {
+                        self.execute(owner, memory, secondary.buf, secondary.id);
+                    } else {
+                        let locked = secondary_memory.try_lock().expect(
+                            "Trying to execute a secondary command buffer, while memory is in-use.",
+                        );
+                        self.execute(&secondary_memory, &locked, secondary.buf, secondary.id);
+                    }
+                }
+                _ => self.process(com, &buffer.data),
+            }
+        }
     }
 
     fn process(&mut self, cmd: &com::Command, data_buf: &[u8]) {
         match *cmd {
             com::Command::BindIndexBuffer(buffer) => {
                 let gl = &self.share.context;
-                self.state.index_buffer = Some(buffer);
-                unsafe { gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(buffer)) };
+                if State::update(
+                    &mut self.state.index_buffer,
+                    buffer,
+                    &mut self.state.elided_calls,
+                ) {
+                    unsafe { gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(buffer)) };
+                }
             }
             //          com::Command::BindVertexBuffers(_data_ptr) =>
             com::Command::Draw {
@@ -365,6 +566,194 @@ impl Queue {
                     log::error!("Instanced indexed drawing is not supported");
                 }
             }
+            com::Command::DrawIndirect {
+                primitive,
+                index_type,
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => {
+                let gl = &self.share.context;
+                unsafe { gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer)) };
+                if draw_count > 1 && self.features.contains(hal::Features::MULTI_DRAW_INDIRECT) {
+                    unsafe {
+                        match index_type {
+                            Some(index_type) => gl.multi_draw_elements_indirect_offset(
+                                primitive,
+                                index_type,
+                                offset as i32,
+                                draw_count as i32,
+                                stride as i32,
+                            ),
+                            None => gl.multi_draw_arrays_indirect_offset(
+                                primitive,
+                                offset as i32,
+                                draw_count as i32,
+                                stride as i32,
+                            ),
+                        }
+                    }
+                } else {
+                    // Zero stride means tightly packed draw parameters.
+                    let stride = match (stride, index_type) {
+                        (0, Some(_)) => mem::size_of::<[u32; 5]>() as hal::buffer::Offset,
+                        (0, None) => mem::size_of::<[u32; 4]>() as hal::buffer::Offset,
+                        (stride, _) => stride as hal::buffer::Offset,
+                    };
+                    for i in 0..draw_count as hal::buffer::Offset {
+                        let offset = (offset + i * stride) as i32;
+                        unsafe {
+                            match index_type {
+                                Some(index_type) => {
+                                    gl.draw_elements_indirect_offset(primitive, index_type, offset)
+                                }
+                                None => gl.draw_arrays_indirect_offset(primitive, offset),
+                            }
+                        }
+                    }
+                }
+            }
+            com::Command::DrawIndirectCount {
+                primitive,
+                index_type,
+                buffer,
+                offset,
+                count_buffer,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            } => {
+                // Support is given by the `DRAW_INDIRECT_COUNT` feature.
+                let gl = &self.share.context;
+                unsafe {
+                    gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                    gl.bind_buffer(glow::PARAMETER_BUFFER, Some(count_buffer));
+                    match index_type {
+                        Some(index_type) => gl.multi_draw_elements_indirect_count_offset(
+                            primitive,
+                            index_type,
+                            offset as i32,
+                            count_buffer_offset as i32,
+                            max_draw_count as i32,
+                            stride as i32,
+                        ),
+                        None => gl.multi_draw_arrays_indirect_count_offset(
+                            primitive,
+                            offset as i32,
+                            count_buffer_offset as i32,
+                            max_draw_count as i32,
+                            stride as i32,
+                        ),
+                    }
+                    gl.bind_buffer(glow::PARAMETER_BUFFER, None);
+                }
+            }
+            com::Command::SetEvent(ref event) => {
+                let gl = &self.share.context;
+                let state = if self.share.private_caps.sync {
+                    native::EventState::Pending(
+                        unsafe { gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0) }.unwrap(),
+                    )
+                } else {
+                    native::EventState::Signaled
+                };
+                if let native::EventState::Pending(sync) = mem::replace(&mut *event.0.lock(), state)
+                {
+                    unsafe { gl.delete_sync(sync) };
+                }
+            }
+            com::Command::ResetEvent(ref event) => {
+                let gl = &self.share.context;
+                if let native::EventState::Pending(sync) =
+                    mem::replace(&mut *event.0.lock(), native::EventState::Reset)
+                {
+                    unsafe { gl.delete_sync(sync) };
+                }
+            }
+            com::Command::BeginQuery(query, target) => {
+                let gl = &self.share.context;
+                unsafe { gl.begin_query(target, query) };
+            }
+            com::Command::EndQuery(target) => {
+                let gl = &self.share.context;
+                unsafe { gl.end_query(target) };
+            }
+            com::Command::QueryCounter(query) => {
+                let gl = &self.share.context;
+                unsafe { gl.query_counter(query, glow::TIMESTAMP) };
+            }
+            com::Command::CopyQueryPoolResults {
+                ref objects,
+                counters,
+                buffer,
+                offset,
+                stride,
+                flags,
+            } => {
+                let gl = &self.share.context;
+                let value_size = if flags.contains(query::ResultFlags::BITS_64) {
+                    8
+                } else {
+                    4
+                };
+                let availability = flags.contains(query::ResultFlags::WITH_AVAILABILITY);
+                let mut data = vec![0u8; value_size * (counters + availability as usize)];
+                unsafe {
+                    gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(buffer));
+                    for (i, query_objects) in objects.chunks(counters).enumerate() {
+                        let available =
+                            device::read_query_results(gl, query_objects, flags, &mut data);
+                        let dst_offset =
+                            offset + i as hal::buffer::Offset * stride as hal::buffer::Offset;
+                        // Only the availability is written when the results aren't there.
+                        let skip = if available || flags.contains(query::ResultFlags::PARTIAL) {
+                            0
+                        } else if availability {
+                            value_size * counters
+                        } else {
+                            continue;
+                        };
+                        gl.buffer_sub_data_u8_slice(
+                            glow::COPY_WRITE_BUFFER,
+                            (dst_offset + skip as hal::buffer::Offset) as i32,
+                            &data[skip..],
+                        );
+                    }
+                    gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
+                }
+            }
+            com::Command::ExecuteCommands(_) => {
+                unreachable!("Secondary command buffers are replayed by `execute`")
+            }
+            com::Command::InsertDebugMarker(label) => {
+                let gl = &self.share.context;
+                if gl.supports_debug() {
+                    let message = str::from_utf8(Self::get_raw(data_buf, label)).unwrap();
+                    unsafe {
+                        gl.debug_message_insert(
+                            glow::DEBUG_SOURCE_APPLICATION,
+                            glow::DEBUG_TYPE_MARKER,
+                            0,
+                            glow::DEBUG_SEVERITY_NOTIFICATION,
+                            message,
+                        );
+                    }
+                }
+            }
+            com::Command::PushDebugGroup(label) => {
+                let gl = &self.share.context;
+                if gl.supports_debug() {
+                    let message = str::from_utf8(Self::get_raw(data_buf, label)).unwrap();
+                    unsafe { gl.push_debug_group(glow::DEBUG_SOURCE_APPLICATION, 0, message) };
+                }
+            }
+            com::Command::PopDebugGroup => {
+                let gl = &self.share.context;
+                if gl.supports_debug() {
+                    unsafe { gl.pop_debug_group() };
+                }
+            }
             com::Command::Dispatch(count) => {
                 // Capability support is given by which queue types will be exposed.
                 // If there is no compute support, this pattern should never be reached
@@ -378,7 +767,7 @@ impl Queue {
                 // because no queue with compute capability can be created.
                 let gl = &self.share.context;
                 unsafe {
-                    gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                    gl.bind_buffer(glow::DISPATCH_INDIRECT_BUFFER, Some(buffer));
                     // TODO: possible integer conversion issue
                     gl.dispatch_compute_indirect(offset as _);
                 }
@@ -402,6 +791,13 @@ impl Queue {
                 if num_viewports == 1 {
                     let view = viewports[0];
                     let depth_range = depth_ranges[0];
+                    if !State::update(
+                        &mut self.state.viewport,
+                        (view, depth_range),
+                        &mut self.state.elided_calls,
+                    ) {
+                        return;
+                    }
                     unsafe {
                         gl.viewport(
                             view[0] as i32,
@@ -417,6 +813,7 @@ impl Queue {
                         }
                     };
                 } else if num_viewports > 1 {
+                    self.state.viewport = None;
                     // Support for these functions is coupled with the support
                     // of multiple viewports.
                     unsafe {
@@ -437,10 +834,20 @@ impl Queue {
                     0 < num_scissors && num_scissors <= self.share.public_caps.limits.max_viewports
                 );
 
+                if first_scissor == 0 {
+                    self.state.scissor = scissors[0];
+                }
                 if num_scissors == 1 {
                     let scissor = scissors[0];
-                    unsafe { gl.scissor(scissor[0], scissor[1], scissor[2], scissor[3]) };
+                    if State::update(
+                        &mut self.state.scissor_rect,
+                        scissor,
+                        &mut self.state.elided_calls,
+                    ) {
+                        unsafe { gl.scissor(scissor[0], scissor[1], scissor[2], scissor[3]) };
+                    }
                 } else {
+                    self.state.scissor_rect = None;
                     // Support for this function is coupled with the support
                     // of multiple viewports.
                     unsafe { gl.scissor_slice(first_scissor, num_scissors as i32, scissors) };
@@ -482,6 +889,22 @@ impl Queue {
                 };
             },
             com::Command::ClearTexture(_color) => unimplemented!(),
+            com::Command::SetClearRect(rect) => unsafe {
+                let gl = &self.share.context;
+                match rect {
+                    Some(rect) => {
+                        gl.enable(glow::SCISSOR_TEST);
+                        gl.scissor(rect.x as i32, rect.y as i32, rect.w as i32, rect.h as i32);
+                        self.state.scissor_rect = None;
+                    }
+                    None => {
+                        gl.disable(glow::SCISSOR_TEST);
+                        let [x, y, w, h] = self.state.scissor;
+                        gl.scissor(x, y, w, h);
+                        self.state.scissor_rect = Some(self.state.scissor);
+                    }
+                }
+            },
             com::Command::BindFramebuffer {
                 target,
                 framebuffer,
@@ -494,15 +917,23 @@ impl Queue {
                     self.bind_target(target, glow::COLOR_ATTACHMENT0 + i as u32, view);
                 }
                 if let Some(ref view) = *depth_stencil {
-                    let aspects = view.aspects();
-                    let attachment = if aspects == hal::format::Aspects::DEPTH {
-                        glow::DEPTH_ATTACHMENT
-                    } else if aspects == hal::format::Aspects::STENCIL {
-                        glow::STENCIL_ATTACHMENT
-                    } else {
-                        glow::DEPTH_STENCIL_ATTACHMENT
-                    };
-                    self.bind_target(target, attachment, view);
+                    self.bind_target(target, attachment_point(view.aspects()), view);
+                }
+            }
+            com::Command::UnbindAttachment(target, attachment) => {
+                self.unbind_target(target, attachment);
+            }
+            com::Command::InvalidateFramebuffer {
+                framebuffer,
+                ref attachments,
+            } => {
+                // Store ops are only a hint, skipped when invalidation is unsupported.
+                if self.share.private_caps.invalidate_framebuffer {
+                    let gl = &self.share.context;
+                    unsafe {
+                        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(framebuffer));
+                        gl.invalidate_framebuffer(glow::DRAW_FRAMEBUFFER, attachments);
+                    }
                 }
             }
             com::Command::FillBuffer(buffer, ref range, value) => {
@@ -542,6 +973,73 @@ impl Queue {
                     gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
                 }
             }
+            com::Command::UpdateBuffer(buffer, offset, data_ptr) => {
+                // Uploaded through the fill buffer, since the buffers with
+                // `DYNAMIC_STORAGE_BIT` can't be written to directly.
+                let data = Self::get_raw(data_buf, data_ptr);
+                assert!(data.len() <= FILL_DATA_WORDS * 4);
+                let gl = &self.share.context;
+                unsafe {
+                    gl.bind_buffer(glow::COPY_READ_BUFFER, Some(self.fill_buffer));
+                    gl.buffer_sub_data_u8_slice(glow::COPY_READ_BUFFER, 0, data);
+                    gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(buffer));
+                    gl.copy_buffer_sub_data(
+                        glow::COPY_READ_BUFFER,
+                        glow::COPY_WRITE_BUFFER,
+                        0,
+                        offset as i32,
+                        data.len() as i32,
+                    );
+                    gl.bind_buffer(glow::COPY_READ_BUFFER, None);
+                    gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
+                }
+            }
+            com::Command::BlitFramebuffer {
+                ref src,
+                ref dst,
+                ref src_bounds,
+                ref dst_bounds,
+                mask,
+                filter,
+            } => {
+                let gl = &self.share.context;
+                unsafe {
+                    let src_fbo = gl.create_framebuffer().unwrap();
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(src_fbo));
+                    Device::bind_target(
+                        gl,
+                        glow::READ_FRAMEBUFFER,
+                        attachment_point(src.aspects()),
+                        src,
+                    );
+
+                    let dst_fbo = gl.create_framebuffer().unwrap();
+                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(dst_fbo));
+                    Device::bind_target(
+                        gl,
+                        glow::DRAW_FRAMEBUFFER,
+                        attachment_point(dst.aspects()),
+                        dst,
+                    );
+
+                    gl.blit_framebuffer(
+                        src_bounds.start.x,
+                        src_bounds.start.y,
+                        src_bounds.end.x,
+                        src_bounds.end.y,
+                        dst_bounds.start.x,
+                        dst_bounds.start.y,
+                        dst_bounds.end.x,
+                        dst_bounds.end.y,
+                        mask,
+                        filter,
+                    );
+
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                    gl.delete_framebuffer(src_fbo);
+                    gl.delete_framebuffer(dst_fbo);
+                }
+            }
             com::Command::SetDrawColorBuffers(ref indices) => {
                 let gl_indices = indices
                     .iter()
@@ -554,14 +1052,29 @@ impl Queue {
                     .context
                     .patch_parameter_i32(glow::PATCH_VERTICES, num);
             },
-            com::Command::BindProgram(program) => unsafe {
-                self.share.context.use_program(Some(program));
-            },
+            com::Command::BindProgram(program) => {
+                if State::update(
+                    &mut self.state.program,
+                    program,
+                    &mut self.state.elided_calls,
+                ) {
+                    unsafe { self.share.context.use_program(Some(program)) };
+                }
+            }
             com::Command::SetBlend(ref blend) => {
-                state::set_blend(&self.share.context, blend);
+                if State::update(&mut self.state.blend, *blend, &mut self.state.elided_calls) {
+                    state::set_blend(&self.share.context, blend);
+                    self.state.blend_slots = [Some(*blend); MAX_COLOR_ATTACHMENTS];
+                }
             }
             com::Command::SetBlendSlot(slot, ref blend) => {
                 if self.share.private_caps.draw_buffers {
+                    if let Some(cache) = self.state.blend_slots.get_mut(slot as usize) {
+                        if !State::update(cache, *blend, &mut self.state.elided_calls) {
+                            return;
+                        }
+                    }
+                    self.state.blend = None;
                     state::set_blend_slot(&self.share.context, slot, blend, &self.features);
                 } else {
                     log::warn!("Draw buffers are not supported");
@@ -580,6 +1093,13 @@ impl Queue {
                 } = attribute;
                 let gl = &self.share.context;
 
+                let binding = (attribute.clone(), handle, stride, rate);
+                if self.state.attributes.get(&location) == Some(&binding) {
+                    self.state.elided_calls += 1;
+                    return;
+                }
+                self.state.attributes.insert(location, binding);
+
                 gl.bind_buffer(glow::ARRAY_BUFFER, Some(handle));
 
                 match vertex_attrib_fn {
@@ -661,87 +1181,219 @@ impl Queue {
                     gl.bind_buffer(copy_dst_target, None);
                 }
             },
-            com::Command::CopyBufferToTexture {
+            com::Command::CopyBufferToImage {
                 src_buffer,
-                dst_texture,
-                texture_target,
-                texture_format,
-                pixel_type,
+                dst_image,
+                ref format_desc,
                 ref data,
-            } => unsafe {
-                // TODO: Fix active texture
-                assert_eq!(data.image_offset.z, 0);
+            } => {
+                let (texture_target, dst_texture, texture_format, pixel_type) = match dst_image {
+                    native::ImageType::Texture {
+                        target,
+                        raw,
+                        format,
+                        pixel_type,
+                        ..
+                    } => (target, raw, format, pixel_type),
+                    native::ImageType::Renderbuffer { .. } => {
+                        // Images copied from buffers are textures, unless multisampled.
+                        log::error!("CopyBufferToImage is not supported for renderbuffers");
+                        return;
+                    }
+                };
 
+                self.set_active_texture(0);
+                self.state.textures[0] = Some((texture_target, dst_texture));
                 let gl = &self.share.context;
 
-                gl.active_texture(glow::TEXTURE0);
-                gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(src_buffer));
-
-                match texture_target {
-                    glow::TEXTURE_2D => {
-                        gl.bind_texture(glow::TEXTURE_2D, Some(dst_texture));
-                        gl.tex_sub_image_2d(
-                            glow::TEXTURE_2D,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
+                let level = data.image_layers.level as i32;
+                let (x, y) = (data.image_offset.x, data.image_offset.y);
+                let width = data.image_extent.width as i32;
+                let height = data.image_extent.height as i32;
+                let slice_pitch = buffer_slice_pitch(format_desc, data);
+                let (block_width, block_height) =
+                    (format_desc.dim.0 as u32, format_desc.dim.1 as u32);
+                let compressed_size = (data.image_extent.width + block_width - 1) / block_width
+                    * ((data.image_extent.height + block_height - 1) / block_height)
+                    * (format_desc.bits as u32 / 8);
+
+                // Uploads the region from the buffer offset, either into a 2D target,
+                // or into the slices of a 3D target.
+                let upload = |target: u32, slices: Option<(i32, i32)>, offset: u32| unsafe {
+                    match slices {
+                        None if pixel_type == glow::NONE => gl.compressed_tex_sub_image_2d_offset(
+                            target,
+                            level,
+                            x,
+                            y,
+                            width,
+                            height,
+                            texture_format,
+                            offset as i32,
+                            compressed_size as i32,
+                        ),
+                        None => gl.tex_sub_image_2d(
+                            target,
+                            level,
+                            x,
+                            y,
+                            width,
+                            height,
                             texture_format,
                             pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
-                    }
-                    glow::TEXTURE_2D_ARRAY => {
-                        gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(dst_texture));
-                        gl.tex_sub_image_3d(
-                            glow::TEXTURE_2D_ARRAY,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_layers.layers.start as i32,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
-                            data.image_layers.layers.end as i32
-                                - data.image_layers.layers.start as i32,
+                            glow::PixelUnpackData::BufferOffset(offset),
+                        ),
+                        Some((z, depth)) if pixel_type == glow::NONE => gl
+                            .compressed_tex_sub_image_3d_offset(
+                                target,
+                                level,
+                                x,
+                                y,
+                                z,
+                                width,
+                                height,
+                                depth,
+                                texture_format,
+                                offset as i32,
+                                (compressed_size * depth as u32) as i32,
+                            ),
+                        Some((z, depth)) => gl.tex_sub_image_3d(
+                            target,
+                            level,
+                            x,
+                            y,
+                            z,
+                            width,
+                            height,
+                            depth,
                             texture_format,
                             pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
+                            glow::PixelUnpackData::BufferOffset(offset),
+                        ),
                     }
-                    _ => unimplemented!(),
+                };
+
+                unsafe {
+                    gl.bind_texture(texture_target, Some(dst_texture));
+                    gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(src_buffer));
+                    gl.pixel_store_i32(glow::UNPACK_ROW_LENGTH, data.buffer_width as i32);
+                    gl.pixel_store_i32(glow::UNPACK_IMAGE_HEIGHT, data.buffer_height as i32);
                 }
 
-                gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
-            },
-            com::Command::CopyBufferToRenderbuffer(..) => {
-                log::error!("CopyBufferToRenderbuffer is not implemented");
+                let buffer_offset = data.buffer_offset as u32;
+                match texture_target {
+                    glow::TEXTURE_2D => upload(glow::TEXTURE_2D, None, buffer_offset),
+                    glow::TEXTURE_CUBE_MAP => {
+                        // Cube map faces are only addressable as separate 2D targets.
+                        for (i, face) in data.image_layers.layers.clone().enumerate() {
+                            upload(
+                                glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                                None,
+                                buffer_offset + i as u32 * slice_pitch,
+                            );
+                        }
+                    }
+                    _ => upload(
+                        texture_target,
+                        Some(region_slices(
+                            &dst_image,
+                            data.image_offset.z,
+                            data.image_extent.depth,
+                            &data.image_layers.layers,
+                        )),
+                        buffer_offset,
+                    ),
+                }
+
+                unsafe {
+                    gl.pixel_store_i32(glow::UNPACK_ROW_LENGTH, 0);
+                    gl.pixel_store_i32(glow::UNPACK_IMAGE_HEIGHT, 0);
+                    gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
+                }
             }
-            com::Command::CopyTextureToBuffer {
-                src_texture,
-                texture_target,
-                texture_format,
-                pixel_type,
+            com::Command::CopyImageToBuffer {
+                src_image,
+                ref format_desc,
                 dst_buffer,
                 ref data,
             } => {
-                if self.share.private_caps.get_tex_image {
-                    // TODO: Fix active texture
+                let (texture_target, src_texture, texture_format, pixel_type) = match src_image {
+                    native::ImageType::Texture {
+                        target,
+                        raw,
+                        format,
+                        pixel_type,
+                        ..
+                    } => (target, raw, format, pixel_type),
+                    native::ImageType::Renderbuffer { .. } => {
+                        // Images copied to buffers are textures, unless multisampled.
+                        log::error!("CopyImageToBuffer is not supported for renderbuffers");
+                        return;
+                    }
+                };
+                if pixel_type == glow::NONE {
+                    log::error!("CopyImageToBuffer is not supported for compressed images");
+                    return;
+                }
+
+                if self.share.private_caps.framebuffer {
+                    let gl = &self.share.context;
+                    let aspects = data.image_layers.aspects;
+                    let (first_slice, num_slices) = region_slices(
+                        &src_image,
+                        data.image_offset.z,
+                        data.image_extent.depth,
+                        &data.image_layers.layers,
+                    );
+                    let slice_pitch = buffer_slice_pitch(format_desc, data);
+                    unsafe {
+                        let fbo = gl.create_framebuffer().unwrap();
+                        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(fbo));
+                        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(dst_buffer));
+                        gl.pixel_store_i32(glow::PACK_ROW_LENGTH, data.buffer_width as i32);
+
+                        for i in 0..num_slices {
+                            Device::bind_target(
+                                gl,
+                                glow::READ_FRAMEBUFFER,
+                                attachment_point(aspects),
+                                &src_image.slice_view(
+                                    aspects,
+                                    data.image_layers.level,
+                                    (first_slice + i) as _,
+                                ),
+                            );
+                            gl.read_pixels(
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                texture_format,
+                                pixel_type,
+                                glow::PixelPackData::BufferOffset(
+                                    data.buffer_offset as u32 + i as u32 * slice_pitch,
+                                ),
+                            );
+                        }
+
+                        gl.pixel_store_i32(glow::PACK_ROW_LENGTH, 0);
+                        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+                        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+                        gl.delete_framebuffer(fbo);
+                    }
+                } else if self.share.private_caps.get_tex_image {
                     // TODO: handle partial copies gracefully
                     assert_eq!(data.image_offset, hal::image::Offset { x: 0, y: 0, z: 0 });
                     assert_eq!(texture_target, glow::TEXTURE_2D);
+                    self.set_active_texture(0);
+                    self.state.textures[0] = Some((texture_target, src_texture));
                     let gl = &self.share.context;
                     unsafe {
-                        gl.active_texture(glow::TEXTURE0);
                         gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(dst_buffer));
                         gl.bind_texture(glow::TEXTURE_2D, Some(src_texture));
                         gl.get_tex_image(
                             glow::TEXTURE_2D,
                             data.image_layers.level as _,
-                            //data.image_offset.x,
-                            //data.image_offset.y,
-                            //data.image_extent.width as _,
-                            //data.image_extent.height as _,
                             texture_format,
                             pixel_type,
                             glow::PixelPackData::BufferOffset(data.buffer_offset as u32),
@@ -749,61 +1401,88 @@ impl Queue {
                         gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
                     }
                 } else {
-                    //TODO: use FBO
-                    log::error!("CopyTextureToBuffer is not implemented on GLES");
+                    log::error!("CopyImageToBuffer is not supported without framebuffers");
                 }
             }
-            com::Command::CopyRenderbufferToBuffer(..) => {
-                //TODO: use FBO
-                log::error!("CopyRenderbufferToBuffer is not implemented");
-            }
-            com::Command::CopyImageToTexture(..) => {
-                //TODO: use FBO
-                log::error!("CopyImageToTexture is not implemented");
-            }
-            com::Command::CopyImageToRenderbuffer {
+            com::Command::CopyImage {
                 src_image,
-                dst_renderbuffer,
-                dst_format,
+                dst_image,
                 ref data,
             } => {
                 let gl = &self.share.context;
+                let (src_slice, num_slices) = region_slices(
+                    &src_image,
+                    data.src_offset.z,
+                    data.extent.depth,
+                    &data.src_subresource.layers,
+                );
+                let (dst_slice, _) = region_slices(
+                    &dst_image,
+                    data.dst_offset.z,
+                    data.extent.depth,
+                    &data.dst_subresource.layers,
+                );
 
-                if data.src_subresource.aspects != hal::format::Aspects::COLOR
-                    || data.dst_subresource.aspects != hal::format::Aspects::COLOR
-                {
-                    unimplemented!()
-                }
-
-                match src_image {
-                    native::ImageType::Texture { .. } => unimplemented!(),
-                    native::ImageType::Renderbuffer {
-                        raw: src_renderbuffer,
-                        format: src_format,
-                    } => {
-                        if src_format != dst_format {
-                            unimplemented!()
-                        }
-
-                        unsafe {
-                            let src_fbo = gl.create_framebuffer().unwrap();
-                            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(src_fbo));
-                            gl.framebuffer_renderbuffer(
+                match (src_image, dst_image) {
+                    (
+                        native::ImageType::Texture {
+                            target: src_target,
+                            raw: src_texture,
+                            ..
+                        },
+                        native::ImageType::Texture {
+                            target: dst_target,
+                            raw: dst_texture,
+                            ..
+                        },
+                    ) if self.share.private_caps.copy_image => unsafe {
+                        gl.copy_image_sub_data(
+                            src_texture,
+                            src_target,
+                            data.src_subresource.level as _,
+                            data.src_offset.x,
+                            data.src_offset.y,
+                            src_slice,
+                            dst_texture,
+                            dst_target,
+                            data.dst_subresource.level as _,
+                            data.dst_offset.x,
+                            data.dst_offset.y,
+                            dst_slice,
+                            data.extent.width as _,
+                            data.extent.height as _,
+                            num_slices,
+                        );
+                    },
+                    _ => unsafe {
+                        let src_aspects = data.src_subresource.aspects;
+                        let dst_aspects = data.dst_subresource.aspects;
+                        let src_fbo = gl.create_framebuffer().unwrap();
+                        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(src_fbo));
+                        let dst_fbo = gl.create_framebuffer().unwrap();
+                        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(dst_fbo));
+
+                        for i in 0..num_slices {
+                            Device::bind_target(
+                                gl,
                                 glow::READ_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0,
-                                glow::RENDERBUFFER,
-                                Some(src_renderbuffer),
+                                attachment_point(src_aspects),
+                                &src_image.slice_view(
+                                    src_aspects,
+                                    data.src_subresource.level,
+                                    (src_slice + i) as _,
+                                ),
                             );
-
-                            let dst_fbo = gl.create_framebuffer().unwrap();
-                            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(dst_fbo));
-                            gl.framebuffer_renderbuffer(
+                            Device::bind_target(
+                                gl,
                                 glow::DRAW_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0,
-                                glow::RENDERBUFFER,
-                                Some(dst_renderbuffer),
+                                attachment_point(dst_aspects),
+                                &dst_image.slice_view(
+                                    dst_aspects,
+                                    data.dst_subresource.level,
+                                    (dst_slice + i) as _,
+                                ),
                             );
-
                             gl.blit_framebuffer(
                                 data.src_offset.x,
                                 data.src_offset.y,
@@ -813,34 +1492,52 @@ impl Queue {
                                 data.dst_offset.y,
                                 data.dst_offset.x + data.extent.width as i32,
                                 data.dst_offset.y + data.extent.height as i32,
-                                glow::COLOR_BUFFER_BIT,
+                                com::blit_mask(src_aspects),
                                 glow::NEAREST,
                             );
-
-                            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-
-                            gl.delete_framebuffer(src_fbo);
-                            gl.delete_framebuffer(dst_fbo);
                         }
-                    }
+
+                        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                        gl.delete_framebuffer(src_fbo);
+                        gl.delete_framebuffer(dst_fbo);
+                    },
                 }
             }
-            com::Command::BindBufferRange(target, index, buffer, offset, size) => unsafe {
-                let gl = &self.share.context;
-                gl.bind_buffer_range(target, index, Some(buffer), offset, size);
-            },
-            com::Command::BindTexture(index, texture, textype) => unsafe {
-                let gl = &self.share.context;
-                gl.active_texture(glow::TEXTURE0 + index);
-                gl.bind_texture(textype, Some(texture));
-            },
-            com::Command::BindSampler(index, sampler) => unsafe {
+            com::Command::BindBufferRange(target, index, buffer, offset, size) => {
+                let range = (buffer, offset, size);
+                if self.state.buffer_ranges.get(&(target, index)) == Some(&range) {
+                    self.state.elided_calls += 1;
+                    return;
+                }
+                self.state.buffer_ranges.insert((target, index), range);
                 let gl = &self.share.context;
-                gl.bind_sampler(index, Some(sampler));
+                unsafe { gl.bind_buffer_range(target, index, Some(buffer), offset, size) };
+            }
+            com::Command::BindTexture(index, texture, textype) => {
+                if let Some(cache) = self.state.textures.get_mut(index as usize) {
+                    if !State::update(cache, (textype, texture), &mut self.state.elided_calls) {
+                        return;
+                    }
+                }
+                self.set_active_texture(index);
+                unsafe { self.share.context.bind_texture(textype, Some(texture)) };
+            }
+            com::Command::BindImageTexture(unit, texture, access, format) => unsafe {
+                self.share
+                    .context
+                    .bind_image_texture(unit, texture, 0, false, 0, access, format)
             },
+            com::Command::BindSampler(index, sampler) => {
+                if let Some(cache) = self.state.samplers.get_mut(index as usize) {
+                    if !State::update(cache, sampler, &mut self.state.elided_calls) {
+                        return;
+                    }
+                }
+                unsafe { self.share.context.bind_sampler(index, Some(sampler)) };
+            }
             com::Command::SetTextureSamplerSettings(index, textype, ref sinfo) => unsafe {
+                self.set_active_texture(index);
                 let gl = &self.share.context;
-                gl.active_texture(glow::TEXTURE0 + index);
                 // TODO: Optimization: only change texture properties that have changed.
                 device::set_sampler_info(
                     &sinfo,
@@ -895,77 +1592,23 @@ impl Queue {
             com::Command::UnbindAttribute(slot) => unsafe {
             self.share.context.DisableVertexAttribArray(slot as gl::types::GLuint);
             },*/
-            com::Command::BindUniform {
-                ref uniform,
-                buffer,
-            } => {
-                let gl = &self.share.context;
-
-                unsafe {
-                    match uniform.utype {
-                        glow::FLOAT => {
-                            let data = Self::get::<f32>(data_buf, buffer)[0];
-                            gl.uniform_1_f32(Some(&(*uniform.location).clone()), data);
-                        }
-                        glow::FLOAT_VEC2 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 2]>(data_buf, buffer)[0];
-                            gl.uniform_2_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_VEC3 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 3]>(data_buf, buffer)[0];
-                            gl.uniform_3_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_VEC4 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_4_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT => {
-                            let data = Self::get::<i32>(data_buf, buffer)[0];
-                            gl.uniform_1_i32(Some(&(*uniform.location).clone()), data);
-                        }
-                        glow::INT_VEC2 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 2]>(data_buf, buffer)[0];
-                            gl.uniform_2_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT_VEC3 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 3]>(data_buf, buffer)[0];
-                            gl.uniform_3_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT_VEC4 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_4_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_MAT2 => {
-                            let data = Self::get::<[f32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_2_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        glow::FLOAT_MAT3 => {
-                            let data = Self::get::<[f32; 9]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_3_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        glow::FLOAT_MAT4 => {
-                            let data = Self::get::<[f32; 16]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_4_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        _ => panic!("Unsupported uniform datatype!"),
+            com::Command::PushConstants(offset, data) => {
+                // All the stages read the push constants from the same block.
+                if let (Some(slot), Some(buffer)) = (
+                    self.share.private_caps.push_constants_slot,
+                    self.push_constants,
+                ) {
+                    let gl = &self.share.context;
+                    self.state
+                        .buffer_ranges
+                        .remove(&(glow::UNIFORM_BUFFER, slot as u32));
+                    unsafe {
+                        gl.bind_buffer_base(glow::UNIFORM_BUFFER, slot as u32, Some(buffer));
+                        gl.buffer_sub_data_u8_slice(
+                            glow::UNIFORM_BUFFER,
+                            offset as i32,
+                            Self::get_raw(data_buf, data),
+                        );
                     }
                 }
             }
@@ -973,6 +1616,13 @@ impl Queue {
                 use hal::pso::FrontFace::*;
                 use hal::pso::PolygonMode::*;
 
+                if !State::update(
+                    &mut self.state.rasterizer,
+                    rasterizer,
+                    &mut self.state.elided_calls,
+                ) {
+                    return;
+                }
                 let gl = &self.share.context;
 
                 unsafe {
@@ -1004,21 +1654,20 @@ impl Queue {
                 };
 
                 if let hal::pso::State::Static(w) = rasterizer.line_width {
-                    if w != 1.0 {
-                        // Default value already 1.0
-                        unsafe { gl.line_width(w) };
-                    }
+                    unsafe { gl.line_width(w) };
                 }
 
                 //TODO: this is not available in GLES
                 //unsafe { gl.polygon_mode(glow::FRONT_AND_BACK, gl_draw) };
 
+                // Dynamic depth bias values are set by `SetDepthBias`.
                 match rasterizer.depth_bias {
-                    Some(hal::pso::State::Static(bias)) => unsafe {
-                        gl.enable(gl_offset);
-                        gl.polygon_offset(bias.slope_factor as _, bias.const_factor as _);
-                    },
-                    _ => unsafe { gl.disable(gl_offset) },
+                    Some(hal::pso::State::Static(bias)) => {
+                        unsafe { gl.enable(gl_offset) };
+                        self.set_depth_bias(bias);
+                    }
+                    Some(hal::pso::State::Dynamic) => unsafe { gl.enable(gl_offset) },
+                    None => unsafe { gl.disable(gl_offset) },
                 }
 
                 if !self.share.info.version.is_embedded {
@@ -1030,32 +1679,81 @@ impl Queue {
                 }
             }
             com::Command::BindDepth(depth_fun) => {
-                use hal::pso::Comparison::*;
-
+                if !State::update(
+                    &mut self.state.depth,
+                    depth_fun,
+                    &mut self.state.elided_calls,
+                ) {
+                    return;
+                }
                 let gl = &self.share.context;
 
                 match depth_fun {
                     Some(depth_fun) => unsafe {
                         gl.enable(glow::DEPTH_TEST);
-
-                        let cmp = match depth_fun {
-                            Never => glow::NEVER,
-                            Less => glow::LESS,
-                            LessEqual => glow::LEQUAL,
-                            Equal => glow::EQUAL,
-                            GreaterEqual => glow::GEQUAL,
-                            Greater => glow::GREATER,
-                            NotEqual => glow::NOTEQUAL,
-                            Always => glow::ALWAYS,
-                        };
-
-                        gl.depth_func(cmp);
+                        gl.depth_func(conv::comparison_to_gl(depth_fun));
                     },
                     None => unsafe {
                         gl.disable(glow::DEPTH_TEST);
                     },
                 }
             }
+            com::Command::BindStencil(faces) => {
+                if !State::update(&mut self.state.stencil, faces, &mut self.state.elided_calls) {
+                    return;
+                }
+                let gl = &self.share.context;
+
+                match faces {
+                    Some(faces) => unsafe {
+                        gl.enable(glow::STENCIL_TEST);
+                        let sides = [(glow::FRONT, faces.front), (glow::BACK, faces.back)];
+                        for &(face, ops) in sides.iter() {
+                            gl.stencil_op_separate(
+                                face,
+                                conv::stencil_op_to_gl(ops.op_fail),
+                                conv::stencil_op_to_gl(ops.op_depth_fail),
+                                conv::stencil_op_to_gl(ops.op_pass),
+                            );
+                        }
+                    },
+                    None => unsafe {
+                        gl.disable(glow::STENCIL_TEST);
+                    },
+                }
+            }
+            com::Command::SetStencilFunc {
+                face,
+                fun,
+                reference,
+                read_mask,
+            } => {
+                let side = if face == glow::BACK { 1 } else { 0 };
+                if !State::update(
+                    &mut self.state.stencil_funcs[side],
+                    (fun, reference, read_mask),
+                    &mut self.state.elided_calls,
+                ) {
+                    return;
+                }
+                unsafe {
+                    self.share.context.stencil_func_separate(
+                        face,
+                        conv::comparison_to_gl(fun),
+                        reference as i32,
+                        read_mask,
+                    )
+                };
+            }
+            com::Command::SetLineWidth(width) => {
+                // The line width of the bound rasterizer is overridden.
+                self.state.rasterizer = None;
+                unsafe { self.share.context.line_width(width) };
+            }
+            com::Command::SetDepthBias(bias) => {
+                self.state.rasterizer = None;
+                self.set_depth_bias(bias);
+            }
             com::Command::SetColorMask(slot, mask) => unsafe {
                 use hal::pso::ColorMask as Cm;
                 if let (true, Some(slot)) = (self.share.private_caps.per_slot_color_mask, slot) {
@@ -1080,20 +1778,37 @@ impl Queue {
                     );
                 }
             },
-            com::Command::SetDepthMask(write) => unsafe {
-                self.share.context.depth_mask(write);
-            },
-            com::Command::SetStencilMask(value) => unsafe {
-                self.share.context.stencil_mask(value);
-            },
-            com::Command::SetStencilMaskSeparate(values) => unsafe {
-                self.share
-                    .context
-                    .stencil_mask_separate(glow::FRONT, values.front);
-                self.share
-                    .context
-                    .stencil_mask_separate(glow::BACK, values.back);
-            },
+            com::Command::SetDepthMask(write) => {
+                if State::update(
+                    &mut self.state.depth_mask,
+                    write,
+                    &mut self.state.elided_calls,
+                ) {
+                    unsafe { self.share.context.depth_mask(write) };
+                }
+            }
+            com::Command::SetStencilMask(value) => {
+                if State::update(
+                    &mut self.state.stencil_mask,
+                    hal::pso::Sided::new(value),
+                    &mut self.state.elided_calls,
+                ) {
+                    unsafe { self.share.context.stencil_mask(value) };
+                }
+            }
+            com::Command::SetStencilMaskSeparate(values) => {
+                if State::update(
+                    &mut self.state.stencil_mask,
+                    values,
+                    &mut self.state.elided_calls,
+                ) {
+                    let gl = &self.share.context;
+                    unsafe {
+                        gl.stencil_mask_separate(glow::FRONT, values.front);
+                        gl.stencil_mask_separate(glow::BACK, values.back);
+                    }
+                }
+            }
             com::Command::MemoryBarrier(mask) => {
                 if self.share.private_caps.memory_barrier {
                     unsafe {
@@ -1120,29 +1835,19 @@ impl hal::queue::Queue<Backend> for Queue {
         Iw: Iterator<Item = (&'a native::Semaphore, hal::pso::PipelineStage)>,
         Is: Iterator<Item = &'a native::Semaphore>,
     {
-        use crate::pool::BufferMemory;
-        {
-            for cmd_buf in command_buffers {
-                let cb = &cmd_buf.data;
-                let memory = cb
-                    .memory
-                    .try_lock()
-                    .expect("Trying to submit a command buffers, while memory is in-use.");
-
-                let buffer = match *memory {
-                    BufferMemory::Linear(ref buffer) => buffer,
-                    BufferMemory::Individual { ref storage, .. } => storage.get(&cb.id).unwrap(),
-                };
+        // The device changes bindings outside of the queue, so the state is only
+        // tracked across the command buffers of a submission.
+        self.state.flush();
+        self.reset_state();
 
-                assert!(buffer.commands.len() >= (cb.buf.offset + cb.buf.size) as usize);
-                let commands = &buffer.commands
-                    [cb.buf.offset as usize..(cb.buf.offset + cb.buf.size) as usize];
-                self.reset_state();
-                for com in commands {
-                    log::trace!("Execute command:{:?}", com);
-                    self.process(com, &buffer.data);
-                }
-            }
+        for cmd_buf in command_buffers {
+            let cb = &cmd_buf.data;
+            let memory = cb
+                .memory
+                .try_lock()
+                .expect("Trying to submit a command buffers, while memory is in-use.");
+
+            self.execute(&cb.memory, &memory, cb.buf, cb.id);
         }
 
         if let Some(fence) = fence {
@@ -1160,6 +1865,50 @@ impl hal::queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a com::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, hal::pso::PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a native::Semaphore, u64)>,
+    {
+        // There is a single queue executing submissions in order, so values set by
+        // earlier submissions are reached in time. Values that only the host can
+        // signal later are not supported, which is why `TIMELINE_SEMAPHORE` is not
+        // exposed: waiting for them here would block the submitting thread.
+        for (semaphore, _, value) in wait_semaphores {
+            if let Some(ref timeline) = semaphore.timeline {
+                let timeline = timeline.lock();
+                if timeline.value < value && timeline.pending.iter().all(|&(v, _)| v < value) {
+                    log::error!("Timeline value {} is not signaled by any submission", value);
+                }
+            }
+        }
+
+        self.submit(command_buffers, iter::empty(), iter::empty(), fence);
+
+        for (semaphore, value) in signal_semaphores {
+            if let Some(ref timeline) = semaphore.timeline {
+                let mut timeline = timeline.lock();
+                if self.share.private_caps.sync {
+                    let sync = self
+                        .share
+                        .context
+                        .fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0)
+                        .unwrap();
+                    timeline.pending.push((value, sync));
+                } else {
+                    self.share.context.flush();
+                    timeline.value = value;
+                }
+            }
+        }
+    }
+
     unsafe fn present(
         &mut self,
         surface: &mut Surface,
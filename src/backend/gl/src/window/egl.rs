@@ -4,7 +4,7 @@ use crate::{conv, native, GlContainer, PhysicalDevice, Starc};
 use glow::HasContext;
 use hal::{image, window as w};
 use parking_lot::Mutex;
-use std::{os::raw, ptr};
+use std::{mem, os::raw, ptr};
 
 #[derive(Debug)]
 pub struct Swapchain {
@@ -20,6 +20,35 @@ pub struct Swapchain {
 pub struct Instance {
     wsi_library: Option<libloading::Library>,
     inner: Mutex<Inner>,
+    /// Displays of the other EGL devices of a headless instance.
+    devices: Vec<Mutex<Inner>>,
+    headless: bool,
+}
+
+/// EGL platform of a headless instance, which doesn't need a display server.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HeadlessPlatform {
+    /// `EGL_MESA_platform_surfaceless`, rendering with the default device
+    /// of the driver (e.g. Mesa's software rasterizer).
+    Surfaceless,
+    /// `EGL_EXT_platform_device`, exposing each EGL device as a separate adapter.
+    Device,
+}
+
+/// EGL context of a headless adapter, made current when its device is opened.
+#[derive(Debug)]
+pub(crate) struct AdapterContext {
+    egl: Starc<egl::DynamicInstance<egl::EGL1_4>>,
+    display: egl::Display,
+    context: egl::Context,
+    pbuffer: Option<egl::Surface>,
+}
+
+impl AdapterContext {
+    pub(crate) fn make_current(&self) -> Result<(), egl::Error> {
+        self.egl
+            .make_current(self.display, self.pbuffer, self.pbuffer, Some(self.context))
+    }
 }
 
 #[derive(Debug)]
@@ -41,6 +70,16 @@ unsafe impl Sync for Instance {}
 
 const EGL_PLATFORM_WAYLAND_KHR: u32 = 0x31D8;
 const EGL_PLATFORM_X11_KHR: u32 = 0x31D5;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+const EGL_PLATFORM_DEVICE_EXT: u32 = 0x313F;
+
+type EglDevice = *mut raw::c_void;
+
+type QueryDevicesFun = unsafe extern "system" fn(
+    max_devices: egl::Int,
+    devices: *mut EglDevice,
+    num_devices: *mut egl::Int,
+) -> egl::Boolean;
 
 type XOpenDisplayFun =
     unsafe extern "system" fn(display_name: *const raw::c_char) -> *mut raw::c_void;
@@ -77,6 +116,50 @@ extern "C" {
     ) -> i32;
 }
 
+fn load_egl() -> Result<Starc<egl::DynamicInstance<egl::EGL1_4>>, hal::UnsupportedBackend> {
+    match unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() } {
+        Ok(egl) => Ok(Starc::new(egl)),
+        Err(e) => {
+            log::warn!("Unable to open libEGL.so: {:?}", e);
+            Err(hal::UnsupportedBackend)
+        }
+    }
+}
+
+fn query_client_extensions(egl: &egl::DynamicInstance<egl::EGL1_4>) -> String {
+    let client_ext_str = match egl.query_string(None, egl::EXTENSIONS) {
+        Ok(ext) => ext.to_string_lossy().into_owned(),
+        Err(_) => String::new(),
+    };
+    log::info!("Client extensions: {:?}", client_ext_str);
+    client_ext_str
+}
+
+/// Enumerate the EGL devices with `EGL_EXT_device_enumeration`.
+fn query_devices(egl: &egl::DynamicInstance<egl::EGL1_4>) -> Vec<EglDevice> {
+    let query_devices_fun: QueryDevicesFun = match egl.get_proc_address("eglQueryDevicesEXT") {
+        Some(fun) => unsafe { mem::transmute(fun) },
+        None => {
+            log::warn!("eglQueryDevicesEXT is not available");
+            return Vec::new();
+        }
+    };
+    unsafe {
+        let mut count = 0;
+        if query_devices_fun(0, ptr::null_mut(), &mut count) != egl::TRUE {
+            log::warn!("Error in eglQueryDevicesEXT");
+            return Vec::new();
+        }
+        let mut devices = vec![ptr::null_mut(); count as usize];
+        if query_devices_fun(count, devices.as_mut_ptr(), &mut count) != egl::TRUE {
+            log::warn!("Error in eglQueryDevicesEXT");
+            return Vec::new();
+        }
+        devices.truncate(count as usize);
+        devices
+    }
+}
+
 fn open_x_display() -> Option<(ptr::NonNull<raw::c_void>, libloading::Library)> {
     log::info!("Loading X11 library to get the current display");
     unsafe {
@@ -234,6 +317,23 @@ impl Inner {
     }
 }
 
+impl Inner {
+    fn make_current(&self) {
+        self.egl
+            .make_current(self.display, self.pbuffer, self.pbuffer, Some(self.context))
+            .unwrap();
+    }
+
+    fn adapter_context(&self) -> AdapterContext {
+        AdapterContext {
+            egl: self.egl.clone(),
+            display: self.display,
+            context: self.context,
+            pbuffer: self.pbuffer,
+        }
+    }
+}
+
 impl Drop for Inner {
     fn drop(&mut self) {
         if let Err(e) = self.egl.destroy_context(self.display, self.context) {
@@ -247,21 +347,8 @@ impl Drop for Inner {
 
 impl hal::Instance<crate::Backend> for Instance {
     fn create(_: &str, _: u32) -> Result<Self, hal::UnsupportedBackend> {
-        let egl = match unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() } {
-            Ok(egl) => Starc::new(egl),
-            Err(e) => {
-                log::warn!("Unable to open libEGL.so: {:?}", e);
-                return Err(hal::UnsupportedBackend);
-            }
-        };
-
-        let client_extensions = egl.query_string(None, egl::EXTENSIONS);
-
-        let client_ext_str = match client_extensions {
-            Ok(ext) => ext.to_string_lossy().into_owned(),
-            Err(_) => String::new(),
-        };
-        log::info!("Client extensions: {:?}", client_ext_str);
+        let egl = load_egl()?;
+        let client_ext_str = query_client_extensions(&egl);
 
         let mut wsi_library = None;
 
@@ -306,32 +393,38 @@ impl hal::Instance<crate::Backend> for Instance {
 
         Ok(Instance {
             inner: Mutex::new(inner),
+            devices: Vec::new(),
             wsi_library,
+            headless: false,
         })
     }
 
     fn enumerate_adapters(&self) -> Vec<hal::adapter::Adapter<crate::Backend>> {
-        let inner = self.inner.lock();
-        inner
-            .egl
-            .make_current(
-                inner.display,
-                inner.pbuffer,
-                inner.pbuffer,
-                Some(inner.context),
-            )
-            .unwrap();
-
-        let context = unsafe {
-            glow::Context::from_loader_function(|name| {
-                inner
-                    .egl
-                    .get_proc_address(name)
-                    .map_or(ptr::null(), |p| p as *const _)
+        std::iter::once(&self.inner)
+            .chain(self.devices.iter())
+            .map(|inner| {
+                let inner = inner.lock();
+                inner.make_current();
+
+                let loader = |name: &str| {
+                    inner
+                        .egl
+                        .get_proc_address(name)
+                        .map_or(ptr::null(), |p| p as *const _)
+                };
+                let context = unsafe { glow::Context::from_loader_function(loader) };
+                let ext = unsafe { crate::ext::ExtFns::load(loader) };
+                // Windowed instances may replace their context when creating a surface,
+                // so only the headless adapters switch contexts.
+                let adapter_context = if self.headless {
+                    Some(inner.adapter_context())
+                } else {
+                    None
+                };
+                // Create physical device
+                PhysicalDevice::new_adapter(context, ext, adapter_context)
             })
-        };
-        // Create physical device
-        vec![PhysicalDevice::new_adapter(context)]
+            .collect()
     }
 
     #[cfg_attr(target_os = "macos", allow(unused, unused_mut, unreachable_code))]
@@ -341,6 +434,11 @@ impl hal::Instance<crate::Backend> for Instance {
     ) -> Result<Surface, w::InitError> {
         use raw_window_handle::RawWindowHandle as Rwh;
 
+        if self.headless {
+            log::error!("Surfaces can't be created with a headless instance");
+            return Err(w::InitError::UnsupportedWindowHandle);
+        }
+
         let mut inner = self.inner.lock();
         let mut wl_window = None;
         #[cfg(not(any(target_os = "android", target_os = "macos")))]
@@ -497,6 +595,84 @@ impl hal::Instance<crate::Backend> for Instance {
     }
 }
 
+impl Instance {
+    /// Create an instance without a display server, for compute and offscreen rendering.
+    ///
+    /// Unlike `Instance::create`, no X11 or Wayland display is probed.
+    pub fn create_headless(platform: HeadlessPlatform) -> Result<Self, hal::UnsupportedBackend> {
+        let egl = load_egl()?;
+        let client_ext_str = query_client_extensions(&egl);
+
+        let egl1_5 = match egl.upcast::<egl::EGL1_5>() {
+            Some(egl1_5) => egl1_5,
+            None => {
+                log::warn!("Headless platforms require EGL 1.5");
+                return Err(hal::UnsupportedBackend);
+            }
+        };
+        let display_attributes = [egl::ATTRIB_NONE];
+
+        let mut inners = match platform {
+            HeadlessPlatform::Surfaceless => {
+                if !client_ext_str.contains("EGL_MESA_platform_surfaceless") {
+                    log::warn!("EGL_MESA_platform_surfaceless is not supported");
+                    return Err(hal::UnsupportedBackend);
+                }
+                log::info!("Using surfaceless platform");
+                let display = egl1_5
+                    .get_platform_display(
+                        EGL_PLATFORM_SURFACELESS_MESA,
+                        egl::DEFAULT_DISPLAY,
+                        &display_attributes,
+                    )
+                    .map_err(|e| {
+                        log::warn!("Error in get_platform_display: {:?}", e);
+                        hal::UnsupportedBackend
+                    })?;
+                vec![Inner::create(egl.clone(), display, None)?]
+            }
+            HeadlessPlatform::Device => {
+                if !client_ext_str.contains("EGL_EXT_platform_device")
+                    || !(client_ext_str.contains("EGL_EXT_device_enumeration")
+                        || client_ext_str.contains("EGL_EXT_device_base"))
+                {
+                    log::warn!("EGL_EXT_platform_device is not supported");
+                    return Err(hal::UnsupportedBackend);
+                }
+                log::info!("Using device platform");
+                query_devices(&egl)
+                    .into_iter()
+                    .filter_map(|device| {
+                        let display = egl1_5
+                            .get_platform_display(
+                                EGL_PLATFORM_DEVICE_EXT,
+                                device,
+                                &display_attributes,
+                            )
+                            .map_err(|e| log::warn!("Error in get_platform_display: {:?}", e))
+                            .ok()?;
+                        // Devices without a usable GLES context are skipped.
+                        Inner::create(egl.clone(), display, None).ok()
+                    })
+                    .collect()
+            }
+        };
+
+        if inners.is_empty() {
+            log::warn!("No EGL device is available");
+            return Err(hal::UnsupportedBackend);
+        }
+        let inner = inners.remove(0);
+
+        Ok(Instance {
+            inner: Mutex::new(inner),
+            devices: inners.into_iter().map(Mutex::new).collect(),
+            wsi_library: None,
+            headless: true,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Surface {
     egl: Starc<egl::DynamicInstance<egl::EGL1_4>>,
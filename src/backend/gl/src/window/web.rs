@@ -20,6 +20,16 @@ pub struct Instance {
     canvas: Mutex<Option<Starc<web_sys::HtmlCanvasElement>>>,
 }
 
+/// The WebGL context of a canvas never needs to be made current.
+#[derive(Debug)]
+pub(crate) enum AdapterContext {}
+
+impl AdapterContext {
+    pub(crate) fn make_current(&self) -> Result<(), ()> {
+        match *self {}
+    }
+}
+
 impl hal::Instance<B> for Instance {
     fn create(_name: &str, _version: u32) -> Result<Self, hal::UnsupportedBackend> {
         Ok(Instance {
@@ -59,7 +69,7 @@ impl hal::Instance<B> for Instance {
             None => return Vec::new(),
         };
 
-        let adapter = PhysicalDevice::new_adapter(context);
+        let adapter = PhysicalDevice::new_adapter(context, Default::default(), None);
         vec![adapter]
     }
 
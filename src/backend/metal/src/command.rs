@@ -2195,31 +2195,23 @@ impl Queue {
             }
         }
     }
-}
 
-impl hal::queue::Queue<Backend> for Queue {
-    unsafe fn submit<'a, Ic, Iw, Is>(
+    /// Commits the command buffers, signaling the semaphores and the fence
+    /// once they are completed.
+    unsafe fn submit_impl<'a, Ic>(
         &mut self,
         command_buffers: Ic,
-        wait_semaphores: Iw,
-        signal_semaphores: Is,
+        system_semaphores: Vec<native::SystemSemaphore>,
+        timeline_semaphores: Vec<(Arc<native::TimelineSemaphore>, u64)>,
         fence: Option<&mut native::Fence>,
     ) where
         Ic: Iterator<Item = &'a CommandBuffer>,
-        Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage)>,
-        Is: Iterator<Item = &'a native::Semaphore>,
     {
-        debug!("submitting with fence {:?}", fence);
-        self.wait(wait_semaphores.map(|(s, _)| s));
-
-        let system_semaphores = signal_semaphores
-            .filter_map(|sem| sem.system.clone())
-            .collect::<Vec<_>>();
-
         #[allow(unused_mut)]
         let (mut num_immediate, mut num_deferred, mut num_remote) = (0, 0, 0);
         let mut event_commands = Vec::new();
-        let do_signal = fence.is_some() || !system_semaphores.is_empty();
+        let do_signal =
+            fence.is_some() || !system_semaphores.is_empty() || !timeline_semaphores.is_empty();
 
         autoreleasepool(|| {
             // for command buffers
@@ -2342,6 +2334,9 @@ impl hal::queue::Queue<Backend> for Queue {
                     for semaphore in &system_semaphores {
                         semaphore.signal();
                     }
+                    for &(ref semaphore, value) in &timeline_semaphores {
+                        semaphore.signal(value);
+                    }
                     // process events
                     for &(ref atomic, value) in &event_commands {
                         atomic.store(value, Ordering::Release);
@@ -2404,6 +2399,63 @@ impl hal::queue::Queue<Backend> for Queue {
             }
         }
     }
+}
+
+impl hal::queue::Queue<Backend> for Queue {
+    unsafe fn submit<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage)>,
+        Is: Iterator<Item = &'a native::Semaphore>,
+    {
+        debug!("submitting with fence {:?}", fence);
+        self.wait(wait_semaphores.map(|(s, _)| s));
+
+        let system_semaphores = signal_semaphores
+            .filter_map(|sem| sem.system.clone())
+            .collect::<Vec<_>>();
+        self.submit_impl(command_buffers, system_semaphores, Vec::new(), fence);
+    }
+
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a native::Semaphore, u64)>,
+    {
+        debug!("submitting timeline with fence {:?}", fence);
+        // Submissions on the queue complete in order, so values signaled by earlier
+        // ones are reached in time. Waiting on the host for any other value would
+        // block the submitting thread, which is why `TIMELINE_SEMAPHORE` is not exposed.
+        self.wait(wait_semaphores.map(|(semaphore, _, _)| semaphore));
+
+        let mut system_semaphores = Vec::new();
+        let mut timeline_semaphores = Vec::new();
+        for (semaphore, value) in signal_semaphores {
+            if let Some(ref system) = semaphore.system {
+                system_semaphores.push(system.clone());
+            }
+            if let Some(ref timeline) = semaphore.timeline {
+                timeline_semaphores.push((Arc::clone(timeline), value));
+            }
+        }
+        self.submit_impl(
+            command_buffers,
+            system_semaphores,
+            timeline_semaphores,
+            fence,
+        );
+    }
 
     unsafe fn present(
         &mut self,
@@ -418,6 +418,20 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         }
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        // Metal doesn't report per-heap budgets, so only the allocations of this
+        // device are known.
+        self.memory_properties()
+            .memory_heaps
+            .iter()
+            .zip(&self.shared.heap_usage)
+            .map(|(heap, usage)| adapter::MemoryHeapBudget {
+                budget: heap.size,
+                usage: usage.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
     fn features(&self) -> hal::Features {
         use hal::Features as F;
         let mut features = F::FULL_DRAW_INDEX_U32
@@ -2041,9 +2055,49 @@ impl hal::device::Device<Backend> for Device {
             } else {
                 None
             },
+            timeline: None,
+        })
+    }
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::Semaphore, d::OutOfMemory> {
+        Ok(n::Semaphore {
+            system: None,
+            timeline: Some(Arc::new(n::TimelineSemaphore::new(initial_value))),
         })
     }
 
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &n::Semaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        Ok(semaphore.timeline().value())
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        semaphore.timeline().signal(value);
+        Ok(())
+    }
+
+    unsafe fn wait_for_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError> {
+        debug!(
+            "wait_for_semaphore {:?} to reach {} for {} ns",
+            semaphore, value, timeout_ns
+        );
+        Ok(semaphore.timeline().wait(value, timeout_ns))
+    }
+
     unsafe fn create_descriptor_pool<I>(
         &self,
         max_sets: usize,
@@ -2458,7 +2512,9 @@ impl hal::device::Device<Backend> for Device {
             n::MemoryHeap::Public(memory_type, cpu_buffer)
         };
 
-        Ok(n::Memory::new(heap, size))
+        let heap_index = self.memory_types[memory_type.0].heap_index;
+        self.shared.heap_usage[heap_index].fetch_add(size, Ordering::Relaxed);
+        Ok(n::Memory::new(heap, size, heap_index))
     }
 
     unsafe fn free_memory(&self, memory: n::Memory) {
@@ -2466,6 +2522,7 @@ impl hal::device::Device<Backend> for Device {
         if let n::MemoryHeap::Public(_, ref cpu_buffer) = memory.heap {
             debug!("\tbacked by cpu buffer {:?}", cpu_buffer.as_ptr());
         }
+        self.shared.heap_usage[memory.heap_index].fetch_sub(memory.size, Ordering::Relaxed);
     }
 
     unsafe fn create_buffer(
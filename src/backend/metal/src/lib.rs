@@ -83,7 +83,7 @@ use std::{
     mem,
     os::raw::c_void,
     ptr::NonNull,
-    sync::{Arc, Once},
+    sync::{atomic::AtomicU64, Arc, Once},
 };
 
 mod command;
@@ -193,6 +193,8 @@ struct Shared {
     disabilities: PrivateDisabilities,
     private_caps: PrivateCapabilities,
     visibility: VisibilityShared,
+    // Bytes allocated from each memory heap by the logical device.
+    heap_usage: [AtomicU64; 2],
 }
 
 unsafe impl Send for Shared {}
@@ -231,6 +233,7 @@ impl Shared {
             private_caps,
             device: Mutex::new(device),
             visibility,
+            heap_usage: Default::default(),
         }
     }
 }
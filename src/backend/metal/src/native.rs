@@ -17,7 +17,7 @@ use range_alloc::RangeAllocator;
 
 use arrayvec::ArrayVec;
 use metal;
-use parking_lot::RwLock;
+use parking_lot::{Condvar, Mutex, RwLock};
 
 use std::{
     fmt,
@@ -25,6 +25,7 @@ use std::{
     os::raw::{c_long, c_void},
     ptr,
     sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -404,6 +405,63 @@ unsafe impl Sync for Sampler {}
 #[derive(Clone, Debug)]
 pub struct Semaphore {
     pub(crate) system: Option<SystemSemaphore>,
+    pub(crate) timeline: Option<Arc<TimelineSemaphore>>,
+}
+
+impl Semaphore {
+    pub(crate) fn timeline(&self) -> &TimelineSemaphore {
+        match self.timeline {
+            Some(ref timeline) => timeline,
+            None => panic!("Semaphore {:?} is not a timeline semaphore", self),
+        }
+    }
+}
+
+/// Counter of a timeline semaphore, advanced on the host by the completion
+/// handler of the submission signaling it.
+#[derive(Debug)]
+pub struct TimelineSemaphore {
+    value: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl TimelineSemaphore {
+    pub(crate) fn new(initial_value: u64) -> Self {
+        TimelineSemaphore {
+            value: Mutex::new(initial_value),
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        *self.value.lock()
+    }
+
+    pub(crate) fn signal(&self, value: u64) {
+        let mut guard = self.value.lock();
+        if *guard < value {
+            *guard = value;
+        }
+        self.condvar.notify_all();
+    }
+
+    /// Blocks until the counter reaches `value`, returning false on timeout.
+    pub(crate) fn wait(&self, value: u64, timeout_ns: u64) -> bool {
+        let mut guard = self.value.lock();
+        if timeout_ns == !0 {
+            while *guard < value {
+                self.condvar.wait(&mut guard);
+            }
+            return true;
+        }
+        let deadline = Instant::now() + Duration::from_nanos(timeout_ns);
+        while *guard < value {
+            if self.condvar.wait_until(&mut guard, deadline).timed_out() {
+                return *guard >= value;
+            }
+        }
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -928,11 +986,16 @@ unsafe impl Sync for DescriptorSet {}
 pub struct Memory {
     pub(crate) heap: MemoryHeap,
     pub(crate) size: u64,
+    pub(crate) heap_index: usize,
 }
 
 impl Memory {
-    pub(crate) fn new(heap: MemoryHeap, size: u64) -> Self {
-        Memory { heap, size }
+    pub(crate) fn new(heap: MemoryHeap, size: u64, heap_index: usize) -> Self {
+        Memory {
+            heap,
+            size,
+            heap_index,
+        }
     }
 
     pub(crate) fn resolve(&self, range: &Segment) -> Range<u64> {
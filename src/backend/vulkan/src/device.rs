@@ -1,5 +1,9 @@
 use arrayvec::ArrayVec;
-use ash::{extensions::khr, version::DeviceV1_0, vk};
+use ash::{
+    extensions::khr,
+    version::{DeviceV1_0, DeviceV1_2},
+    vk,
+};
 use inplace_it::inplace_or_alloc_from_iter;
 use smallvec::SmallVec;
 
@@ -12,9 +16,18 @@ use hal::{
     {buffer, device as d, format, image, pass, pso, query, queue}, {Features, MemoryTypeId},
 };
 
-use std::{ffi::CString, marker::PhantomData, mem, ops::Range, ptr, sync::Arc};
+use std::{
+    ffi::CString,
+    marker::PhantomData,
+    mem,
+    ops::Range,
+    ptr,
+    sync::{atomic::Ordering, Arc},
+};
 
-use crate::{command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B};
+use crate::{
+    command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B, ExtensionFn,
+};
 
 #[derive(Debug, Default)]
 struct GraphicsPipelineInfoBuf<'a> {
@@ -466,7 +479,16 @@ impl d::Device<B> for super::Device {
         let result = self.shared.raw.allocate_memory(&info, None);
 
         match result {
-            Ok(memory) => Ok(n::Memory { raw: memory }),
+            Ok(memory) => {
+                let heap_index =
+                    self.ash_memory_heap_indices[self.get_ash_memory_type_index(mem_type) as usize];
+                self.shared.heap_usage[heap_index].fetch_add(size, Ordering::Relaxed);
+                Ok(n::Memory {
+                    raw: memory,
+                    heap_index,
+                    size,
+                })
+            }
             Err(vk::Result::ERROR_TOO_MANY_OBJECTS) => Err(d::AllocationError::TooManyObjects),
             Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
             Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
@@ -1533,6 +1555,132 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::Semaphore, d::OutOfMemory> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let info = vk::SemaphoreCreateInfo::builder()
+            .flags(vk::SemaphoreCreateFlags::empty())
+            .push_next(&mut type_info);
+
+        let result = unsafe { self.shared.raw.create_semaphore(&info, None) };
+
+        match result {
+            Ok(semaphore) => Ok(n::Semaphore(semaphore)),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &n::Semaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        let device = self.shared.raw.handle();
+        let mut value = 0;
+        let timeline_fn = self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect(
+                "Feature TIMELINE_SEMAPHORE must be enabled to call get_semaphore_counter_value",
+            );
+        let result = match timeline_fn {
+            ExtensionFn::Extension(t) => {
+                t.get_semaphore_counter_value_khr(device, semaphore.0, &mut value)
+            }
+            ExtensionFn::Promoted => self.shared.raw.fp_v1_2().get_semaphore_counter_value(
+                device,
+                semaphore.0,
+                &mut value,
+            ),
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(value),
+            vk::Result::ERROR_DEVICE_LOST => Err(d::DeviceLost),
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        let device = self.shared.raw.handle();
+        let info = vk::SemaphoreSignalInfo::builder()
+            .semaphore(semaphore.0)
+            .value(value);
+        let timeline_fn = self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect("Feature TIMELINE_SEMAPHORE must be enabled to call signal_semaphore");
+        let result = match timeline_fn {
+            ExtensionFn::Extension(t) => t.signal_semaphore_khr(device, &*info),
+            ExtensionFn::Promoted => self.shared.raw.fp_v1_2().signal_semaphore(device, &*info),
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(()),
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY => Err(d::OutOfMemory::Host),
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(d::OutOfMemory::Device),
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn wait_for_semaphores<'a, I>(
+        &self,
+        semaphores: I,
+        wait: d::WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError>
+    where
+        I: Iterator<Item = (&'a n::Semaphore, u64)>,
+    {
+        let device = self.shared.raw.handle();
+        let (raw_semaphores, values): (SmallVec<[_; 4]>, SmallVec<[_; 4]>) = semaphores
+            .map(|(semaphore, value)| (semaphore.0, value))
+            .unzip();
+        let info = vk::SemaphoreWaitInfo::builder()
+            .flags(match wait {
+                d::WaitFor::Any => vk::SemaphoreWaitFlags::ANY,
+                d::WaitFor::All => vk::SemaphoreWaitFlags::empty(),
+            })
+            .semaphores(&raw_semaphores)
+            .values(&values);
+        let timeline_fn = self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect("Feature TIMELINE_SEMAPHORE must be enabled to call wait_for_semaphores");
+        let result = match timeline_fn {
+            ExtensionFn::Extension(t) => t.wait_semaphores_khr(device, &*info, timeout_ns),
+            ExtensionFn::Promoted => self
+                .shared
+                .raw
+                .fp_v1_2()
+                .wait_semaphores(device, &*info, timeout_ns),
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(true),
+            vk::Result::TIMEOUT => Ok(false),
+            vk::Result::ERROR_DEVICE_LOST => Err(d::DeviceLost.into()),
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY => Err(d::OutOfMemory::Host.into()),
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
     fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
         let info = vk::FenceCreateInfo::builder().flags(if signaled {
             vk::FenceCreateFlags::SIGNALED
@@ -1644,6 +1792,7 @@ impl d::Device<B> for super::Device {
 
     unsafe fn free_memory(&self, memory: n::Memory) {
         self.shared.raw.free_memory(memory.raw, None);
+        self.shared.heap_usage[memory.heap_index].fetch_sub(memory.size, Ordering::Relaxed);
     }
 
     unsafe fn create_query_pool(
@@ -49,7 +49,7 @@ use std::{
     cmp,
     ffi::{CStr, CString},
     fmt, slice,
-    sync::Arc,
+    sync::{atomic::AtomicU64, Arc},
     thread, unreachable,
 };
 
@@ -676,6 +676,7 @@ impl queue::QueueFamily for QueueFamily {
 struct DeviceExtensionFunctions {
     mesh_shaders: Option<ExtensionFn<MeshShader>>,
     draw_indirect_count: Option<ExtensionFn<khr::DrawIndirectCount>>,
+    timeline_semaphore: Option<ExtensionFn<vk::KhrTimelineSemaphoreFn>>,
 }
 
 // TODO there's no reason why this can't be unified--the function pointers should all be the same--it's not clear how to do this with `ash`.
@@ -709,6 +710,8 @@ pub struct RawDevice {
     flip_y_requires_shift: bool,
     imageless_framebuffers: bool,
     timestamp_period: f32,
+    /// Bytes allocated from each memory heap, shared with the physical device.
+    heap_usage: Arc<[AtomicU64; vk::MAX_MEMORY_HEAPS]>,
 }
 
 impl fmt::Debug for RawDevice {
@@ -834,6 +837,63 @@ impl queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a command::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a native::Semaphore, u64)>,
+    {
+        if self.device.extension_fns.timeline_semaphore.is_none() {
+            return self.submit(
+                command_buffers,
+                wait_semaphores.map(|(semaphore, stage, _)| (semaphore, stage)),
+                signal_semaphores.map(|(semaphore, _)| semaphore),
+                fence,
+            );
+        }
+
+        //TODO: avoid heap allocations
+        let mut waits = Vec::new();
+        let mut wait_values = Vec::new();
+        let mut stages = Vec::new();
+
+        let buffers = command_buffers.map(|cmd| cmd.raw).collect::<Vec<_>>();
+        for (semaphore, stage, value) in wait_semaphores {
+            waits.push(semaphore.0);
+            wait_values.push(value);
+            stages.push(conv::map_pipeline_stage(stage));
+        }
+        let (signals, signal_values): (Vec<_>, Vec<_>) = signal_semaphores
+            .map(|(semaphore, value)| (semaphore.0, value))
+            .unzip();
+
+        // Values for binary semaphores in the submission are ignored.
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let mut info = vk::SubmitInfo::builder()
+            .wait_semaphores(&waits)
+            .command_buffers(&buffers)
+            .signal_semaphores(&signals)
+            .push_next(&mut timeline_info);
+        // If count is zero, AMD driver crashes if nullptr is not set for stage masks
+        if !stages.is_empty() {
+            info = info.wait_dst_stage_mask(&stages);
+        }
+
+        let fence_raw = fence.map(|fence| fence.0).unwrap_or(vk::Fence::null());
+
+        let result = self.device.raw.queue_submit(*self.raw, &[*info], fence_raw);
+        if let Err(e) = result {
+            error!("Submit resulted in {:?}", e);
+        }
+    }
+
     unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
         &mut self,
         wait_semaphores: Iw,
@@ -1029,6 +1089,8 @@ pub struct Device {
     shared: Arc<RawDevice>,
     vendor_id: u32,
     valid_ash_memory_types: u32,
+    /// Heap index of each Vulkan memory type.
+    ash_memory_heap_indices: Vec<usize>,
     #[cfg(feature = "naga")]
     naga_options: naga::back::spv::Options,
 }
@@ -28,6 +28,8 @@ pub struct ComputePipeline(pub vk::Pipeline);
 #[derive(Debug, Hash)]
 pub struct Memory {
     pub(crate) raw: vk::DeviceMemory,
+    pub(crate) heap_index: usize,
+    pub(crate) size: u64,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -6,7 +6,14 @@ use ash::{
 
 use hal::{DescriptorLimits, DownlevelProperties, DynamicStates, Features, Limits, PhysicalDeviceProperties, adapter, device::{CreationError, OutOfMemory}, format, image, pso::PatchSize, queue};
 
-use std::{ffi::CStr, fmt, mem, ptr, sync::Arc};
+use std::{
+    ffi::CStr,
+    fmt, mem, ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use crate::{
     conv, info, Backend, Device, DeviceExtensionFunctions, ExtensionFn, Queue, QueueFamily,
@@ -21,6 +28,7 @@ pub struct PhysicalDeviceFeatures {
     descriptor_indexing: Option<vk::PhysicalDeviceDescriptorIndexingFeaturesEXT>,
     mesh_shader: Option<vk::PhysicalDeviceMeshShaderFeaturesNV>,
     imageless_framebuffer: Option<vk::PhysicalDeviceImagelessFramebufferFeaturesKHR>,
+    timeline_semaphore: Option<vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR>,
 }
 
 // This is safe because the structs have `p_next: *mut c_void`, which we null out/never read.
@@ -47,6 +55,9 @@ impl PhysicalDeviceFeatures {
         if let Some(ref mut feature) = self.imageless_framebuffer {
             info = info.push_next(feature);
         }
+        if let Some(ref mut feature) = self.timeline_semaphore {
+            info = info.push_next(feature);
+        }
 
         info
     }
@@ -163,6 +174,7 @@ impl PhysicalDeviceFeatures {
                         )
                         .sampler_filter_minmax(supports_vulkan12_sampler_filter_minmax)
                         .imageless_framebuffer(supports_vulkan12_imageless_framebuffer)
+                        .timeline_semaphore(features.contains(Features::TIMELINE_SEMAPHORE))
                         .build(),
                 )
             } else {
@@ -214,6 +226,16 @@ impl PhysicalDeviceFeatures {
             } else {
                 None
             },
+            timeline_semaphore: if enabled_extensions.contains(&vk::KhrTimelineSemaphoreFn::name())
+            {
+                Some(
+                    vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR::builder()
+                        .timeline_semaphore(features.contains(Features::TIMELINE_SEMAPHORE))
+                        .build(),
+                )
+            } else {
+                None
+            },
         }
     }
 
@@ -439,6 +461,9 @@ impl PhysicalDeviceFeatures {
             if vulkan_1_2.draw_indirect_count != 0 {
                 bits |= Features::DRAW_INDIRECT_COUNT
             }
+            if vulkan_1_2.timeline_semaphore != 0 {
+                bits |= Features::TIMELINE_SEMAPHORE;
+            }
         }
 
         if let Some(ref descriptor_indexing) = self.descriptor_indexing {
@@ -468,6 +493,12 @@ impl PhysicalDeviceFeatures {
             }
         }
 
+        if let Some(ref timeline_semaphore) = self.timeline_semaphore {
+            if timeline_semaphore.timeline_semaphore != 0 {
+                bits |= Features::TIMELINE_SEMAPHORE;
+            }
+        }
+
         bits
     }
 }
@@ -546,6 +577,12 @@ impl PhysicalDeviceInfo {
             requested_extensions.push(DrawIndirectCount::name());
         }
 
+        if self.api_version() < Version::V1_2
+            && requested_features.contains(Features::TIMELINE_SEMAPHORE)
+        {
+            requested_extensions.push(vk::KhrTimelineSemaphoreFn::name());
+        }
+
         if requested_features.contains(Features::CONSERVATIVE_RASTERIZATION) {
             requested_extensions.push(vk::ExtConservativeRasterizationFn::name());
             requested_extensions.push(vk::KhrGetDisplayProperties2Fn::name()); // TODO NOT NEEDED, RIGHT?
@@ -609,6 +646,17 @@ impl PhysicalDeviceInfo {
                 mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
             }
 
+            // On 1.2 devices, timeline semaphore support is reported through `PhysicalDeviceVulkan12Features`.
+            if device_properties.api_version() < Version::V1_2
+                && device_properties.supports_extension(vk::KhrTimelineSemaphoreFn::name())
+            {
+                features.timeline_semaphore =
+                    Some(vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR::builder().build());
+
+                let mut_ref = features.timeline_semaphore.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
             unsafe {
                 get_device_properties
                     .get_physical_device_features2_khr(device, &mut features2 as *mut _);
@@ -632,6 +680,7 @@ impl PhysicalDeviceInfo {
             null_p_next(&mut features.descriptor_indexing);
             null_p_next(&mut features.mesh_shader);
             null_p_next(&mut features.imageless_framebuffer);
+            null_p_next(&mut features.timeline_semaphore);
         }
 
         (device_properties, features)
@@ -644,6 +693,8 @@ pub struct PhysicalDevice {
     known_memory_flags: vk::MemoryPropertyFlags,
     device_info: PhysicalDeviceInfo,
     device_features: PhysicalDeviceFeatures,
+    /// Bytes allocated from each memory heap by the logical devices.
+    heap_usage: Arc<[AtomicU64; vk::MAX_MEMORY_HEAPS]>,
 }
 
 pub(crate) fn load_adapter(
@@ -681,6 +732,7 @@ pub(crate) fn load_adapter(
             | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
         device_info,
         device_features,
+        heap_usage: Arc::default(),
     };
 
     let queue_families = unsafe {
@@ -749,22 +801,23 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             supported_extensions
         };
 
-        let valid_ash_memory_types = {
-            let mem_properties = self
-                .instance
-                .inner
-                .get_physical_device_memory_properties(self.handle);
-            mem_properties.memory_types[..mem_properties.memory_type_count as usize]
-                .iter()
-                .enumerate()
-                .fold(0, |u, (i, mem)| {
-                    if self.known_memory_flags.contains(mem.property_flags) {
-                        u | (1 << i)
-                    } else {
-                        u
-                    }
-                })
-        };
+        let mem_properties = self
+            .instance
+            .inner
+            .get_physical_device_memory_properties(self.handle);
+        let ash_memory_types =
+            &mem_properties.memory_types[..mem_properties.memory_type_count as usize];
+        let valid_ash_memory_types = ash_memory_types.iter().enumerate().fold(0, |u, (i, mem)| {
+            if self.known_memory_flags.contains(mem.property_flags) {
+                u | (1 << i)
+            } else {
+                u
+            }
+        });
+        let ash_memory_heap_indices = ash_memory_types
+            .iter()
+            .map(|mem| mem.heap_index as usize)
+            .collect();
 
         let supports_vulkan12_imageless_framebuffer = self
             .device_features
@@ -842,6 +895,25 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             None
         };
 
+        let timeline_semaphore_fn =
+            if enabled_extensions.contains(&vk::KhrTimelineSemaphoreFn::name()) {
+                Some(ExtensionFn::Extension(vk::KhrTimelineSemaphoreFn::load(
+                    |name| {
+                        mem::transmute(
+                            self.instance
+                                .inner
+                                .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                        )
+                    },
+                )))
+            } else if self.device_info.api_version() >= Version::V1_2
+                && requested_features.contains(Features::TIMELINE_SEMAPHORE)
+            {
+                Some(ExtensionFn::Promoted)
+            } else {
+                None
+            };
+
         #[cfg(feature = "naga")]
         let naga_options = {
             use naga::back::spv;
@@ -879,6 +951,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 extension_fns: DeviceExtensionFunctions {
                     mesh_shaders: mesh_fn,
                     draw_indirect_count: indirect_count_fn,
+                    timeline_semaphore: timeline_semaphore_fn,
                 },
                 flip_y_requires_shift: self.device_info.api_version() >= Version::V1_1
                     || self
@@ -889,9 +962,11 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                         .device_info
                         .supports_extension(vk::KhrImagelessFramebufferFn::name()),
                 timestamp_period: self.device_info.properties.limits.timestamp_period,
+                heap_usage: Arc::clone(&self.heap_usage),
             }),
             vendor_id: self.device_info.properties.vendor_id,
             valid_ash_memory_types,
+            ash_memory_heap_indices,
             #[cfg(feature = "naga")]
             naga_options,
         };
@@ -1037,6 +1112,47 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         }
     }
 
+    fn memory_budget(&self) -> Vec<adapter::MemoryHeapBudget> {
+        match self.instance.get_physical_device_properties.as_ref() {
+            Some(get_physical_device_properties)
+                if self
+                    .device_info
+                    .supports_extension(vk::ExtMemoryBudgetFn::name()) =>
+            {
+                let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::builder();
+                let mut mem_properties = vk::PhysicalDeviceMemoryProperties2::builder()
+                    .push_next(&mut budget_properties)
+                    .build();
+
+                unsafe {
+                    get_physical_device_properties.get_physical_device_memory_properties2_khr(
+                        self.handle,
+                        &mut mem_properties as *mut _,
+                    );
+                }
+
+                let heap_count = mem_properties.memory_properties.memory_heap_count as usize;
+                budget_properties.heap_budget[..heap_count]
+                    .iter()
+                    .zip(&budget_properties.heap_usage[..heap_count])
+                    .map(|(&budget, &usage)| adapter::MemoryHeapBudget { budget, usage })
+                    .collect()
+            }
+            // Without `VK_EXT_memory_budget`, the whole heap is the budget,
+            // and only the allocations of the logical devices are known.
+            _ => self
+                .memory_properties()
+                .memory_heaps
+                .iter()
+                .zip(self.heap_usage.iter())
+                .map(|(heap, usage)| adapter::MemoryHeapBudget {
+                    budget: heap.size,
+                    usage: usage.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+
     fn features(&self) -> Features {
         let mut bits = self.device_features.to_hal_features(&self.device_info);
 
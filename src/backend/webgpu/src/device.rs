@@ -348,6 +348,28 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        _initial_value: u64,
+    ) -> Result<<Backend as hal::Backend>::Semaphore, OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        _semaphore: &<Backend as hal::Backend>::Semaphore,
+    ) -> Result<u64, DeviceLost> {
+        todo!()
+    }
+
+    unsafe fn signal_semaphore(
+        &self,
+        _semaphore: &<Backend as hal::Backend>::Semaphore,
+        _value: u64,
+    ) -> Result<(), OutOfMemory> {
+        todo!()
+    }
+
     fn create_fence(
         &self,
         _signaled: bool,
@@ -1,7 +1,7 @@
 use std::borrow::Borrow;
 
 use hal::{
-    adapter::{Adapter, AdapterInfo, DeviceType, Gpu, MemoryProperties},
+    adapter::{Adapter, AdapterInfo, DeviceType, Gpu, MemoryHeapBudget, MemoryProperties},
     device::CreationError,
     format, image,
     queue::{QueueFamilyId, QueuePriority, QueueType},
@@ -183,6 +183,10 @@ impl hal::adapter::PhysicalDevice<Backend> for PhysicalDevice {
         todo!()
     }
 
+    fn memory_budget(&self) -> Vec<MemoryHeapBudget> {
+        todo!()
+    }
+
     fn features(&self) -> hal::Features {
         todo!()
     }
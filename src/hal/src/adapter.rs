@@ -48,6 +48,17 @@ pub struct MemoryProperties {
     pub memory_heaps: Vec<MemoryHeap>,
 }
 
+/// Current budget and usage of a memory heap, in bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryHeapBudget {
+    /// Estimate of how much memory the process can use from the heap before
+    /// allocations may fail or degrade performance.
+    pub budget: u64,
+    /// Estimate of how much memory the process currently uses from the heap.
+    pub usage: u64,
+}
+
 /// Represents a combination of a [logical device][crate::device::Device] and the
 /// [hardware queues][QueueGroup] it provides.
 ///
@@ -115,6 +126,16 @@ pub trait PhysicalDevice<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Fetch details for the memory regions provided by the device.
     fn memory_properties(&self) -> MemoryProperties;
 
+    /// Fetch the current budget and usage of each heap in
+    /// [`MemoryProperties::memory_heaps`], in the same order.
+    ///
+    /// Unlike the heap sizes, these values change over time, as memory is allocated
+    /// by this process and others. Backends without a native query approximate them
+    /// from the allocations made through their own devices, and report the heap size
+    /// as the budget. Heaps of unknown size, like the ones of the GL backend,
+    /// have a budget of `u64::MAX`.
+    fn memory_budget(&self) -> Vec<MemoryHeapBudget>;
+
     /// Returns the features of this `PhysicalDevice`. This usually depends on the graphics API being
     /// used, as well as the actual platform underneath.
     fn features(&self) -> Features;
@@ -544,6 +544,86 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a semaphore object.
     unsafe fn destroy_semaphore(&self, semaphore: B::Semaphore);
 
+    /// Create a new timeline semaphore object.
+    ///
+    /// Unlike binary semaphores, the payload of a timeline semaphore is a 64-bit counter,
+    /// which only ever increases. Queues signal and wait on specific values of the counter
+    /// with [`submit_timeline`][crate::queue::Queue::submit_timeline], and the host can
+    /// query, signal and wait on it directly.
+    ///
+    /// Only valid to call if `Features::TIMELINE_SEMAPHORE` is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_value` - the starting value of the counter.
+    fn create_timeline_semaphore(&self, initial_value: u64) -> Result<B::Semaphore, OutOfMemory>;
+
+    /// Query the current counter value of a timeline semaphore.
+    unsafe fn get_semaphore_counter_value(
+        &self,
+        semaphore: &B::Semaphore,
+    ) -> Result<u64, DeviceLost>;
+
+    /// Set the counter of a timeline semaphore to `value` from the host.
+    ///
+    /// `value` must be greater than the current value of the counter, and than the values
+    /// of any pending signal operations on it.
+    unsafe fn signal_semaphore(
+        &self,
+        semaphore: &B::Semaphore,
+        value: u64,
+    ) -> Result<(), OutOfMemory>;
+
+    /// Blocks until the counter of a timeline semaphore reaches `value`.
+    /// Returns true if the value was reached before the timeout.
+    unsafe fn wait_for_semaphore(
+        &self,
+        semaphore: &B::Semaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, WaitError> {
+        self.wait_for_semaphores(iter::once((semaphore, value)), WaitFor::All, timeout_ns)
+    }
+
+    /// Blocks until all or one of the given timeline semaphores reach their associated values.
+    /// Returns true if the values were reached before the timeout.
+    unsafe fn wait_for_semaphores<'a, I>(
+        &self,
+        semaphores: I,
+        wait: WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, WaitError>
+    where
+        I: Iterator<Item = (&'a B::Semaphore, u64)>,
+    {
+        use std::{thread, time};
+        fn to_ns(duration: time::Duration) -> u64 {
+            duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+        }
+
+        let start = time::Instant::now();
+        let semaphores: Vec<_> = semaphores.collect();
+        loop {
+            let mut reached = 0;
+            for &(semaphore, value) in &semaphores {
+                if self.get_semaphore_counter_value(semaphore)? >= value {
+                    reached += 1;
+                }
+            }
+            let done = match wait {
+                WaitFor::All => reached == semaphores.len(),
+                WaitFor::Any => reached != 0 || semaphores.is_empty(),
+            };
+            if done {
+                return Ok(true);
+            }
+            if to_ns(start.elapsed()) >= timeout_ns {
+                return Ok(false);
+            }
+            thread::sleep(time::Duration::from_millis(1));
+        }
+    }
+
     /// Create a new fence object.
     ///
     /// Fences are a synchronization primitive that **can** be used to insert a dependency from
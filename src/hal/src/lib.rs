@@ -287,6 +287,9 @@ bitflags! {
         const MESH_SHADER = 0x0002 << 96;
         /// Mask for all the features associated with mesh shader stages.
         const MESH_SHADER_MASK = Features::TASK_SHADER.bits | Features::MESH_SHADER.bits;
+        /// Supports timeline semaphores, whose payload is a monotonically increasing 64-bit counter
+        /// that can be signaled and waited on by value from both the host and the queues.
+        const TIMELINE_SEMAPHORE = 0x0004 << 96;
     }
 }
 
@@ -129,6 +129,46 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
         Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
         Is: Iterator<Item = &'a B::Semaphore>;
 
+    /// Submit command buffers to queue for execution, waiting on and signaling
+    /// specific values of timeline semaphores.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_buffers` - command buffers to submit.
+    /// * `wait_semaphores` - semaphores to wait on before submission, along with the
+    ///   counter value to wait for. The value is ignored for binary semaphores.
+    /// * `signal_semaphores` - semaphores to signal after all command buffers
+    ///   in the submission have finished execution, along with the counter value
+    ///   to set. The value is ignored for binary semaphores.
+    /// * `fence` - must be in unsignaled state, and will be signaled after
+    ///   all command buffers in the submission have finished execution.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`submit`][Queue::submit].
+    /// Timeline semaphores can only be used if `Features::TIMELINE_SEMAPHORE` is enabled.
+    ///
+    /// The default implementation drops the values and falls back to [`submit`][Queue::submit],
+    /// which is only correct for backends without `Features::TIMELINE_SEMAPHORE`.
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut B::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a B::CommandBuffer>,
+        Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage, u64)>,
+        Is: Iterator<Item = (&'a B::Semaphore, u64)>,
+    {
+        self.submit(
+            command_buffers,
+            wait_semaphores.map(|(semaphore, stage, _)| (semaphore, stage)),
+            signal_semaphores.map(|(semaphore, _)| semaphore),
+            fence,
+        )
+    }
+
     /// Present a swapchain image directly to a surface, after waiting on `wait_semaphore`.
     ///
     /// # Safety
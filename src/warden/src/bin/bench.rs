@@ -14,7 +14,7 @@ extern crate gfx_warden as warden;
 extern crate serde;
 
 use hal::{adapter::PhysicalDevice as _, Instance as _};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::path::PathBuf;
 
@@ -29,6 +29,10 @@ enum Expectation {
 #[derive(Debug, Deserialize)]
 struct Test {
     jobs: Vec<String>,
+    /// Only parsed to reject the tests using them, since
+    /// the submissions can't be timed as a single command buffer.
+    #[serde(default)]
+    submissions: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +53,91 @@ struct TestGroup {
 #[derive(Default)]
 struct Disabilities {}
 
+/// Timing statistics of a single test, in nanoseconds.
+#[derive(Debug, Deserialize, Serialize)]
+struct Stats {
+    min: f64,
+    median: f64,
+    p95: f64,
+    iterations: usize,
+}
+
+impl Stats {
+    fn from_samples(mut samples: Vec<f64>) -> Self {
+        assert!(!samples.is_empty());
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        Stats {
+            min: samples[0],
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            iterations: samples.len(),
+        }
+    }
+}
+
+/// Statistics per backend, then per "scene/test".
+type Baseline = BTreeMap<String, BTreeMap<String, Stats>>;
+
+struct Options {
+    suite_name: String,
+    warmup: usize,
+    iterations: usize,
+    /// Path to save the results to.
+    save: Option<PathBuf>,
+    /// Path of a previous baseline to compare with.
+    baseline: Option<PathBuf>,
+    /// Relative slowdown of the median considered a regression, in percent.
+    threshold: f64,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            suite_name: String::new(),
+            warmup: 3,
+            iterations: 20,
+            save: None,
+            baseline: None,
+            threshold: 5.0,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+            match arg.as_str() {
+                "--warmup" => {
+                    options.warmup = value(&arg)?
+                        .parse::<usize>()
+                        .map_err(|e| format!("{:?}", e))?
+                }
+                "--iterations" => {
+                    options.iterations = value(&arg)?
+                        .parse::<usize>()
+                        .map_err(|e| format!("{:?}", e))?
+                }
+                "--threshold" => {
+                    options.threshold = value(&arg)?
+                        .parse::<f64>()
+                        .map_err(|e| format!("{:?}", e))?
+                }
+                "--save" => options.save = Some(PathBuf::from(value(&arg)?)),
+                "--baseline" => options.baseline = Some(PathBuf::from(value(&arg)?)),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                other => options.suite_name = other.to_string(),
+            }
+        }
+        if options.suite_name.is_empty() {
+            return Err("Missing the bench suite name".to_string());
+        }
+        if options.iterations == 0 {
+            return Err("At least one iteration is required".to_string());
+        }
+        Ok(options)
+    }
+}
+
 struct Harness {
     base_path: PathBuf,
     suite: Vec<TestGroup>,
@@ -74,6 +163,16 @@ impl Harness {
                     .map_err(de::Error::from)
                     .and_then(de::from_reader)
                     .expect("failed to open/parse the scene");
+                if let Some((test_name, _)) = raw_group
+                    .tests
+                    .iter()
+                    .find(|&(_, test)| !test.submissions.is_empty())
+                {
+                    panic!(
+                        "Test '{}/{}' uses submissions, which can't be benched",
+                        name, test_name
+                    );
+                }
                 let features = raw_group
                     .features
                     .into_iter()
@@ -90,17 +189,24 @@ impl Harness {
         Harness { base_path, suite }
     }
 
-    fn run<B: hal::Backend>(&self, name: &str, disabilities: Disabilities) {
+    fn run<B: hal::Backend>(
+        &self,
+        name: &str,
+        options: &Options,
+        disabilities: Disabilities,
+    ) -> BTreeMap<String, Stats> {
         println!("Benching {}:", name);
         let instance = B::Instance::create("warden", 1).unwrap();
-        self.run_instance(instance, disabilities)
+        self.run_instance(instance, options, disabilities)
     }
 
     fn run_instance<B: hal::Backend, I: hal::Instance<B>>(
         &self,
         instance: I,
+        options: &Options,
         _disabilities: Disabilities,
-    ) {
+    ) -> BTreeMap<String, Stats> {
+        let mut results = BTreeMap::new();
         for tg in &self.suite {
             let mut adapters = instance.enumerate_adapters();
             let adapter = adapters.remove(0);
@@ -109,18 +215,6 @@ impl Harness {
             //println!("\t{:?}", adapter.info);
             println!("\tScene '{}':", tg.name);
 
-            #[cfg(not(feature = "glsl-to-spirv"))]
-            {
-                let all_spirv = tg.scene.resources.values().all(|res| match *res {
-                    warden::raw::Resource::Shader(ref name) => name.ends_with(".spirv"),
-                    _ => true,
-                });
-                if !all_spirv {
-                    println!("\t\tskipped {} tests (GLSL shaders)", tg.tests.len());
-                    continue;
-                }
-            }
-
             if !supported_features.contains(tg.features) {
                 println!(
                     "\tskipped (features missing: {:?})",
@@ -141,11 +235,9 @@ impl Harness {
                 print!("\t\tTest '{}' ...", test_name);
                 let mut max_compute_work_groups = [0; 3];
                 for job_name in &test.jobs {
-                    if let warden::raw::Job::Compute { dispatch, .. } = tg.scene.jobs[job_name] {
-                        for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter())
-                        {
-                            *max = (*max).max(*count);
-                        }
+                    let dispatch = tg.scene.jobs[job_name].max_dispatch();
+                    for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter()) {
+                        *max = (*max).max(*count);
                     }
                 }
                 if max_compute_work_groups[0] > limits.max_compute_work_group_size[0]
@@ -156,48 +248,113 @@ impl Harness {
                     continue;
                 }
 
-                scene.run(test.jobs.iter());
-                let time = scene.measure_time();
-                println!(" {} mcs", time / 1000);
+                for _ in 0..options.warmup {
+                    scene.run(test.jobs.iter());
+                }
+                let mut samples = Vec::with_capacity(options.iterations);
+                for _ in 0..options.iterations {
+                    scene.run(test.jobs.iter());
+                    match scene.measure_time() {
+                        Some(time) => samples.push(time),
+                        None => break,
+                    }
+                }
+                if samples.is_empty() {
+                    println!("\tskipped (no timestamps)");
+                    continue;
+                }
+
+                let stats = Stats::from_samples(samples);
+                println!(
+                    " min {:.1} mcs, median {:.1} mcs, p95 {:.1} mcs",
+                    stats.min / 1000.0,
+                    stats.median / 1000.0,
+                    stats.p95 / 1000.0,
+                );
+                results.insert(format!("{}/{}", tg.name, test_name), stats);
+            }
+        }
+        results
+    }
+}
+
+/// Compares the medians against the baseline, returning the number of regressions.
+fn compare(baseline: &Baseline, current: &Baseline, threshold: f64) -> usize {
+    let mut num_regressions = 0;
+    for (backend, tests) in current {
+        let old_tests = match baseline.get(backend) {
+            Some(tests) => tests,
+            None => continue,
+        };
+        println!("Comparing {}:", backend);
+        for (name, stats) in tests {
+            let old = match old_tests.get(name) {
+                Some(old) => old,
+                None => {
+                    println!("\t{}: new", name);
+                    continue;
+                }
+            };
+            let change = 100.0 * (stats.median - old.median) / old.median;
+            if change > threshold {
+                println!("\t{}: REGRESSION {:+.1}%", name, change);
+                num_regressions += 1;
+            } else {
+                println!("\t{}: {:+.1}%", name, change);
             }
         }
     }
+    num_regressions
 }
 
 fn main() {
-    use std::env;
+    use std::{env, process};
 
     #[cfg(feature = "env_logger")]
     env_logger::init();
 
-    let suite_name = match env::args().nth(1) {
-        Some(name) => name,
-        None => {
-            println!("Call with the argument of the bench suite name");
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
+            println!(
+                "Usage: bench <suite> [--warmup N] [--iterations N] \
+                 [--save results.json] [--baseline results.json] [--threshold percent]"
+            );
             return;
         }
     };
 
-    let harness = Harness::new(&suite_name);
+    let harness = Harness::new(&options.suite_name);
+    let mut results = Baseline::new();
     #[cfg(feature = "vulkan")]
     {
-        harness.run::<gfx_backend_vulkan::Backend>("Vulkan", Disabilities::default());
+        let stats =
+            harness.run::<gfx_backend_vulkan::Backend>("Vulkan", &options, Disabilities::default());
+        results.insert("Vulkan".to_string(), stats);
     }
     #[cfg(feature = "dx12")]
     {
-        harness.run::<gfx_backend_dx12::Backend>("DX12", Disabilities::default());
+        let stats =
+            harness.run::<gfx_backend_dx12::Backend>("DX12", &options, Disabilities::default());
+        results.insert("DX12".to_string(), stats);
     }
     #[cfg(feature = "dx11")]
     {
-        harness.run::<gfx_backend_dx11::Backend>("DX11", Disabilities::default());
+        let stats =
+            harness.run::<gfx_backend_dx11::Backend>("DX11", &options, Disabilities::default());
+        results.insert("DX11".to_string(), stats);
     }
     #[cfg(feature = "metal")]
     {
-        harness.run::<gfx_backend_metal::Backend>("Metal", Disabilities::default());
+        let stats =
+            harness.run::<gfx_backend_metal::Backend>("Metal", &options, Disabilities::default());
+        results.insert("Metal".to_string(), stats);
     }
     #[cfg(feature = "gl")]
     {
-        harness.run::<gfx_backend_gl::Backend>("GL", Disabilities::default());
+        let stats = harness.run::<gfx_backend_gl::Backend>("GL", &options, Disabilities::default());
+        results.insert("GL".to_string(), stats);
     }
     #[cfg(not(any(
         feature = "vulkan",
@@ -210,4 +367,144 @@ fn main() {
         println!("No backend selected!");
         let _ = harness;
     }
+
+    if let Some(ref path) = options.save {
+        let file = File::create(path).expect(&format!("failed to create {:?}", path));
+        serde_json::to_writer_pretty(file, &results).expect("failed to save the results");
+        println!("Saved the results to {:?}", path);
+    }
+
+    if let Some(ref path) = options.baseline {
+        let baseline: Baseline = File::open(path)
+            .map_err(serde_json::Error::io)
+            .and_then(serde_json::from_reader)
+            .expect(&format!("failed to open/parse the baseline: {:?}", path));
+        let num_regressions = compare(&baseline, &results, options.threshold);
+        if num_regressions != 0 {
+            println!(
+                "{} regression(s) above {}%",
+                num_regressions, options.threshold
+            );
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(median: f64) -> Stats {
+        Stats {
+            min: median,
+            median,
+            p95: median,
+            iterations: 1,
+        }
+    }
+
+    fn baseline(entries: &[(&str, &str, f64)]) -> Baseline {
+        let mut baseline = Baseline::new();
+        for &(backend, test, median) in entries {
+            baseline
+                .entry(backend.to_string())
+                .or_default()
+                .insert(test.to_string(), stats(median));
+        }
+        baseline
+    }
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn stats_from_unsorted_samples() {
+        let stats = Stats::from_samples(vec![5.0, 1.0, 4.0, 2.0, 3.0]);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.p95, 5.0);
+        assert_eq!(stats.iterations, 5);
+    }
+
+    #[test]
+    fn stats_percentiles() {
+        let stats = Stats::from_samples((1..=100).map(|x| x as f64).collect());
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.median, 51.0);
+        assert_eq!(stats.p95, 95.0);
+    }
+
+    #[test]
+    fn stats_from_single_sample() {
+        let stats = Stats::from_samples(vec![7.0]);
+        assert_eq!((stats.min, stats.median, stats.p95), (7.0, 7.0, 7.0));
+    }
+
+    #[test]
+    fn compare_threshold() {
+        let old = baseline(&[("GL", "a/slower", 100.0), ("GL", "a/regressed", 100.0)]);
+        let new = baseline(&[("GL", "a/slower", 105.0), ("GL", "a/regressed", 105.1)]);
+        assert_eq!(compare(&old, &new, 5.0), 1);
+        assert_eq!(compare(&old, &new, 10.0), 0);
+    }
+
+    #[test]
+    fn compare_ignores_improvements() {
+        let old = baseline(&[("Vulkan", "a/faster", 100.0)]);
+        let new = baseline(&[("Vulkan", "a/faster", 50.0)]);
+        assert_eq!(compare(&old, &new, 0.0), 0);
+    }
+
+    #[test]
+    fn compare_skips_unknown_entries() {
+        let old = baseline(&[("Vulkan", "a/old", 100.0)]);
+        let new = baseline(&[("Vulkan", "a/new", 1000.0), ("GL", "a/old", 1000.0)]);
+        assert_eq!(compare(&old, &new, 5.0), 0);
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let options = parse(&["blit"]).unwrap();
+        assert_eq!(options.suite_name, "blit");
+        assert_eq!(options.warmup, 3);
+        assert_eq!(options.iterations, 20);
+        assert_eq!(options.threshold, 5.0);
+        assert!(options.save.is_none());
+        assert!(options.baseline.is_none());
+    }
+
+    #[test]
+    fn parse_all_options() {
+        let options = parse(&[
+            "--warmup",
+            "1",
+            "blit",
+            "--iterations",
+            "50",
+            "--threshold",
+            "2.5",
+            "--save",
+            "new.json",
+            "--baseline",
+            "old.json",
+        ])
+        .unwrap();
+        assert_eq!(options.suite_name, "blit");
+        assert_eq!(options.warmup, 1);
+        assert_eq!(options.iterations, 50);
+        assert_eq!(options.threshold, 2.5);
+        assert_eq!(options.save, Some(PathBuf::from("new.json")));
+        assert_eq!(options.baseline, Some(PathBuf::from("old.json")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--warmup", "1"]).is_err());
+        assert!(parse(&["blit", "--iterations"]).is_err());
+        assert!(parse(&["blit", "--iterations", "0"]).is_err());
+        assert!(parse(&["blit", "--iterations", "many"]).is_err());
+        assert!(parse(&["blit", "--verbose"]).is_err());
+    }
 }
@@ -28,7 +28,10 @@ enum Expectation {
 
 #[derive(Debug, Deserialize)]
 struct Test {
+    #[serde(default)]
     jobs: Vec<String>,
+    #[serde(default)]
+    submissions: Vec<String>,
     expect: Expectation,
 }
 
@@ -122,19 +125,6 @@ impl Harness {
             //println!("\t{:?}", adapter.info);
             println!("\tScene '{}':", tg.name);
 
-            #[cfg(not(feature = "glsl-to-spirv"))]
-            {
-                let all_spirv = tg.scene.resources.values().all(|res| match *res {
-                    warden::raw::Resource::Shader(ref name) => name.ends_with(".spirv"),
-                    _ => true,
-                });
-                if !all_spirv {
-                    println!("\t\tskipped {} tests (GLSL shaders)", tg.tests.len());
-                    results.skip += tg.tests.len();
-                    continue;
-                }
-            }
-
             if !supported_features.contains(tg.features) {
                 println!(
                     "\tskipped (features missing: {:?})",
@@ -155,12 +145,14 @@ impl Harness {
             for (test_name, test) in &tg.tests {
                 print!("\t\tTest '{}' ...", test_name);
                 let mut max_compute_work_groups = [0; 3];
-                for job_name in &test.jobs {
-                    if let warden::raw::Job::Compute { dispatch, .. } = tg.scene.jobs[job_name] {
-                        for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter())
-                        {
-                            *max = (*max).max(*count);
-                        }
+                let submitted_jobs = test
+                    .submissions
+                    .iter()
+                    .flat_map(|name| tg.scene.submissions[name].jobs.iter());
+                for job_name in test.jobs.iter().chain(submitted_jobs) {
+                    let dispatch = tg.scene.jobs[job_name].max_dispatch();
+                    for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter()) {
+                        *max = (*max).max(*count);
                     }
                 }
                 if max_compute_work_groups[0] > limits.max_compute_work_group_size[0]
@@ -172,7 +164,11 @@ impl Harness {
                     continue;
                 }
 
-                scene.run(test.jobs.iter());
+                if test.submissions.is_empty() {
+                    scene.run(test.jobs.iter());
+                } else {
+                    scene.run_submissions(test.submissions.iter());
+                }
 
                 print!("\tran: ");
                 let (guard, row, data) = match test.expect {
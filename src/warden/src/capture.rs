@@ -0,0 +1,1448 @@
+//! Capture of a live hal session into a warden scene.
+//!
+//! The application calls the capture hooks next to its own hal calls,
+//! naming the objects it creates and the commands it records, and the
+//! `Capture` saves them as a `raw::Scene` that `gpu::Scene` can replay
+//! on any backend. Hooks like `create_render_pass` take the same arguments
+//! as the matching `Device` method and name the objects they refer to.
+//!
+//! The `CommandBuffer` hooks take the name of the job they record into
+//! as their first argument. Jobs are created on the default queue by the
+//! first command recorded into them, unless they were added with `add_job`.
+
+use crate::raw;
+
+use hal::{buffer as b, command as c, format as f, image as i, pass, pso, queue::QueueType};
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// A `pso::Descriptor` with the objects it refers to named.
+#[derive(Clone, Debug)]
+pub enum Descriptor<'a> {
+    Sampler(&'a str),
+    Image(&'a str, i::Layout),
+    CombinedImageSampler(&'a str, i::Layout, &'a str),
+    Buffer(&'a str, b::SubRange),
+    TexelBuffer(&'a str),
+}
+
+impl Descriptor<'_> {
+    fn to_range(&self) -> raw::DescriptorRange {
+        let mut range = match *self {
+            Descriptor::Sampler(_) => raw::DescriptorRange::Samplers(Vec::new()),
+            Descriptor::Image(..) => raw::DescriptorRange::Images(Vec::new()),
+            Descriptor::CombinedImageSampler(..) => {
+                raw::DescriptorRange::CombinedImageSamplers(Vec::new())
+            }
+            Descriptor::Buffer(..) => raw::DescriptorRange::BufferRanges(Vec::new()),
+            Descriptor::TexelBuffer(_) => raw::DescriptorRange::TexelBuffers(Vec::new()),
+        };
+        let appended = self.append_to(&mut range);
+        debug_assert!(appended);
+        range
+    }
+
+    /// Appends the descriptor to a range of the same type, returning `false`
+    /// if the types don't match.
+    fn append_to(&self, range: &mut raw::DescriptorRange) -> bool {
+        match (self, range) {
+            (&Descriptor::Sampler(sampler), &mut raw::DescriptorRange::Samplers(ref mut list)) => {
+                list.push(sampler.to_string())
+            }
+            (&Descriptor::Image(view, layout), &mut raw::DescriptorRange::Images(ref mut list)) => {
+                list.push((view.to_string(), layout))
+            }
+            (
+                &Descriptor::CombinedImageSampler(view, layout, sampler),
+                &mut raw::DescriptorRange::CombinedImageSamplers(ref mut list),
+            ) => list.push((view.to_string(), layout, sampler.to_string())),
+            (
+                &Descriptor::Buffer(buffer, ref sub),
+                &mut raw::DescriptorRange::BufferRanges(ref mut list),
+            ) => list.push((buffer.to_string(), sub.clone())),
+            (
+                &Descriptor::TexelBuffer(view),
+                &mut raw::DescriptorRange::TexelBuffers(ref mut list),
+            ) => list.push(view.to_string()),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A `memory::Barrier` with the objects it refers to named, and the queue
+/// names in place of the queue families of ownership transfers.
+#[derive(Clone, Debug)]
+pub enum Barrier<'a> {
+    AllBuffers(Range<b::Access>),
+    AllImages(Range<i::Access>),
+    Buffer {
+        states: Range<b::State>,
+        target: &'a str,
+        range: b::SubRange,
+        families: Option<Range<&'a str>>,
+    },
+    Image {
+        states: Range<i::State>,
+        target: &'a str,
+        range: i::SubresourceRange,
+        families: Option<Range<&'a str>>,
+    },
+}
+
+impl Barrier<'_> {
+    fn to_raw(&self) -> raw::Barrier {
+        let families = |families: &Option<Range<&str>>| {
+            families
+                .as_ref()
+                .map(|f| f.start.to_string()..f.end.to_string())
+        };
+        match *self {
+            Barrier::AllBuffers(ref access) => raw::Barrier::AllBuffers(access.clone()),
+            Barrier::AllImages(ref access) => raw::Barrier::AllImages(access.clone()),
+            Barrier::Buffer {
+                ref states,
+                target,
+                ref range,
+                families: ref fam,
+            } => raw::Barrier::Buffer {
+                buffer: target.to_string(),
+                states: states.clone(),
+                range: range.clone(),
+                families: families(fam),
+            },
+            Barrier::Image {
+                ref states,
+                target,
+                ref range,
+                families: ref fam,
+            } => raw::Barrier::Image {
+                image: target.to_string(),
+                states: states.clone(),
+                range: range.clone(),
+                families: families(fam),
+            },
+        }
+    }
+}
+
+/// Converts a clear value to the type of the format it applies to.
+fn clear_value(format: Option<f::Format>, value: c::ClearValue) -> raw::ClearValue {
+    // The union is read as the type the format is cleared with.
+    unsafe {
+        match format {
+            Some(format) if format.is_depth() || format.is_stencil() => {
+                raw::ClearValue::DepthStencil(value.depth_stencil)
+            }
+            Some(format) => raw::ClearValue::Color(match format.base_format().1 {
+                f::ChannelType::Uint => raw::ClearColor::Uint(value.color.uint32),
+                f::ChannelType::Sint => raw::ClearColor::Sint(value.color.sint32),
+                _ => raw::ClearColor::Float(value.color.float32),
+            }),
+            None => raw::ClearValue::Color(raw::ClearColor::Float(value.color.float32)),
+        }
+    }
+}
+
+/// Collects the specialization constants of an entry point into `constants`,
+/// which is shared by all the stages of a pipeline.
+fn add_specialization<B: hal::Backend>(
+    constants: &mut Vec<(u32, raw::SpecializationConstant)>,
+    entry: &pso::EntryPoint<B>,
+) {
+    assert_eq!(
+        entry.entry, "main",
+        "Only the `main` entry points can be captured"
+    );
+    let spec = &entry.specialization;
+    for constant in spec.constants.iter() {
+        let bytes = &spec.data[constant.range.start as usize..constant.range.end as usize];
+        let bytes: [u8; 4] = bytes.try_into().expect(&format!(
+            "Specialization constant {} is not 32-bit",
+            constant.id
+        ));
+        let value = u32::from_ne_bytes(bytes);
+        match constants.iter().find(|&&(id, _)| id == constant.id) {
+            Some(&(_, raw::SpecializationConstant::U32(other))) => assert_eq!(
+                value, other,
+                "Specialization constant {} differs between stages",
+                constant.id
+            ),
+            Some(_) => unreachable!(),
+            None => constants.push((constant.id, raw::SpecializationConstant::U32(value))),
+        }
+    }
+}
+
+pub struct Capture {
+    name: String,
+    scene: raw::Scene,
+    /// Contents of the data files, by file name.
+    data: HashMap<String, Vec<u8>>,
+    /// Current subpass of the graphics jobs inside of their render pass.
+    subpasses: HashMap<String, pass::SubpassId>,
+}
+
+impl Capture {
+    /// Starts a new capture. The name is used for the scene file
+    /// and as a prefix for the data files.
+    pub fn new(name: &str) -> Self {
+        Capture {
+            name: name.to_string(),
+            scene: raw::Scene::default(),
+            data: HashMap::new(),
+            subpasses: HashMap::new(),
+        }
+    }
+
+    pub fn scene(&self) -> &raw::Scene {
+        &self.scene
+    }
+
+    fn add_data(&mut self, resource: &str, extension: &str, contents: Vec<u8>) -> String {
+        let stem: String = resource
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+        let file_name = format!("{}-{}.{}", self.name, stem, extension);
+        let old = self.data.insert(file_name.clone(), contents);
+        assert!(old.is_none(), "Duplicate data file: {}", file_name);
+        file_name
+    }
+
+    /// Records a resource that doesn't carry any data.
+    pub fn add_resource(&mut self, name: &str, resource: raw::Resource) {
+        let old = self.scene.resources.insert(name.to_string(), resource);
+        assert!(old.is_none(), "Duplicate resource: {}", name);
+    }
+
+    /// Records a buffer, optionally with its initial contents.
+    pub fn add_buffer(
+        &mut self,
+        name: &str,
+        size: usize,
+        usage: b::Usage,
+        contents: Option<&[u8]>,
+    ) {
+        let data = match contents {
+            Some(bytes) => {
+                assert_eq!(bytes.len(), size, "Buffer {} contents size mismatch", name);
+                self.add_data(name, "raw", bytes.to_vec())
+            }
+            None => String::new(),
+        };
+        self.add_resource(name, raw::Resource::Buffer { size, usage, data });
+    }
+
+    /// Records an image, optionally with the contents of all its levels,
+    /// laid out as described by `raw::image_level_data_size`.
+    pub fn add_image(
+        &mut self,
+        name: &str,
+        kind: i::Kind,
+        num_levels: i::Level,
+        format: f::Format,
+        usage: i::Usage,
+        view_caps: i::ViewCapabilities,
+        contents: Option<&[u8]>,
+    ) {
+        let data = match contents {
+            Some(bytes) => {
+                let size = (0..num_levels)
+                    .map(|level| raw::image_level_data_size(kind, format, level))
+                    .sum::<usize>();
+                assert_eq!(bytes.len(), size, "Image {} contents size mismatch", name);
+                self.add_data(name, "raw", bytes.to_vec())
+            }
+            None => String::new(),
+        };
+        self.add_resource(
+            name,
+            raw::Resource::Image {
+                kind,
+                num_levels,
+                format,
+                usage,
+                view_caps,
+                data,
+            },
+        );
+    }
+
+    /// Records a render pass from the arguments of `Device::create_render_pass`.
+    /// Attachments are named after their indices, and so are the subpasses.
+    pub fn create_render_pass<'a>(
+        &mut self,
+        name: &str,
+        attachments: impl IntoIterator<Item = pass::Attachment>,
+        subpasses: impl IntoIterator<Item = pass::SubpassDesc<'a>>,
+        dependencies: impl IntoIterator<Item = pass::SubpassDependency>,
+    ) {
+        let att_ref =
+            |&(id, layout): &pass::AttachmentRef| raw::AttachmentRef(id.to_string(), layout);
+        let subpass_name =
+            |id: Option<pass::SubpassId>| id.map_or(String::new(), |id| id.to_string());
+        let resource = raw::Resource::RenderPass {
+            attachments: attachments
+                .into_iter()
+                .enumerate()
+                .map(|(id, attachment)| (id.to_string(), attachment))
+                .collect(),
+            subpasses: subpasses
+                .into_iter()
+                .enumerate()
+                .map(|(id, sp)| {
+                    let subpass = raw::Subpass {
+                        colors: sp.colors.iter().map(att_ref).collect(),
+                        depth_stencil: sp.depth_stencil.map(att_ref),
+                        inputs: sp.inputs.iter().map(att_ref).collect(),
+                        preserves: sp.preserves.iter().map(|id| id.to_string()).collect(),
+                        resolves: sp.resolves.iter().map(att_ref).collect(),
+                    };
+                    (id.to_string(), subpass)
+                })
+                .collect(),
+            dependencies: dependencies
+                .into_iter()
+                .map(|dep| raw::SubpassDependency {
+                    passes: subpass_name(dep.passes.start)..subpass_name(dep.passes.end),
+                    stages: dep.stages,
+                    accesses: dep.accesses,
+                })
+                .collect(),
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a shader module created from SPIR-V.
+    pub fn add_shader(&mut self, name: &str, spirv: &[u32]) {
+        let mut bytes = Vec::with_capacity(spirv.len() * 4);
+        for word in spirv {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        let path = self.add_data(name, "spirv", bytes);
+        self.add_resource(name, raw::Resource::Shader(path));
+    }
+
+    /// Records a shader module from its source code, with the extension
+    /// telling the language apart, e.g. "wgsl" or "comp".
+    pub fn add_shader_source(&mut self, name: &str, extension: &str, code: &str) {
+        let path = self.add_data(name, extension, code.as_bytes().to_vec());
+        self.add_resource(name, raw::Resource::Shader(path));
+    }
+
+    /// Records a descriptor set layout from the arguments of
+    /// `Device::create_descriptor_set_layout`.
+    pub fn create_descriptor_set_layout<'a>(
+        &mut self,
+        name: &str,
+        bindings: impl IntoIterator<Item = pso::DescriptorSetLayoutBinding>,
+        immutable_samplers: impl IntoIterator<Item = &'a str>,
+    ) {
+        let resource = raw::Resource::DescriptorSetLayout {
+            bindings: bindings.into_iter().collect(),
+            immutable_samplers: immutable_samplers
+                .into_iter()
+                .map(|sampler| sampler.to_string())
+                .collect(),
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a descriptor pool from the arguments of `Device::create_descriptor_pool`.
+    pub fn create_descriptor_pool(
+        &mut self,
+        name: &str,
+        max_sets: usize,
+        descriptor_ranges: impl IntoIterator<Item = pso::DescriptorRangeDesc>,
+    ) {
+        let resource = raw::Resource::DescriptorPool {
+            capacity: max_sets,
+            ranges: descriptor_ranges.into_iter().collect(),
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a descriptor set allocated from a pool, to be filled
+    /// by `write_descriptor_set`.
+    pub fn allocate_descriptor_set(&mut self, name: &str, pool: &str, layout: &str) {
+        let resource = raw::Resource::DescriptorSet {
+            pool: pool.to_string(),
+            layout: layout.to_string(),
+            data: Vec::new(),
+            writes: Vec::new(),
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records the descriptors of a `pso::DescriptorSetWrite`. The descriptors
+    /// spill over onto the next bindings, like in `Device::write_descriptor_set`.
+    pub fn write_descriptor_set<'a>(
+        &mut self,
+        set: &str,
+        binding: pso::DescriptorBinding,
+        array_offset: pso::DescriptorArrayIndex,
+        descriptors: impl IntoIterator<Item = Descriptor<'a>>,
+    ) {
+        let layout = match self.scene.resources.get(set) {
+            Some(&raw::Resource::DescriptorSet { ref layout, .. }) => layout,
+            other => panic!("Missing descriptor set {}: {:?}", set, other),
+        };
+        let mut bindings = match self.scene.resources.get(layout) {
+            Some(&raw::Resource::DescriptorSetLayout { ref bindings, .. }) => bindings.clone(),
+            other => panic!("Missing descriptor set layout {}: {:?}", layout, other),
+        };
+        bindings.sort_by_key(|b| b.binding);
+
+        let mut index = bindings
+            .iter()
+            .position(|b| b.binding == binding)
+            .expect(&format!("Missing binding {} in set {}", binding, set));
+        let mut array_offset = array_offset;
+        let mut writes = Vec::<raw::DescriptorWrite>::new();
+        for descriptor in descriptors {
+            while array_offset >= bindings[index].count {
+                array_offset -= bindings[index].count;
+                index += 1;
+            }
+            // Descriptors of the same type are kept in a single write,
+            // which spills over the same way when replayed.
+            let appended = match writes.last_mut() {
+                Some(write) => descriptor.append_to(&mut write.descriptors),
+                None => false,
+            };
+            if !appended {
+                writes.push(raw::DescriptorWrite {
+                    binding: bindings[index].binding,
+                    array_offset,
+                    descriptors: descriptor.to_range(),
+                });
+            }
+            array_offset += 1;
+        }
+
+        match self.scene.resources.get_mut(set) {
+            Some(&mut raw::Resource::DescriptorSet {
+                writes: ref mut set_writes,
+                ..
+            }) => set_writes.extend(writes),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Records a pipeline layout from the arguments of `Device::create_pipeline_layout`.
+    pub fn create_pipeline_layout<'a>(
+        &mut self,
+        name: &str,
+        set_layouts: impl IntoIterator<Item = &'a str>,
+        push_constant_ranges: impl IntoIterator<Item = (pso::ShaderStageFlags, Range<u32>)>,
+    ) {
+        let resource = raw::Resource::PipelineLayout {
+            set_layouts: set_layouts
+                .into_iter()
+                .map(|layout| layout.to_string())
+                .collect(),
+            push_constant_ranges: push_constant_ranges.into_iter().collect(),
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a graphics pipeline from the description passed to
+    /// `Device::create_graphics_pipeline`, with the names of its shader modules,
+    /// layout and render pass. Only the vertex pipelines can be captured,
+    /// and their baked states are replaced with dynamic ones when replayed.
+    pub fn create_graphics_pipeline<B: hal::Backend>(
+        &mut self,
+        name: &str,
+        desc: &pso::GraphicsPipelineDesc<B>,
+        shaders: raw::GraphicsShaderSet,
+        layout: &str,
+        render_pass: &str,
+    ) {
+        let (buffers, attributes, input_assembler, vertex, tessellation, geometry) =
+            match desc.primitive_assembler {
+                pso::PrimitiveAssemblerDesc::Vertex {
+                    buffers,
+                    attributes,
+                    ref input_assembler,
+                    ref vertex,
+                    ref tessellation,
+                    ref geometry,
+                } => (
+                    buffers,
+                    attributes,
+                    input_assembler,
+                    vertex,
+                    tessellation,
+                    geometry,
+                ),
+                pso::PrimitiveAssemblerDesc::Mesh { .. } => {
+                    panic!(
+                        "Pipeline {} uses mesh shaders, which can't be captured",
+                        name
+                    )
+                }
+            };
+        let stages = [
+            (Some(vertex), &shaders.vertex),
+            (tessellation.as_ref().map(|t| &t.0), &shaders.hull),
+            (tessellation.as_ref().map(|t| &t.1), &shaders.domain),
+            (geometry.as_ref(), &shaders.geometry),
+            (desc.fragment.as_ref(), &shaders.fragment),
+        ];
+        let mut specialization = Vec::new();
+        for &(entry, shader) in stages.iter() {
+            assert_eq!(
+                entry.is_some(),
+                !shader.is_empty(),
+                "Shader names of pipeline {} don't match its stages",
+                name
+            );
+            if let Some(entry) = entry {
+                add_specialization(&mut specialization, entry);
+            }
+        }
+
+        let resource = raw::Resource::GraphicsPipeline {
+            shaders,
+            rasterizer: desc.rasterizer,
+            vertex_buffers: buffers.to_vec(),
+            attributes: attributes.to_vec(),
+            input_assembler: input_assembler.clone(),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            layout: layout.to_string(),
+            subpass: raw::SubpassRef {
+                parent: render_pass.to_string(),
+                index: desc.subpass.index,
+            },
+            specialization,
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a compute pipeline from the description passed to
+    /// `Device::create_compute_pipeline`, with the names of its shader module and layout.
+    pub fn create_compute_pipeline<B: hal::Backend>(
+        &mut self,
+        name: &str,
+        desc: &pso::ComputePipelineDesc<B>,
+        shader: &str,
+        layout: &str,
+    ) {
+        let mut specialization = Vec::new();
+        add_specialization(&mut specialization, &desc.shader);
+        let resource = raw::Resource::ComputePipeline {
+            shader: shader.to_string(),
+            layout: layout.to_string(),
+            specialization,
+        };
+        self.add_resource(name, resource);
+    }
+
+    /// Records a framebuffer from the arguments of `Device::create_framebuffer`.
+    /// The attachments are named after their indices, like in `create_render_pass`.
+    pub fn create_framebuffer(
+        &mut self,
+        name: &str,
+        render_pass: &str,
+        attachments: impl IntoIterator<Item = i::FramebufferAttachment>,
+        extent: i::Extent,
+    ) {
+        let resource = raw::Resource::Framebuffer {
+            pass: render_pass.to_string(),
+            attachments: attachments
+                .into_iter()
+                .enumerate()
+                .map(|(id, attachment)| (id.to_string(), attachment))
+                .collect(),
+            extent,
+        };
+        self.add_resource(name, resource);
+    }
+
+    pub fn add_queue(&mut self, name: &str, ty: QueueType) {
+        let old = self.scene.queues.insert(name.to_string(), ty);
+        assert!(old.is_none(), "Duplicate queue: {}", name);
+    }
+
+    /// Records a job, which can be appended with the `record_*` methods.
+    pub fn add_job(&mut self, name: &str, job: raw::Job) {
+        let old = self.scene.jobs.insert(name.to_string(), job);
+        assert!(old.is_none(), "Duplicate job: {}", name);
+    }
+
+    fn job_mut(&mut self, name: &str) -> &mut raw::Job {
+        self.scene
+            .jobs
+            .get_mut(name)
+            .expect(&format!("Missing job: {}", name))
+    }
+
+    pub fn record_transfer(&mut self, job: &str, command: raw::TransferCommand) {
+        match *self.job_mut(job) {
+            raw::Job::Transfer {
+                ref mut commands, ..
+            } => commands.push(command),
+            ref other => panic!("Job {} is not a transfer: {:?}", job, other),
+        }
+    }
+
+    pub fn record_draw(&mut self, job: &str, subpass: &str, command: raw::DrawCommand) {
+        match *self.job_mut(job) {
+            raw::Job::Graphics {
+                pass: (_, ref mut passes),
+                ..
+            } => passes
+                .entry(subpass.to_string())
+                .or_insert_with(|| raw::DrawPass {
+                    commands: Vec::new(),
+                })
+                .commands
+                .push(command),
+            ref other => panic!("Job {} is not a graphics one: {:?}", job, other),
+        }
+    }
+
+    pub fn record_dispatch(&mut self, job: &str, command: raw::DispatchCommand) {
+        match *self.job_mut(job) {
+            raw::Job::Compute {
+                ref mut commands, ..
+            } => commands.push(command),
+            ref other => panic!("Job {} is not a compute one: {:?}", job, other),
+        }
+    }
+
+    pub fn record_sync(&mut self, job: &str, command: raw::SyncCommand) {
+        match *self.job_mut(job) {
+            raw::Job::Sync {
+                ref mut commands, ..
+            } => commands.push(command),
+            ref other => panic!("Job {} is not a sync one: {:?}", job, other),
+        }
+    }
+
+    fn transfer(&mut self, job: &str, command: raw::TransferCommand) {
+        self.scene
+            .jobs
+            .entry(job.to_string())
+            .or_insert_with(|| raw::Job::Transfer {
+                queue: String::new(),
+                commands: Vec::new(),
+            });
+        self.record_transfer(job, command);
+    }
+
+    fn sync(&mut self, job: &str, command: raw::SyncCommand) {
+        self.scene
+            .jobs
+            .entry(job.to_string())
+            .or_insert_with(|| raw::Job::Sync {
+                queue: String::new(),
+                commands: Vec::new(),
+            });
+        self.record_sync(job, command);
+    }
+
+    fn draw_command(&mut self, job: &str, command: raw::DrawCommand) {
+        let subpass = match self.subpasses.get(job) {
+            Some(subpass) => subpass.to_string(),
+            None => panic!("Job {} is not inside of a render pass", job),
+        };
+        self.record_draw(job, &subpass, command);
+    }
+
+    fn compute_mut(&mut self, job: &str) -> &mut raw::Job {
+        self.scene
+            .jobs
+            .entry(job.to_string())
+            .or_insert_with(|| raw::Job::Compute {
+                queue: String::new(),
+                pipeline: String::new(),
+                descriptor_sets: Vec::new(),
+                dynamic_offsets: Vec::new(),
+                commands: Vec::new(),
+                dispatch: [0; 3],
+            })
+    }
+
+    fn resource_format(&self, name: &str) -> f::Format {
+        match self.scene.resources.get(name) {
+            Some(&raw::Resource::Image { format, .. }) => format,
+            other => panic!("Missing image {}: {:?}", name, other),
+        }
+    }
+
+    pub fn pipeline_barrier<'a>(
+        &mut self,
+        job: &str,
+        stages: Range<pso::PipelineStage>,
+        barriers: impl IntoIterator<Item = Barrier<'a>>,
+    ) {
+        let barriers = barriers.into_iter().map(|b| b.to_raw()).collect();
+        self.sync(job, raw::SyncCommand::PipelineBarrier { stages, barriers });
+    }
+
+    pub fn set_event(&mut self, job: &str, event: &str, stages: pso::PipelineStage) {
+        let event = event.to_string();
+        self.sync(job, raw::SyncCommand::SetEvent { event, stages });
+    }
+
+    pub fn reset_event(&mut self, job: &str, event: &str, stages: pso::PipelineStage) {
+        let event = event.to_string();
+        self.sync(job, raw::SyncCommand::ResetEvent { event, stages });
+    }
+
+    pub fn wait_events<'a>(
+        &mut self,
+        job: &str,
+        events: impl IntoIterator<Item = &'a str>,
+        stages: Range<pso::PipelineStage>,
+        barriers: impl IntoIterator<Item = Barrier<'a>>,
+    ) {
+        let command = raw::SyncCommand::WaitEvents {
+            events: events.into_iter().map(|e| e.to_string()).collect(),
+            stages,
+            barriers: barriers.into_iter().map(|b| b.to_raw()).collect(),
+        };
+        self.sync(job, command);
+    }
+
+    pub fn fill_buffer(&mut self, job: &str, buffer: &str, range: b::SubRange, data: u32) {
+        let command = raw::TransferCommand::FillBuffer {
+            buffer: buffer.to_string(),
+            offset: range.offset,
+            size: range.size,
+            data,
+        };
+        self.transfer(job, command);
+    }
+
+    /// Records an image clear. The value is read as the type of the image format.
+    pub fn clear_image(
+        &mut self,
+        job: &str,
+        image: &str,
+        value: c::ClearValue,
+        subresource_ranges: impl IntoIterator<Item = i::SubresourceRange>,
+    ) {
+        let format = self.resource_format(image);
+        let command = raw::TransferCommand::ClearImage {
+            image: image.to_string(),
+            value: clear_value(Some(format), value),
+            ranges: subresource_ranges.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    pub fn blit_image(
+        &mut self,
+        job: &str,
+        src: &str,
+        dst: &str,
+        filter: i::Filter,
+        regions: impl IntoIterator<Item = c::ImageBlit>,
+    ) {
+        let command = raw::TransferCommand::BlitImage {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            filter,
+            regions: regions.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    pub fn copy_buffer(
+        &mut self,
+        job: &str,
+        src: &str,
+        dst: &str,
+        regions: impl IntoIterator<Item = c::BufferCopy>,
+    ) {
+        let command = raw::TransferCommand::CopyBuffer {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            regions: regions.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    pub fn copy_image(
+        &mut self,
+        job: &str,
+        src: &str,
+        dst: &str,
+        regions: impl IntoIterator<Item = c::ImageCopy>,
+    ) {
+        let command = raw::TransferCommand::CopyImage {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            regions: regions.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    pub fn copy_buffer_to_image(
+        &mut self,
+        job: &str,
+        src: &str,
+        dst: &str,
+        regions: impl IntoIterator<Item = c::BufferImageCopy>,
+    ) {
+        let command = raw::TransferCommand::CopyBufferToImage {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            regions: regions.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    pub fn copy_image_to_buffer(
+        &mut self,
+        job: &str,
+        src: &str,
+        dst: &str,
+        regions: impl IntoIterator<Item = c::BufferImageCopy>,
+    ) {
+        let command = raw::TransferCommand::CopyImageToBuffer {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            regions: regions.into_iter().collect(),
+        };
+        self.transfer(job, command);
+    }
+
+    /// Starts a graphics job with the image views and clear values of the attachments,
+    /// in the order of the render pass. The whole framebuffer is rendered when replayed.
+    pub fn begin_render_pass<'a>(
+        &mut self,
+        job: &str,
+        render_pass: &str,
+        framebuffer: &str,
+        attachments: impl IntoIterator<Item = (&'a str, c::ClearValue)>,
+    ) {
+        let formats = match self.scene.resources.get(render_pass) {
+            Some(&raw::Resource::RenderPass {
+                ref attachments, ..
+            }) => attachments
+                .iter()
+                .map(|(name, attachment)| (name.clone(), attachment.format))
+                .collect::<HashMap<_, _>>(),
+            other => panic!("Missing render pass {}: {:?}", render_pass, other),
+        };
+        let attachments = attachments
+            .into_iter()
+            .enumerate()
+            .map(|(id, (image_view, value))| {
+                let id = id.to_string();
+                let info = raw::RenderAttachmentInfo {
+                    image_view: image_view.to_string(),
+                    clear_value: clear_value(formats[&id], value),
+                };
+                (id, info)
+            })
+            .collect();
+        // Jobs added beforehand only choose the queue.
+        let queue = match self.scene.jobs.get(job) {
+            None => String::new(),
+            Some(&raw::Job::Graphics {
+                ref queue,
+                pass: (_, ref passes),
+                ..
+            }) if passes.is_empty() => queue.clone(),
+            Some(other) => panic!("Job {} is already recorded: {:?}", job, other),
+        };
+        let new_job = raw::Job::Graphics {
+            queue,
+            framebuffer: framebuffer.to_string(),
+            attachments,
+            pass: (render_pass.to_string(), HashMap::new()),
+        };
+        self.scene.jobs.insert(job.to_string(), new_job);
+        self.subpasses.insert(job.to_string(), 0);
+    }
+
+    pub fn next_subpass(&mut self, job: &str) {
+        match self.subpasses.get_mut(job) {
+            Some(subpass) => *subpass += 1,
+            None => panic!("Job {} is not inside of a render pass", job),
+        }
+    }
+
+    pub fn end_render_pass(&mut self, job: &str) {
+        let subpass = self.subpasses.remove(job);
+        assert!(
+            subpass.is_some(),
+            "Job {} is not inside of a render pass",
+            job
+        );
+    }
+
+    pub fn bind_index_buffer(
+        &mut self,
+        job: &str,
+        buffer: &str,
+        sub: b::SubRange,
+        ty: hal::IndexType,
+    ) {
+        let command = raw::DrawCommand::BindIndexBuffer {
+            buffer: buffer.to_string(),
+            range: sub,
+            index_type: ty,
+        };
+        self.draw_command(job, command);
+    }
+
+    /// Records vertex buffers, which are bound from the first binding when replayed.
+    pub fn bind_vertex_buffers<'a>(
+        &mut self,
+        job: &str,
+        first_binding: pso::BufferIndex,
+        buffers: impl IntoIterator<Item = (&'a str, b::SubRange)>,
+    ) {
+        assert_eq!(
+            first_binding, 0,
+            "Vertex buffers are captured from binding 0"
+        );
+        let buffers = buffers
+            .into_iter()
+            .map(|(buffer, sub)| (buffer.to_string(), sub))
+            .collect();
+        self.draw_command(job, raw::DrawCommand::BindVertexBuffers(buffers));
+    }
+
+    /// Records viewports, which are set from the first one when replayed.
+    pub fn set_viewports(
+        &mut self,
+        job: &str,
+        first_viewport: u32,
+        viewports: impl IntoIterator<Item = pso::Viewport>,
+    ) {
+        assert_eq!(first_viewport, 0, "Viewports are captured from index 0");
+        let viewports = viewports.into_iter().collect();
+        self.draw_command(job, raw::DrawCommand::SetViewports(viewports));
+    }
+
+    /// Records scissors, which are set from the first one when replayed.
+    pub fn set_scissors(
+        &mut self,
+        job: &str,
+        first_scissor: u32,
+        rects: impl IntoIterator<Item = pso::Rect>,
+    ) {
+        assert_eq!(first_scissor, 0, "Scissors are captured from index 0");
+        let rects = rects.into_iter().collect();
+        self.draw_command(job, raw::DrawCommand::SetScissors(rects));
+    }
+
+    pub fn set_stencil_reference(&mut self, job: &str, faces: pso::Face, value: pso::StencilValue) {
+        self.draw_command(job, raw::DrawCommand::SetStencilReference { faces, value });
+    }
+
+    pub fn set_blend_constants(&mut self, job: &str, color: pso::ColorValue) {
+        self.draw_command(job, raw::DrawCommand::SetBlendConstants(color));
+    }
+
+    pub fn set_depth_bias(&mut self, job: &str, depth_bias: pso::DepthBias) {
+        self.draw_command(job, raw::DrawCommand::SetDepthBias(depth_bias));
+    }
+
+    pub fn bind_graphics_pipeline(&mut self, job: &str, pipeline: &str) {
+        let pipeline = pipeline.to_string();
+        self.draw_command(job, raw::DrawCommand::BindPipeline(pipeline));
+    }
+
+    pub fn bind_graphics_descriptor_sets<'a>(
+        &mut self,
+        job: &str,
+        layout: &str,
+        first_set: usize,
+        sets: impl IntoIterator<Item = &'a str>,
+        offsets: impl IntoIterator<Item = c::DescriptorSetOffset>,
+    ) {
+        let command = raw::DrawCommand::BindDescriptorSets {
+            layout: layout.to_string(),
+            first: first_set,
+            sets: sets.into_iter().map(|set| set.to_string()).collect(),
+            dynamic_offsets: offsets.into_iter().collect(),
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn push_graphics_constants(
+        &mut self,
+        job: &str,
+        layout: &str,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        let command = raw::DrawCommand::PushConstants {
+            layout: layout.to_string(),
+            stages,
+            offset,
+            data: constants.to_vec(),
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn draw(
+        &mut self,
+        job: &str,
+        vertices: Range<hal::VertexCount>,
+        instances: Range<hal::InstanceCount>,
+    ) {
+        self.draw_command(
+            job,
+            raw::DrawCommand::Draw {
+                vertices,
+                instances,
+            },
+        );
+    }
+
+    pub fn draw_indexed(
+        &mut self,
+        job: &str,
+        indices: Range<hal::IndexCount>,
+        base_vertex: hal::VertexOffset,
+        instances: Range<hal::InstanceCount>,
+    ) {
+        let command = raw::DrawCommand::DrawIndexed {
+            indices,
+            base_vertex,
+            instances,
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn draw_indirect(
+        &mut self,
+        job: &str,
+        buffer: &str,
+        offset: b::Offset,
+        draw_count: hal::DrawCount,
+        stride: b::Stride,
+    ) {
+        let command = raw::DrawCommand::DrawIndirect {
+            buffer: buffer.to_string(),
+            offset,
+            draw_count,
+            stride,
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn draw_indexed_indirect(
+        &mut self,
+        job: &str,
+        buffer: &str,
+        offset: b::Offset,
+        draw_count: hal::DrawCount,
+        stride: b::Stride,
+    ) {
+        let command = raw::DrawCommand::DrawIndexedIndirect {
+            buffer: buffer.to_string(),
+            offset,
+            draw_count,
+            stride,
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn draw_indirect_count(
+        &mut self,
+        job: &str,
+        buffer: &str,
+        offset: b::Offset,
+        count_buffer: &str,
+        count_buffer_offset: b::Offset,
+        max_draw_count: hal::DrawCount,
+        stride: b::Stride,
+    ) {
+        let command = raw::DrawCommand::DrawIndirectCount {
+            buffer: buffer.to_string(),
+            offset,
+            count_buffer: count_buffer.to_string(),
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        };
+        self.draw_command(job, command);
+    }
+
+    pub fn draw_indexed_indirect_count(
+        &mut self,
+        job: &str,
+        buffer: &str,
+        offset: b::Offset,
+        count_buffer: &str,
+        count_buffer_offset: b::Offset,
+        max_draw_count: hal::DrawCount,
+        stride: b::Stride,
+    ) {
+        let command = raw::DrawCommand::DrawIndexedIndirectCount {
+            buffer: buffer.to_string(),
+            offset,
+            count_buffer: count_buffer.to_string(),
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        };
+        self.draw_command(job, command);
+    }
+
+    /// Starts a compute job with the pipeline, or changes the pipeline
+    /// of a job that hasn't dispatched anything yet.
+    pub fn bind_compute_pipeline(&mut self, job: &str, pipeline: &str) {
+        match *self.compute_mut(job) {
+            raw::Job::Compute {
+                pipeline: ref mut job_pipeline,
+                ref commands,
+                ..
+            } => {
+                assert!(
+                    commands.is_empty(),
+                    "Job {} can't change its pipeline after dispatching",
+                    job
+                );
+                *job_pipeline = pipeline.to_string();
+            }
+            ref other => panic!("Job {} is not a compute one: {:?}", job, other),
+        }
+    }
+
+    /// Records the descriptor sets of a compute job, which are bound
+    /// with the layout of its pipeline when replayed.
+    pub fn bind_compute_descriptor_sets<'a>(
+        &mut self,
+        job: &str,
+        first_set: usize,
+        sets: impl IntoIterator<Item = &'a str>,
+        offsets: impl IntoIterator<Item = c::DescriptorSetOffset>,
+    ) {
+        match *self.compute_mut(job) {
+            raw::Job::Compute {
+                ref mut descriptor_sets,
+                ref mut dynamic_offsets,
+                ref commands,
+                ..
+            } => {
+                assert!(
+                    commands.is_empty(),
+                    "Job {} can't change its descriptor sets after dispatching",
+                    job
+                );
+                // The dynamic offsets can't be told apart between the sets,
+                // so the sets are either all replaced, or appended.
+                if first_set == 0 {
+                    descriptor_sets.clear();
+                    dynamic_offsets.clear();
+                }
+                assert_eq!(
+                    first_set,
+                    descriptor_sets.len(),
+                    "Job {} needs to bind its descriptor sets in order",
+                    job
+                );
+                descriptor_sets.extend(sets.into_iter().map(|set| set.to_string()));
+                dynamic_offsets.extend(offsets);
+            }
+            ref other => panic!("Job {} is not a compute one: {:?}", job, other),
+        }
+    }
+
+    pub fn push_compute_constants(&mut self, job: &str, offset: u32, constants: &[u32]) {
+        let command = raw::DispatchCommand::PushConstants {
+            offset,
+            data: constants.to_vec(),
+        };
+        self.record_dispatch(job, command);
+    }
+
+    pub fn dispatch(&mut self, job: &str, count: hal::WorkGroupCount) {
+        self.record_dispatch(job, raw::DispatchCommand::Dispatch(count));
+    }
+
+    pub fn dispatch_indirect(&mut self, job: &str, buffer: &str, offset: b::Offset) {
+        let command = raw::DispatchCommand::DispatchIndirect {
+            buffer: buffer.to_string(),
+            offset,
+        };
+        self.record_dispatch(job, command);
+    }
+
+    pub fn add_submission(&mut self, name: &str, submission: raw::Submission) {
+        for job in &submission.jobs {
+            assert!(self.scene.jobs.contains_key(job), "Missing job: {}", job);
+        }
+        let old = self.scene.submissions.insert(name.to_string(), submission);
+        assert!(old.is_none(), "Duplicate submission: {}", name);
+    }
+
+    /// Writes the scene into "scenes" and the data files into "data"
+    /// of the given work directory. Returns the path of the scene.
+    pub fn save(&self, work_path: &Path) -> io::Result<PathBuf> {
+        let data_path = work_path.join("data");
+        fs::create_dir_all(&data_path)?;
+        for (file_name, contents) in &self.data {
+            File::create(data_path.join(file_name))?.write_all(contents)?;
+        }
+
+        let scene_path = work_path
+            .join("scenes")
+            .join(&self.name)
+            .with_extension("ron");
+        fs::create_dir_all(scene_path.parent().unwrap())?;
+        let text = ron::ser::to_string_pretty(&self.scene, ron::ser::PrettyConfig::new())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        File::create(&scene_path)?.write_all(text.as_bytes())?;
+        info!("saved capture {:?}", scene_path);
+        Ok(scene_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::{format::Format, pso};
+
+    #[test]
+    fn round_trip() {
+        let mut capture = Capture::new("capture-test");
+        // Two levels of 4x4 blocks: 2x1 blocks, then a single one.
+        let kind = i::Kind::D2(8, 4, 1, 1);
+        let contents = (0..24).collect::<Vec<u8>>();
+        capture.add_image(
+            "image",
+            kind,
+            2,
+            Format::Bc1RgbaUnorm,
+            i::Usage::TRANSFER_DST | i::Usage::SAMPLED,
+            i::ViewCapabilities::empty(),
+            Some(&contents),
+        );
+        let attachment = pass::Attachment {
+            format: Some(Format::Rgba8Unorm),
+            samples: 1,
+            ops: pass::AttachmentOps::INIT,
+            stencil_ops: pass::AttachmentOps::DONT_CARE,
+            layouts: i::Layout::General..i::Layout::General,
+        };
+        let subpass = pass::SubpassDesc {
+            colors: &[(0, i::Layout::General)],
+            depth_stencil: None,
+            inputs: &[],
+            resolves: &[],
+            preserves: &[],
+        };
+        let dependency = pass::SubpassDependency {
+            passes: None..Some(0),
+            stages: pso::PipelineStage::TOP_OF_PIPE..pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            accesses: i::Access::empty()..i::Access::COLOR_ATTACHMENT_WRITE,
+            flags: hal::memory::Dependencies::empty(),
+        };
+        capture.create_render_pass("pass", Some(attachment), Some(subpass), Some(dependency));
+
+        let work_path = std::env::temp_dir().join("warden-capture-test");
+        let scene_path = capture.save(&work_path).unwrap();
+        let text = fs::read_to_string(&scene_path).unwrap();
+        let scene: raw::Scene = ron::de::from_str(&text).unwrap();
+
+        match scene.resources["image"] {
+            raw::Resource::Image {
+                num_levels,
+                ref data,
+                ..
+            } => {
+                assert_eq!(num_levels, 2);
+                assert_eq!(
+                    raw::image_level_data_size(kind, Format::Bc1RgbaUnorm, 0),
+                    16
+                );
+                assert_eq!(raw::image_level_data_size(kind, Format::Bc1RgbaUnorm, 1), 8);
+                assert_eq!(
+                    fs::read(work_path.join("data").join(data)).unwrap(),
+                    contents
+                );
+            }
+            ref other => panic!("Unexpected image: {:?}", other),
+        }
+        match scene.resources["pass"] {
+            raw::Resource::RenderPass {
+                ref attachments,
+                ref subpasses,
+                ref dependencies,
+            } => {
+                let (ref subpass_name, ref subpass) = subpasses[0];
+                assert!(attachments.contains_key(&subpass.colors[0].0));
+                assert_eq!(dependencies[0].passes, String::new()..subpass_name.clone());
+            }
+            ref other => panic!("Unexpected render pass: {:?}", other),
+        }
+        // Saving the parsed scene again gives back the same file,
+        // up to the order of the maps.
+        let again = ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::new()).unwrap();
+        let sorted_lines = |text: &str| {
+            let mut lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+            lines.sort();
+            lines
+        };
+        assert_eq!(sorted_lines(&again), sorted_lines(&text));
+
+        fs::remove_dir_all(&work_path).unwrap();
+    }
+
+    #[test]
+    fn hooks() {
+        let mut capture = Capture::new("capture-hooks-test");
+        let binding = |binding, ty, count| pso::DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stage_flags: pso::ShaderStageFlags::COMPUTE,
+            immutable_samplers: false,
+        };
+        let storage = pso::DescriptorType::Buffer {
+            ty: pso::BufferDescriptorType::Storage { read_only: false },
+            format: pso::BufferDescriptorFormat::Structured {
+                dynamic_offset: true,
+            },
+        };
+        let sampled = pso::DescriptorType::Image {
+            ty: pso::ImageDescriptorType::Sampled {
+                with_sampler: false,
+            },
+        };
+        capture.create_descriptor_set_layout(
+            "set-layout",
+            vec![binding(1, sampled, 1), binding(0, storage, 2)],
+            None,
+        );
+        capture.create_descriptor_pool("pool", 1, None);
+        capture.allocate_descriptor_set("set", "pool", "set-layout");
+        // The last buffer and the image spill over onto the next bindings.
+        capture.write_descriptor_set(
+            "set",
+            0,
+            1,
+            vec![
+                Descriptor::Buffer("buffer", b::SubRange::WHOLE),
+                Descriptor::Image("view", i::Layout::General),
+            ],
+        );
+        match scene_resource(&capture, "set") {
+            raw::Resource::DescriptorSet { ref writes, .. } => {
+                let positions = writes
+                    .iter()
+                    .map(|w| (w.binding, w.array_offset))
+                    .collect::<Vec<_>>();
+                assert_eq!(positions, [(0, 1), (1, 0)]);
+            }
+            other => panic!("Unexpected descriptor set: {:?}", other),
+        }
+
+        capture.bind_compute_pipeline("compute", "pipeline");
+        capture.bind_compute_descriptor_sets("compute", 0, Some("set"), vec![256, 512]);
+        capture.push_compute_constants("compute", 0, &[1, 2]);
+        capture.dispatch("compute", [4, 1, 1]);
+        match capture.scene().jobs["compute"] {
+            raw::Job::Compute {
+                ref pipeline,
+                ref descriptor_sets,
+                ref dynamic_offsets,
+                ref commands,
+                dispatch,
+                ..
+            } => {
+                assert_eq!(pipeline, "pipeline");
+                assert_eq!(descriptor_sets, &["set".to_string()]);
+                assert_eq!(dynamic_offsets, &[256, 512]);
+                assert_eq!(commands.len(), 2);
+                assert_eq!(dispatch, [0; 3]);
+            }
+            ref other => panic!("Unexpected compute job: {:?}", other),
+        }
+
+        let attachment = |format| pass::Attachment {
+            format: Some(format),
+            samples: 1,
+            ops: pass::AttachmentOps::INIT,
+            stencil_ops: pass::AttachmentOps::DONT_CARE,
+            layouts: i::Layout::General..i::Layout::General,
+        };
+        let subpass = pass::SubpassDesc {
+            colors: &[(0, i::Layout::General)],
+            depth_stencil: Some(&(1, i::Layout::General)),
+            inputs: &[],
+            resolves: &[],
+            preserves: &[],
+        };
+        capture.create_render_pass(
+            "pass",
+            vec![attachment(Format::Rgba8Uint), attachment(Format::D32Sfloat)],
+            vec![subpass.clone(), subpass],
+            None,
+        );
+        capture.begin_render_pass(
+            "graphics",
+            "pass",
+            "framebuffer",
+            vec![
+                (
+                    "color",
+                    c::ClearValue {
+                        color: c::ClearColor { uint32: [1; 4] },
+                    },
+                ),
+                (
+                    "depth",
+                    c::ClearValue {
+                        depth_stencil: c::ClearDepthStencil {
+                            depth: 1.0,
+                            stencil: 0,
+                        },
+                    },
+                ),
+            ],
+        );
+        capture.bind_graphics_pipeline("graphics", "pipeline");
+        capture.next_subpass("graphics");
+        capture.draw("graphics", 0..3, 0..1);
+        capture.end_render_pass("graphics");
+        match capture.scene().jobs["graphics"] {
+            raw::Job::Graphics {
+                ref attachments,
+                pass: (_, ref passes),
+                ..
+            } => {
+                match attachments["0"].clear_value {
+                    raw::ClearValue::Color(raw::ClearColor::Uint(value)) => {
+                        assert_eq!(value, [1; 4])
+                    }
+                    ref other => panic!("Unexpected color clear value: {:?}", other),
+                }
+                match attachments["1"].clear_value {
+                    raw::ClearValue::DepthStencil(value) => assert_eq!(value.depth, 1.0),
+                    ref other => panic!("Unexpected depth clear value: {:?}", other),
+                }
+                assert_eq!(passes["0"].commands.len(), 1);
+                assert_eq!(passes["1"].commands.len(), 1);
+            }
+            ref other => panic!("Unexpected graphics job: {:?}", other),
+        }
+    }
+
+    fn scene_resource<'a>(capture: &'a Capture, name: &str) -> &'a raw::Resource {
+        &capture.scene().resources[name]
+    }
+}
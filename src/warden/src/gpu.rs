@@ -51,7 +51,7 @@ pub struct Buffer<B: hal::Backend> {
 }
 
 impl<B: hal::Backend> Buffer<B> {
-    fn _barrier_to(&self, access: b::Access) -> memory::Barrier<B> {
+    fn barrier_to(&self, access: b::Access) -> memory::Barrier<B> {
         memory::Barrier::whole_buffer(&self.handle, self.stable_state..access)
     }
     fn barrier_from(&self, access: b::Access) -> memory::Barrier<B> {
@@ -153,26 +153,224 @@ pub struct Resources<B: hal::Backend> {
     pub pipeline_layouts: HashMap<String, B::PipelineLayout>,
     pub graphics_pipelines: HashMap<String, B::GraphicsPipeline>,
     pub compute_pipelines: HashMap<String, (String, B::ComputePipeline)>,
+    pub events: HashMap<String, B::Event>,
+    pub semaphores: HashMap<String, B::Semaphore>,
+    pub buffer_views: HashMap<String, B::BufferView>,
+}
+
+impl<B: hal::Backend> Resources<B> {
+    fn descriptors(&self, range: &raw::DescriptorRange) -> Vec<pso::Descriptor<B>> {
+        let buffer = |s: &String| {
+            &self
+                .buffers
+                .get(s)
+                .expect(&format!("Missing buffer: {}", s))
+                .handle
+        };
+        let image_view = |s: &String| {
+            &self
+                .image_views
+                .get(s)
+                .expect(&format!("Missing image view: {}", s))
+                .handle
+        };
+        let sampler = |s: &String| {
+            self.samplers
+                .get(s)
+                .expect(&format!("Missing sampler: {}", s))
+        };
+        match *range {
+            raw::DescriptorRange::Buffers(ref names) => names
+                .iter()
+                .map(|s| pso::Descriptor::Buffer(buffer(s), b::SubRange::WHOLE))
+                .collect(),
+            raw::DescriptorRange::BufferRanges(ref names_and_ranges) => names_and_ranges
+                .iter()
+                .map(|&(ref s, ref sub)| pso::Descriptor::Buffer(buffer(s), sub.clone()))
+                .collect(),
+            raw::DescriptorRange::TexelBuffers(ref names) => names
+                .iter()
+                .map(|s| {
+                    pso::Descriptor::TexelBuffer(
+                        self.buffer_views
+                            .get(s)
+                            .expect(&format!("Missing buffer view: {}", s)),
+                    )
+                })
+                .collect(),
+            raw::DescriptorRange::Images(ref names_and_layouts) => names_and_layouts
+                .iter()
+                .map(|&(ref s, layout)| pso::Descriptor::Image(image_view(s), layout))
+                .collect(),
+            raw::DescriptorRange::Samplers(ref names) => names
+                .iter()
+                .map(|s| pso::Descriptor::Sampler(sampler(s)))
+                .collect(),
+            raw::DescriptorRange::CombinedImageSamplers(ref list) => list
+                .iter()
+                .map(|&(ref view, layout, ref samp)| {
+                    pso::Descriptor::CombinedImageSampler(image_view(view), layout, sampler(samp))
+                })
+                .collect(),
+        }
+    }
+
+    fn barrier<'a>(
+        &'a self,
+        raw: &raw::Barrier,
+        queue_families: &HashMap<String, queue::QueueFamilyId>,
+    ) -> memory::Barrier<'a, B> {
+        let family_range = |names: &Option<Range<String>>| {
+            names.as_ref().map(|r| {
+                let family = |name: &String| {
+                    *queue_families
+                        .get(name)
+                        .expect(&format!("Missing queue: {}", name))
+                };
+                family(&r.start)..family(&r.end)
+            })
+        };
+        match *raw {
+            raw::Barrier::AllBuffers(ref states) => memory::Barrier::AllBuffers(states.clone()),
+            raw::Barrier::AllImages(ref states) => memory::Barrier::AllImages(states.clone()),
+            raw::Barrier::Buffer {
+                ref buffer,
+                ref states,
+                ref range,
+                ref families,
+            } => memory::Barrier::Buffer {
+                states: states.clone(),
+                target: &self
+                    .buffers
+                    .get(buffer)
+                    .expect(&format!("Missing buffer: {}", buffer))
+                    .handle,
+                range: range.clone(),
+                families: family_range(families),
+            },
+            raw::Barrier::Image {
+                ref image,
+                ref states,
+                ref range,
+                ref families,
+            } => memory::Barrier::Image {
+                states: states.clone(),
+                target: &self
+                    .images
+                    .get(image)
+                    .expect(&format!("Missing image: {}", image))
+                    .handle,
+                range: range.clone(),
+                families: family_range(families),
+            },
+        }
+    }
 }
 
 pub struct Job<B: hal::Backend> {
     submission: B::CommandBuffer,
+    queue: String,
 }
 
 pub struct Scene<B: hal::Backend> {
     pub resources: Resources<B>,
     pub jobs: HashMap<String, Job<B>>,
+    submissions: HashMap<String, raw::Submission>,
     init_submit: B::CommandBuffer,
     finish_submit: B::CommandBuffer,
     device: B::Device,
-    queue_group: queue::QueueGroup<B>,
-    command_pool: Option<B::CommandPool>,
+    /// Queue groups, with the default queue being the first one of the first group.
+    queue_groups: Vec<queue::QueueGroup<B>>,
+    /// Queue name to the group and queue indices.
+    queues: HashMap<String, (usize, usize)>,
+    /// Command pools, one per queue group.
+    command_pools: Vec<B::CommandPool>,
     query_pool: Option<B::QueryPool>,
     upload_buffers: HashMap<String, (B::Buffer, B::Memory)>,
     download_types: Vec<hal::MemoryTypeId>,
+    device_types: Vec<hal::MemoryTypeId>,
     limits: hal::Limits,
 }
 
+fn specialization(
+    constants: &[(u32, raw::SpecializationConstant)],
+) -> pso::Specialization<'static> {
+    let mut data = Vec::with_capacity(constants.len() * 4);
+    let constants = constants
+        .iter()
+        .map(|&(id, ref constant)| {
+            let offset = data.len() as u16;
+            data.extend_from_slice(&constant.to_bytes());
+            pso::SpecializationConstant {
+                id,
+                range: offset..data.len() as u16,
+            }
+        })
+        .collect::<Vec<_>>();
+    pso::Specialization {
+        constants: constants.into(),
+        data: data.into(),
+    }
+}
+
+/// Parses a WGSL or GLSL shader with naga. The stage of GLSL shaders
+/// is derived from the file extension, and their entry point is `main`.
+/// GLSL only goes through naga when the `glsl-to-spirv` feature is disabled.
+fn parse_shader(code: &str, extension: &str) -> Result<naga::Module, String> {
+    let stage = match extension {
+        "wgsl" => return naga::front::wgsl::parse_str(code).map_err(|e| format!("{:?}", e)),
+        "vert" => naga::ShaderStage::Vertex,
+        "frag" => naga::ShaderStage::Fragment,
+        "comp" => naga::ShaderStage::Compute,
+        other => return Err(format!("Unknown shader extension: {}", other)),
+    };
+    let options = naga::front::glsl::Options {
+        entry_points: iter::once(("main".to_string(), stage)).collect(),
+        defines: Default::default(),
+    };
+    naga::front::glsl::parse_str(code, &options).map_err(|e| format!("{:?}", e))
+}
+
+/// Creates a shader module out of naga IR, going through SPIR-V
+/// for the backends that don't accept naga modules.
+unsafe fn create_naga_shader_module<B: hal::Backend>(
+    device: &B::Device,
+    module: naga::Module,
+) -> B::ShaderModule {
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all())
+        .validate(&module)
+        .expect("Shader validation failure");
+    match device.create_shader_module_from_naga(hal::device::NagaShader { module, info }) {
+        Ok(module) => module,
+        Err((hal::device::ShaderError::Unsupported, shader)) => {
+            use naga::back::spv;
+            let capabilities = [
+                spv::Capability::Shader,
+                spv::Capability::Matrix,
+                spv::Capability::InputAttachment,
+                spv::Capability::Sampled1D,
+                spv::Capability::Image1D,
+                spv::Capability::SampledBuffer,
+                spv::Capability::ImageBuffer,
+                spv::Capability::ImageQuery,
+                spv::Capability::DerivativeControl,
+            ]
+            .iter()
+            .cloned()
+            .collect();
+            let options = spv::Options {
+                lang_version: (1, 0),
+                flags: spv::WriterFlags::empty(),
+                capabilities,
+            };
+            let spirv = spv::write_vec(&shader.module, &shader.info, &options)
+                .expect("SPIR-V generation failure");
+            device.create_shader_module(&spirv).unwrap()
+        }
+        Err((e, _)) => panic!("Shader module creation failure: {:?}", e),
+    }
+}
+
 fn align(x: u64, y: u64) -> u64 {
     if x > 0 && y > 0 {
         ((x - 1) | (y - 1)) + 1
@@ -192,15 +390,72 @@ impl<B: hal::Backend> Scene<B> {
         let memory_types = adapter.physical_device.memory_properties().memory_types;
         let limits = adapter.physical_device.properties().limits;
 
+        // assign the named queues to families, starting with the default one
+        let mut family_queues = vec![(0, 1)];
+        let mut queues = HashMap::new();
+        queues.insert(String::new(), (0, 0));
+        // sort the names, so that the assignment is the same on every run
+        let mut raw_queues = raw.queues.iter().collect::<Vec<_>>();
+        raw_queues.sort_by_key(|&(name, _)| name);
+        for (name, &ty) in raw_queues {
+            let supports = |family_ty: queue::QueueType| match ty {
+                queue::QueueType::General => family_ty == queue::QueueType::General,
+                queue::QueueType::Graphics => family_ty.supports_graphics(),
+                queue::QueueType::Compute => family_ty.supports_compute(),
+                queue::QueueType::Transfer => family_ty.supports_transfer(),
+            };
+            let family_index = adapter
+                .queue_families
+                .iter()
+                .position(|qf| qf.queue_type() == ty)
+                .or_else(|| {
+                    adapter
+                        .queue_families
+                        .iter()
+                        .position(|qf| supports(qf.queue_type()))
+                })
+                .expect(&format!("No queue family for {:?} queue: {}", ty, name));
+            let group = match family_queues.iter().position(|&(fi, _)| fi == family_index) {
+                Some(group) => group,
+                None => {
+                    family_queues.push((family_index, 0));
+                    family_queues.len() - 1
+                }
+            };
+            let max_queues = adapter.queue_families[family_index].max_queues();
+            let count = &mut family_queues[group].1;
+            let index = if *count < max_queues {
+                *count += 1;
+                *count - 1
+            } else {
+                warn!("Queue {} is shared with other queues of the family", name);
+                max_queues - 1
+            };
+            queues.insert(name.clone(), (group, index));
+        }
+        info!("queue families: {:?}", family_queues);
+
         // initialize graphics
-        let mut gpu = unsafe {
-            adapter
-                .physical_device
-                .open(&[(&adapter.queue_families[0], &[1.0])], featues)
-                .unwrap()
-        };
+        let priorities = vec![1.0; family_queues.iter().map(|&(_, count)| count).max().unwrap()];
+        let families = family_queues
+            .iter()
+            .map(|&(fi, count)| (&adapter.queue_families[fi], &priorities[..count]))
+            .collect::<Vec<_>>();
+        let gpu = unsafe { adapter.physical_device.open(&families, featues).unwrap() };
         let device = gpu.device;
-        let queue_group = gpu.queue_groups.pop().unwrap();
+        let mut opened_groups = gpu.queue_groups;
+        let queue_groups = family_queues
+            .iter()
+            .map(|&(fi, _)| {
+                let id = adapter.queue_families[fi].id();
+                let pos = opened_groups.iter().position(|qg| qg.family == id).unwrap();
+                opened_groups.swap_remove(pos)
+            })
+            .collect::<Vec<_>>();
+        let queue_families = queues
+            .iter()
+            .map(|(name, &(group, _))| (name.clone(), queue_groups[group].family))
+            .collect::<HashMap<_, _>>();
 
         let upload_types: Vec<hal::MemoryTypeId> = memory_types
             .iter()
@@ -227,17 +482,28 @@ impl<B: hal::Backend> Scene<B> {
                 }
             })
             .collect();
+        let device_types: Vec<hal::MemoryTypeId> = memory_types
+            .iter()
+            .enumerate()
+            .filter_map(|(i, mt)| {
+                if mt.properties.contains(memory::Properties::DEVICE_LOCAL) {
+                    Some(i.into())
+                } else {
+                    None
+                }
+            })
+            .collect();
         info!("upload memory: {:?}", upload_types);
         info!("download memory: {:?}", &download_types);
 
-        let mut command_pool = unsafe {
-            device
-                .create_command_pool(
-                    queue_group.family,
-                    hal::pool::CommandPoolCreateFlags::empty(),
-                )
-                .unwrap()
-        };
+        let mut command_pools = queue_groups
+            .iter()
+            .map(|group| unsafe {
+                device
+                    .create_command_pool(group.family, hal::pool::CommandPoolCreateFlags::empty())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
         let query_pool = unsafe { device.create_query_pool(query::Type::Timestamp, 2) };
 
         // create resources
@@ -255,11 +521,14 @@ impl<B: hal::Backend> Scene<B> {
             pipeline_layouts: HashMap::new(),
             graphics_pipelines: HashMap::new(),
             compute_pipelines: HashMap::new(),
+            events: HashMap::new(),
+            semaphores: HashMap::new(),
+            buffer_views: HashMap::new(),
         };
         let mut upload_buffers = HashMap::new();
         let (mut finish_cmd, mut init_cmd);
         unsafe {
-            finish_cmd = command_pool.allocate_one(c::Level::Primary);
+            finish_cmd = command_pools[0].allocate_one(c::Level::Primary);
             finish_cmd.begin_primary(c::CommandBufferFlags::empty());
             if let Ok(ref pool) = query_pool {
                 finish_cmd.write_timestamp(
@@ -271,7 +540,7 @@ impl<B: hal::Backend> Scene<B> {
             finish_cmd.finish();
         }
         unsafe {
-            init_cmd = command_pool.allocate_one(c::Level::Primary);
+            init_cmd = command_pools[0].allocate_one(c::Level::Primary);
             init_cmd.begin_primary(c::CommandBufferFlags::empty());
             init_cmd.begin_debug_marker("_init", 0x0000FF00);
             if let Ok(ref pool) = query_pool {
@@ -282,7 +551,7 @@ impl<B: hal::Backend> Scene<B> {
                 );
             }
         }
-        // Pass[1]: images, samplers, buffers, passes, descriptor set layouts/pools
+        // Pass[1]: images, samplers, buffers, passes, descriptor pools
         for (name, resource) in &raw.resources {
             match *resource {
                 raw::Resource::Buffer {
@@ -482,24 +751,46 @@ impl<B: hal::Backend> Scene<B> {
                         }
                         (access, layout)
                     } else {
-                        // calculate required sizes
-                        let extent = kind.extent();
                         assert_eq!(kind.num_samples(), 1);
-
-                        let base_format = format.base_format();
-                        let format_desc = base_format.0.desc();
-                        let (block_width, block_height) = format_desc.dim;
-
-                        // Width and height need to be multiple of the block dimensions.
-                        let w = align(extent.width as _, block_width as _);
-                        let h = align(extent.height as _, block_height as _);
-                        let d = extent.depth;
-
-                        let width_bytes = (format_desc.bits as u64 * w) / (8 * block_width as u64);
-                        let row_pitch =
-                            align(width_bytes, limits.optimal_buffer_copy_pitch_alignment);
-                        let upload_size =
-                            (row_pitch as u64 * h as u64 * d as u64) / block_height as u64;
+                        let format_desc = format.base_format().0.desc();
+                        let (block_width, block_height) =
+                            (format_desc.dim.0 as u64, format_desc.dim.1 as u64);
+                        let block_bytes = format_desc.bits as u64 / 8;
+                        let num_layers = kind.num_layers();
+
+                        // lay out the levels in the upload buffer, with aligned rows of blocks
+                        let mut upload_size = 0;
+                        let levels = (0..num_levels)
+                            .map(|level| {
+                                let extent = kind.level_extent(level);
+                                // Width and height are rounded up to whole blocks.
+                                let width_blocks =
+                                    (extent.width as u64 + block_width - 1) / block_width;
+                                let height_blocks =
+                                    (extent.height as u64 + block_height - 1) / block_height;
+                                let row_bytes = width_blocks * block_bytes;
+                                let row_pitch =
+                                    align(row_bytes, limits.optimal_buffer_copy_pitch_alignment);
+                                let num_rows =
+                                    height_blocks * extent.depth as u64 * num_layers as u64;
+                                let buffer_offset =
+                                    align(upload_size, limits.optimal_buffer_copy_offset_alignment);
+                                upload_size = buffer_offset + row_pitch * num_rows;
+                                let copy = c::BufferImageCopy {
+                                    buffer_offset,
+                                    buffer_width: (row_pitch / block_bytes * block_width) as u32,
+                                    buffer_height: (height_blocks * block_height) as u32,
+                                    image_layers: i::SubresourceLayers {
+                                        aspects: f::Aspects::COLOR,
+                                        level,
+                                        layers: 0..num_layers,
+                                    },
+                                    image_offset: i::Offset::ZERO,
+                                    image_extent: extent,
+                                };
+                                (copy, row_pitch, row_bytes, num_rows)
+                            })
+                            .collect::<Vec<_>>();
                         // create upload buffer
                         let mut upload_buffer = unsafe {
                             device.create_buffer(
@@ -525,12 +816,15 @@ impl<B: hal::Backend> Scene<B> {
                             let mapping = device
                                 .map_memory(&mut upload_memory, memory::Segment::ALL)
                                 .unwrap();
-                            for y in 0..(h as usize * d as usize) {
-                                let slice = slice::from_raw_parts_mut(
-                                    mapping.offset(y as isize * row_pitch as isize),
-                                    width_bytes as usize,
-                                );
-                                file.read_exact(slice).unwrap();
+                            for &(ref copy, row_pitch, row_bytes, num_rows) in &levels {
+                                for row in 0..num_rows {
+                                    let offset = copy.buffer_offset + row * row_pitch;
+                                    let slice = slice::from_raw_parts_mut(
+                                        mapping.offset(offset as isize),
+                                        row_bytes as usize,
+                                    );
+                                    file.read_exact(slice).unwrap();
+                                }
                             }
                             device.unmap_memory(&mut upload_memory);
                         }
@@ -555,25 +849,12 @@ impl<B: hal::Backend> Scene<B> {
                             );
                         }
 
-                        let buffer_width = (row_pitch as u32 * 8) / format_desc.bits as u32;
-                        let copy = c::BufferImageCopy {
-                            buffer_offset: 0,
-                            buffer_width,
-                            buffer_height: h as u32,
-                            image_layers: i::SubresourceLayers {
-                                aspects: f::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            image_offset: i::Offset::ZERO,
-                            image_extent: extent,
-                        };
                         unsafe {
                             init_cmd.copy_buffer_to_image(
                                 &upload_buffer,
                                 &image,
                                 i::Layout::TransferDstOptimal,
-                                iter::once(copy),
+                                levels.into_iter().map(|(copy, ..)| copy),
                             );
                         }
                         let post_barrier = memory::Barrier::Image {
@@ -631,16 +912,16 @@ impl<B: hal::Backend> Scene<B> {
                             None
                         } else {
                             subpasses
-                                .keys()
-                                .position(|sp| s == sp)
+                                .iter()
+                                .position(|&(ref sp, _)| s == sp)
                                 .map(|id| id as hal::pass::SubpassId)
                         }
                     };
 
                     let raw_atts = attachments.values().cloned();
                     let temp = subpasses
-                        .values()
-                        .map(|sp| {
+                        .iter()
+                        .map(|&(_, ref sp)| {
                             let colors = sp.colors.iter().map(&att_ref).collect::<Vec<_>>();
                             let ds = sp.depth_stencil.as_ref().map(&att_ref);
                             let inputs = sp.inputs.iter().map(&att_ref).collect::<Vec<_>>();
@@ -674,7 +955,10 @@ impl<B: hal::Backend> Scene<B> {
                             .iter()
                             .map(|(key, at)| (key.clone(), at.layouts.clone()))
                             .collect(),
-                        subpasses: subpasses.keys().cloned().collect(),
+                        subpasses: subpasses
+                            .iter()
+                            .map(|&(ref name, _)| name.clone())
+                            .collect(),
                     };
                     resources.render_passes.insert(name.clone(), rp);
                 }
@@ -686,8 +970,9 @@ impl<B: hal::Backend> Scene<B> {
                         glsl_to_spirv::compile(&code, ty).unwrap()
                     }
                     let full_path = data_path.join(local_path);
-                    let base_file = File::open(&full_path).unwrap();
-                    let file = match &*full_path.extension().unwrap().to_string_lossy() {
+                    let mut base_file = File::open(&full_path)
+                        .expect(&format!("Missing shader file: {:?}", full_path));
+                    let spirv_file = match &*full_path.extension().unwrap().to_string_lossy() {
                         "spirv" => base_file,
                         #[cfg(feature = "glsl-to-spirv")]
                         "vert" => transpile(base_file, glsl_to_spirv::ShaderType::Vertex),
@@ -695,28 +980,20 @@ impl<B: hal::Backend> Scene<B> {
                         "frag" => transpile(base_file, glsl_to_spirv::ShaderType::Fragment),
                         #[cfg(feature = "glsl-to-spirv")]
                         "comp" => transpile(base_file, glsl_to_spirv::ShaderType::Compute),
-                        other => panic!("Unknown shader extension: {}", other),
+                        other => {
+                            let mut code = String::new();
+                            base_file.read_to_string(&mut code).unwrap();
+                            let module = parse_shader(&code, other)
+                                .expect(&format!("Failed to parse shader: {:?}", full_path));
+                            let module = unsafe { create_naga_shader_module(&device, module) };
+                            resources.shaders.insert(name.clone(), module);
+                            continue;
+                        }
                     };
-                    let spirv = auxil::read_spirv(file).unwrap();
+                    let spirv = auxil::read_spirv(spirv_file).unwrap();
                     let module = unsafe { device.create_shader_module(&spirv) }.unwrap();
                     resources.shaders.insert(name.clone(), module);
                 }
-                raw::Resource::DescriptorSetLayout {
-                    ref bindings,
-                    ref immutable_samplers,
-                } => {
-                    assert!(immutable_samplers.is_empty()); //TODO! requires changing the order,
-                    assert!(!bindings.is_empty());
-                    // since samples are expect to be all read by this point
-                    let layout = unsafe {
-                        device.create_descriptor_set_layout(bindings.iter().cloned(), iter::empty())
-                    }
-                    .expect("Descriptor set layout creation failure!");
-                    let binding_indices = bindings.iter().map(|dsb| dsb.binding).collect();
-                    resources
-                        .desc_set_layouts
-                        .insert(name.clone(), (binding_indices, layout));
-                }
                 raw::Resource::DescriptorPool {
                     capacity,
                     ref ranges,
@@ -733,13 +1010,37 @@ impl<B: hal::Backend> Scene<B> {
                     .expect("Descriptor pool creation failure!");
                     resources.desc_pools.insert(name.clone(), pool);
                 }
+                raw::Resource::Event => {
+                    let event = device.create_event().expect("Event creation failure!");
+                    resources.events.insert(name.clone(), event);
+                }
+                raw::Resource::Semaphore => {
+                    let semaphore = device
+                        .create_semaphore()
+                        .expect("Semaphore creation failure!");
+                    resources.semaphores.insert(name.clone(), semaphore);
+                }
                 _ => {}
             }
         }
 
-        // Pass[2]: image & buffer views, pipeline layouts
+        // Pass[2]: image & buffer views, descriptor set layouts
         for (name, resource) in &raw.resources {
             match *resource {
+                raw::Resource::BufferView {
+                    ref buffer,
+                    format,
+                    ref range,
+                } => {
+                    let buf = &resources
+                        .buffers
+                        .get(buffer)
+                        .expect(&format!("Missing buffer: {}", buffer))
+                        .handle;
+                    let view = unsafe { device.create_buffer_view(buf, format, range.clone()) }
+                        .expect("Buffer view creation failure!");
+                    resources.buffer_views.insert(name.clone(), view);
+                }
                 raw::Resource::ImageView {
                     ref image,
                     kind,
@@ -760,6 +1061,34 @@ impl<B: hal::Backend> Scene<B> {
                         },
                     );
                 }
+                raw::Resource::DescriptorSetLayout {
+                    ref bindings,
+                    ref immutable_samplers,
+                } => {
+                    assert!(!bindings.is_empty());
+                    // samplers are all created by this point
+                    let samplers = immutable_samplers.iter().map(|s| {
+                        resources
+                            .samplers
+                            .get(s)
+                            .expect(&format!("Missing immutable sampler: {}", s))
+                    });
+                    let layout = unsafe {
+                        device.create_descriptor_set_layout(bindings.iter().cloned(), samplers)
+                    }
+                    .expect("Descriptor set layout creation failure!");
+                    let binding_indices = bindings.iter().map(|dsb| dsb.binding).collect();
+                    resources
+                        .desc_set_layouts
+                        .insert(name.clone(), (binding_indices, layout));
+                }
+                _ => {}
+            }
+        }
+
+        // Pass[3]: pipeline layouts
+        for (name, resource) in &raw.resources {
+            match *resource {
                 raw::Resource::PipelineLayout {
                     ref set_layouts,
                     ref push_constant_ranges,
@@ -782,13 +1111,14 @@ impl<B: hal::Backend> Scene<B> {
             }
         }
 
-        // Pass[3]: descriptor sets, framebuffers and pipelines
+        // Pass[4]: descriptor sets, framebuffers and pipelines
         for (name, resource) in &raw.resources {
             match *resource {
                 raw::Resource::DescriptorSet {
                     ref pool,
                     ref layout,
                     ref data,
+                    ref writes,
                 } => {
                     // create a descriptor set
                     let (ref binding_indices, ref set_layout) = resources.desc_set_layouts[layout];
@@ -805,50 +1135,33 @@ impl<B: hal::Backend> Scene<B> {
                     ));
                     // fill it up
                     let mut views = Vec::new();
-                    for (&binding, range) in binding_indices.iter().zip(data) {
+                    let ranges = binding_indices
+                        .iter()
+                        .zip(data)
+                        .map(|(&binding, range)| (binding, 0, range))
+                        .chain(
+                            writes
+                                .iter()
+                                .map(|w| (w.binding, w.array_offset, &w.descriptors)),
+                        );
+                    for (binding, array_offset, range) in ranges {
+                        match *range {
+                            raw::DescriptorRange::Images(ref names_and_layouts) => {
+                                views.extend_from_slice(names_and_layouts);
+                            }
+                            raw::DescriptorRange::CombinedImageSamplers(ref list) => {
+                                views.extend(
+                                    list.iter()
+                                        .map(|&(ref view, layout, _)| (view.clone(), layout)),
+                                );
+                            }
+                            _ => {}
+                        }
                         let write = hal::pso::DescriptorSetWrite {
                             set: &mut desc_set,
                             binding,
-                            array_offset: 0,
-                            descriptors: match *range {
-                                raw::DescriptorRange::Buffers(ref names) => names
-                                    .iter()
-                                    .map(|s| {
-                                        let buf = resources
-                                            .buffers
-                                            .get(s)
-                                            .expect(&format!("Missing buffer: {}", s));
-                                        hal::pso::Descriptor::Buffer(
-                                            &buf.handle,
-                                            b::SubRange::WHOLE,
-                                        )
-                                    })
-                                    .collect::<Vec<_>>(),
-                                raw::DescriptorRange::Images(ref names_and_layouts) => {
-                                    views.extend_from_slice(names_and_layouts);
-                                    names_and_layouts
-                                        .iter()
-                                        .map(|&(ref s, layout)| {
-                                            let view = resources
-                                                .image_views
-                                                .get(s)
-                                                .expect(&format!("Missing image view: {}", s));
-                                            hal::pso::Descriptor::Image(&view.handle, layout)
-                                        })
-                                        .collect::<Vec<_>>()
-                                }
-                                raw::DescriptorRange::Samplers(ref names) => names
-                                    .iter()
-                                    .map(|s| {
-                                        let sampler = resources
-                                            .samplers
-                                            .get(s)
-                                            .expect(&format!("Missing sampler: {}", s));
-                                        hal::pso::Descriptor::Sampler(sampler)
-                                    })
-                                    .collect::<Vec<_>>(),
-                            }
-                            .into_iter(),
+                            array_offset,
+                            descriptors: resources.descriptors(range).into_iter(),
                         };
                         unsafe {
                             device.write_descriptor_set(write);
@@ -893,8 +1206,10 @@ impl<B: hal::Backend> Scene<B> {
                     ref input_assembler,
                     ref blender,
                     depth_stencil,
+                    ref multisampling,
                     ref layout,
                     ref subpass,
+                    specialization: ref constants,
                 } => {
                     let reshaders = &resources.shaders;
                     let entry = |shader: &String| -> Option<pso::EntryPoint<B>> {
@@ -906,7 +1221,7 @@ impl<B: hal::Backend> Scene<B> {
                                 module: reshaders
                                     .get(shader)
                                     .expect(&format!("Missing shader: {}", shader)),
-                                specialization: pso::Specialization::default(),
+                                specialization: specialization(constants),
                             })
                         }
                     };
@@ -931,7 +1246,7 @@ impl<B: hal::Backend> Scene<B> {
                                 module: reshaders
                                     .get(&shaders.vertex)
                                     .expect(&format!("Missing vertex shader: {}", shaders.vertex)),
-                                specialization: pso::Specialization::default(),
+                                specialization: specialization(constants),
                             },
                             tessellation,
                             geometry: entry(&shaders.geometry),
@@ -940,7 +1255,7 @@ impl<B: hal::Backend> Scene<B> {
                         blender: blender.clone(),
                         depth_stencil: depth_stencil.clone(),
                         baked_states: pso::BakedStates::default(), //TODO
-                        multisampling: None,                       // TODO
+                        multisampling: multisampling.clone(),
                         layout: &resources.pipeline_layouts[layout],
                         subpass: hal::pass::Subpass {
                             main_pass: &resources
@@ -959,6 +1274,7 @@ impl<B: hal::Backend> Scene<B> {
                 raw::Resource::ComputePipeline {
                     ref shader,
                     ref layout,
+                    specialization: ref constants,
                 } => {
                     let desc = pso::ComputePipelineDesc {
                         label: None,
@@ -968,7 +1284,7 @@ impl<B: hal::Backend> Scene<B> {
                                 .shaders
                                 .get(shader)
                                 .expect(&format!("Missing compute shader: {}", shader)),
-                            specialization: pso::Specialization::default(),
+                            specialization: specialization(constants),
                         },
                         layout: resources
                             .pipeline_layouts
@@ -995,14 +1311,17 @@ impl<B: hal::Backend> Scene<B> {
         let mut jobs = HashMap::new();
         for (name, job) in &raw.jobs {
             use crate::raw::TransferCommand as Tc;
+            let (group, _) = *queues
+                .get(job.queue())
+                .expect(&format!("Missing queue: {}", job.queue()));
             let mut command_buf;
             unsafe {
-                command_buf = command_pool.allocate_one(c::Level::Primary);
+                command_buf = command_pools[group].allocate_one(c::Level::Primary);
                 command_buf.begin_primary(c::CommandBufferFlags::SIMULTANEOUS_USE);
                 command_buf.begin_debug_marker(name, 0x00FF0000);
             }
             match *job {
-                raw::Job::Transfer { ref commands } => {
+                raw::Job::Transfer { ref commands, .. } => {
                     let mut buffers = HashMap::new();
                     let mut images = HashMap::new();
                     let src_stage =
@@ -1253,19 +1572,33 @@ impl<B: hal::Backend> Scene<B> {
                     ref framebuffer,
                     ref pass,
                     ref attachments,
+                    ..
                 } => unsafe {
-                    // collect all used image descriptors
+                    // collect all used image descriptors and indirect buffers,
+                    // leaving out the attachments that are transitioned by the pass
+                    let attachment_images = attachments
+                        .values()
+                        .map(|info| &resources.image_views[&info.image_view].image)
+                        .collect::<Vec<_>>();
                     let mut all_images = Vec::new();
+                    let mut indirect_buffers = Vec::new();
                     for subpass in pass.1.iter() {
                         for com in subpass.1.commands.iter() {
                             if let raw::DrawCommand::BindDescriptorSets { ref sets, .. } = *com {
                                 for set in sets {
                                     for pair in resources.desc_sets[set].views.iter() {
                                         let view = &resources.image_views[&pair.0];
-                                        all_images.push((view.image.clone(), pair.1));
+                                        if !attachment_images.contains(&&view.image) {
+                                            all_images.push((view.image.clone(), pair.1));
+                                        }
                                     }
                                 }
                             }
+                            for name in com.indirect_buffers() {
+                                if !indirect_buffers.contains(&name) {
+                                    indirect_buffers.push(name);
+                                }
+                            }
                         }
                     }
 
@@ -1277,6 +1610,14 @@ impl<B: hal::Backend> Scene<B> {
                         .render_passes
                         .get(&pass.0)
                         .expect(&format!("Missing render pass: {}", pass.0));
+                    for subpass in pass.1.keys() {
+                        assert!(
+                            rp.subpasses.contains(subpass),
+                            "Missing subpass {} in render pass {}",
+                            subpass,
+                            pass.0
+                        );
+                    }
                     let rect = pso::Rect {
                         x: 0,
                         y: 0,
@@ -1303,6 +1644,18 @@ impl<B: hal::Backend> Scene<B> {
                             resources.images[name].barrier_to(i::Access::SHADER_READ, layout)
                         }),
                     );
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::VERTEX_SHADER | pso::PipelineStage::FRAGMENT_SHADER
+                            ..pso::PipelineStage::DRAW_INDIRECT,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources
+                                .buffers
+                                .get(*name)
+                                .expect(&format!("Missing indirect buffer: {}", name))
+                                .barrier_to(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
                     command_buf.begin_render_pass(
                         &rp.handle,
                         &fb.handle,
@@ -1325,11 +1678,15 @@ impl<B: hal::Backend> Scene<B> {
                         }),
                     );
 
-                    for subpass in &rp.subpasses {
-                        if Some(subpass) != rp.subpasses.first() {
+                    for (index, subpass) in rp.subpasses.iter().enumerate() {
+                        if index != 0 {
                             command_buf.next_subpass(c::SubpassContents::Inline);
                         }
-                        for command in &pass.1[subpass].commands {
+                        let draw_pass = match pass.1.get(subpass) {
+                            Some(draw_pass) => draw_pass,
+                            None => continue,
+                        };
+                        for command in &draw_pass.commands {
                             use crate::raw::DrawCommand as Dc;
                             match *command {
                                 Dc::BindIndexBuffer {
@@ -1370,6 +1727,7 @@ impl<B: hal::Backend> Scene<B> {
                                     ref layout,
                                     first,
                                     ref sets,
+                                    ref dynamic_offsets,
                                 } => {
                                     command_buf.bind_graphics_descriptor_sets(
                                         resources.pipeline_layouts.get(layout).expect(&format!(
@@ -1387,7 +1745,7 @@ impl<B: hal::Backend> Scene<B> {
                                                 ))
                                                 .handle
                                         }),
-                                        iter::empty(),
+                                        dynamic_offsets.iter().cloned(),
                                     );
                                 }
                                 Dc::Draw {
@@ -1407,12 +1765,97 @@ impl<B: hal::Backend> Scene<B> {
                                         instances.clone(),
                                     );
                                 }
+                                Dc::DrawIndirect {
+                                    ref buffer,
+                                    offset,
+                                    draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indirect(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::DrawIndexedIndirect {
+                                    ref buffer,
+                                    offset,
+                                    draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indexed_indirect(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::DrawIndirectCount {
+                                    ref buffer,
+                                    offset,
+                                    ref count_buffer,
+                                    count_buffer_offset,
+                                    max_draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indirect_count(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        &resources.buffers[count_buffer].handle,
+                                        count_buffer_offset,
+                                        max_draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::DrawIndexedIndirectCount {
+                                    ref buffer,
+                                    offset,
+                                    ref count_buffer,
+                                    count_buffer_offset,
+                                    max_draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indexed_indirect_count(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        &resources.buffers[count_buffer].handle,
+                                        count_buffer_offset,
+                                        max_draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::PushConstants {
+                                    ref layout,
+                                    stages,
+                                    offset,
+                                    ref data,
+                                } => {
+                                    command_buf.push_graphics_constants(
+                                        resources.pipeline_layouts.get(layout).expect(&format!(
+                                            "Missing pipeline layout: {}",
+                                            layout
+                                        )),
+                                        stages,
+                                        offset,
+                                        data,
+                                    );
+                                }
                                 Dc::SetViewports(ref viewports) => {
                                     command_buf.set_viewports(0, viewports.iter().cloned());
                                 }
                                 Dc::SetScissors(ref scissors) => {
                                     command_buf.set_scissors(0, scissors.iter().cloned());
                                 }
+                                Dc::SetStencilReference { faces, value } => {
+                                    command_buf.set_stencil_reference(faces, value);
+                                }
+                                Dc::SetBlendConstants(color) => {
+                                    command_buf.set_blend_constants(color);
+                                }
+                                Dc::SetDepthBias(depth_bias) => {
+                                    command_buf.set_depth_bias(depth_bias);
+                                }
                             }
                         }
                     }
@@ -1426,6 +1869,15 @@ impl<B: hal::Backend> Scene<B> {
                             resources.images[name].barrier_from(i::Access::SHADER_READ, layout)
                         }),
                     );
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::DRAW_INDIRECT
+                            ..pso::PipelineStage::VERTEX_SHADER
+                                | pso::PipelineStage::FRAGMENT_SHADER,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers[*name].barrier_from(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
                     command_buf.pipeline_barrier(
                         pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
                             ..pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
@@ -1441,15 +1893,39 @@ impl<B: hal::Backend> Scene<B> {
                 raw::Job::Compute {
                     ref pipeline,
                     ref descriptor_sets,
+                    ref dynamic_offsets,
+                    ref commands,
                     dispatch,
+                    ..
                 } => unsafe {
                     let (ref layout, ref pso) = resources.compute_pipelines[pipeline];
+                    let pipeline_layout = resources
+                        .pipeline_layouts
+                        .get(layout)
+                        .expect(&format!("Missing pipeline layout: {}", layout));
+                    let mut indirect_buffers = Vec::new();
+                    for command in commands {
+                        if let raw::DispatchCommand::DispatchIndirect { ref buffer, .. } = *command
+                        {
+                            if !indirect_buffers.contains(&buffer) {
+                                indirect_buffers.push(buffer);
+                            }
+                        }
+                    }
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::COMPUTE_SHADER..pso::PipelineStage::DRAW_INDIRECT,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources
+                                .buffers
+                                .get(*name)
+                                .expect(&format!("Missing indirect buffer: {}", name))
+                                .barrier_to(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
                     command_buf.bind_compute_pipeline(pso);
                     command_buf.bind_compute_descriptor_sets(
-                        resources
-                            .pipeline_layouts
-                            .get(layout)
-                            .expect(&format!("Missing pipeline layout: {}", layout)),
+                        pipeline_layout,
                         0,
                         descriptor_sets.iter().map(|name| {
                             &resources
@@ -1458,9 +1934,82 @@ impl<B: hal::Backend> Scene<B> {
                                 .expect(&format!("Missing descriptor set: {}", name))
                                 .handle
                         }),
-                        iter::empty(),
+                        dynamic_offsets.iter().cloned(),
                     );
-                    command_buf.dispatch(dispatch);
+                    for command in commands {
+                        match *command {
+                            raw::DispatchCommand::PushConstants { offset, ref data } => {
+                                command_buf.push_compute_constants(pipeline_layout, offset, data);
+                            }
+                            raw::DispatchCommand::Dispatch(count) => {
+                                command_buf.dispatch(count);
+                            }
+                            raw::DispatchCommand::DispatchIndirect { ref buffer, offset } => {
+                                command_buf
+                                    .dispatch_indirect(&resources.buffers[buffer].handle, offset);
+                            }
+                        }
+                    }
+                    if dispatch.iter().all(|&count| count != 0) {
+                        command_buf.dispatch(dispatch);
+                    }
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::DRAW_INDIRECT..pso::PipelineStage::COMPUTE_SHADER,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers[*name].barrier_from(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
+                },
+                raw::Job::Sync { ref commands, .. } => unsafe {
+                    for command in commands {
+                        match *command {
+                            raw::SyncCommand::PipelineBarrier {
+                                ref stages,
+                                ref barriers,
+                            } => {
+                                command_buf.pipeline_barrier(
+                                    stages.clone(),
+                                    memory::Dependencies::empty(),
+                                    barriers
+                                        .iter()
+                                        .map(|barrier| resources.barrier(barrier, &queue_families)),
+                                );
+                            }
+                            raw::SyncCommand::SetEvent { ref event, stages } => {
+                                let event = resources
+                                    .events
+                                    .get(event)
+                                    .expect(&format!("Missing event: {}", event));
+                                command_buf.set_event(event, stages);
+                            }
+                            raw::SyncCommand::ResetEvent { ref event, stages } => {
+                                let event = resources
+                                    .events
+                                    .get(event)
+                                    .expect(&format!("Missing event: {}", event));
+                                command_buf.reset_event(event, stages);
+                            }
+                            raw::SyncCommand::WaitEvents {
+                                ref events,
+                                ref stages,
+                                ref barriers,
+                            } => {
+                                command_buf.wait_events(
+                                    events.iter().map(|name| {
+                                        resources
+                                            .events
+                                            .get(name)
+                                            .expect(&format!("Missing event: {}", name))
+                                    }),
+                                    stages.clone(),
+                                    barriers
+                                        .iter()
+                                        .map(|barrier| resources.barrier(barrier, &queue_families)),
+                                );
+                            }
+                        }
+                    }
                 },
             }
 
@@ -1472,6 +2021,7 @@ impl<B: hal::Backend> Scene<B> {
                 name.clone(),
                 Job {
                     submission: command_buf,
+                    queue: job.queue().to_string(),
                 },
             );
         }
@@ -1480,11 +2030,13 @@ impl<B: hal::Backend> Scene<B> {
         Ok(Scene {
             resources,
             jobs,
+            submissions: raw.submissions.clone(),
             init_submit: init_cmd,
             finish_submit: finish_cmd,
             device,
-            queue_group,
-            command_pool: Some(command_pool),
+            queue_groups,
+            queues,
+            command_pools,
             query_pool: query_pool.ok(),
             upload_buffers,
             download_types: if download_types.is_empty() {
@@ -1492,6 +2044,7 @@ impl<B: hal::Backend> Scene<B> {
             } else {
                 download_types
             },
+            device_types,
             limits,
         })
     }
@@ -1515,7 +2068,107 @@ impl<B: hal::Backend> Scene<B> {
             .chain(submits)
             .chain(iter::once(&self.finish_submit));
         unsafe {
-            self.queue_group.queues[0].submit(command_buffers, iter::empty(), iter::empty(), None);
+            self.queue_groups[0].queues[0].submit(
+                command_buffers,
+                iter::empty(),
+                iter::empty(),
+                None,
+            );
+        }
+    }
+
+    /// Run the named submissions in order, each on its own queue.
+    ///
+    /// The initialization is complete before the first submission starts,
+    /// and all the queues are idle by the time this returns.
+    pub fn run_submissions<I>(&mut self, submission_names: I)
+    where
+        I: Iterator,
+        I::Item: AsRef<str>,
+    {
+        let mut init_fence = self
+            .device
+            .create_fence(false)
+            .expect("Can't create init-fence");
+        unsafe {
+            self.queue_groups[0].queues[0].submit(
+                iter::once(&self.init_submit),
+                iter::empty(),
+                iter::empty(),
+                Some(&mut init_fence),
+            );
+            self.device.wait_for_fence(&init_fence, !0).unwrap();
+            self.device.destroy_fence(init_fence);
+        }
+
+        for name in submission_names {
+            let submission = self
+                .submissions
+                .get(name.as_ref())
+                .expect(&format!("Missing submission: {}", name.as_ref()));
+            let (group, index) = *self
+                .queues
+                .get(&submission.queue)
+                .expect(&format!("Missing queue: {}", submission.queue));
+            let jobs = &self.jobs;
+            let queues = &self.queues;
+            let command_buffers = submission.jobs.iter().map(|job_name| {
+                let job = jobs
+                    .get(job_name)
+                    .expect(&format!("Missing job: {}", job_name));
+                assert_eq!(
+                    queues[&job.queue].0, group,
+                    "Job {} is recorded for a different queue family",
+                    job_name
+                );
+                &job.submission
+            });
+            let semaphores = &self.resources.semaphores;
+            let semaphore = |name: &String| {
+                semaphores
+                    .get(name)
+                    .expect(&format!("Missing semaphore: {}", name))
+            };
+            let wait_semaphores = submission
+                .wait_semaphores
+                .iter()
+                .map(|&(ref name, stages)| (semaphore(name), stages));
+            let signal_semaphores = submission.signal_semaphores.iter().map(&semaphore);
+            let mut fence = if submission.host_wait {
+                Some(
+                    self.device
+                        .create_fence(false)
+                        .expect("Can't create submission fence"),
+                )
+            } else {
+                None
+            };
+            unsafe {
+                self.queue_groups[group].queues[index].submit(
+                    command_buffers,
+                    wait_semaphores,
+                    signal_semaphores,
+                    fence.as_mut(),
+                );
+                if let Some(fence) = fence {
+                    self.device.wait_for_fence(&fence, !0).unwrap();
+                    self.device.destroy_fence(fence);
+                }
+            }
+        }
+
+        for group in self.queue_groups.iter_mut() {
+            for queue in group.queues.iter_mut() {
+                queue.wait_idle().unwrap();
+            }
+        }
+        unsafe {
+            self.queue_groups[0].queues[0].submit(
+                iter::once(&self.finish_submit),
+                iter::empty(),
+                iter::empty(),
+                None,
+            );
         }
     }
 
@@ -1557,7 +2210,7 @@ impl<B: hal::Backend> Scene<B> {
 
         let mut command_pool = unsafe {
             self.device.create_command_pool(
-                self.queue_group.family,
+                self.queue_groups[0].family,
                 hal::pool::CommandPoolCreateFlags::empty(),
             )
         }
@@ -1602,7 +2255,7 @@ impl<B: hal::Backend> Scene<B> {
             .create_fence(false)
             .expect("Can't create copy-fence");
         unsafe {
-            self.queue_group.queues[0].submit(
+            self.queue_groups[0].queues[0].submit(
                 iter::once(&cmd_buffer),
                 iter::empty(),
                 iter::empty(),
@@ -1642,7 +2295,6 @@ impl<B: hal::Backend> Scene<B> {
             height,
             depth,
         } = image.kind.extent();
-        assert_eq!(image.kind.num_samples(), 1);
 
         // TODO:
         let base_format = image.format.base_format();
@@ -1679,9 +2331,48 @@ impl<B: hal::Backend> Scene<B> {
         }
         .unwrap();
 
+        // multisampled images are resolved into a temporary image first
+        let resolved = if image.kind.num_samples() > 1 {
+            let kind = match image.kind {
+                i::Kind::D2(width, height, layers, _) => i::Kind::D2(width, height, layers, 1),
+                other => panic!("Unexpected multisampled image kind: {:?}", other),
+            };
+            let mut resolved_image = unsafe {
+                self.device.create_image(
+                    kind,
+                    1,
+                    image.format,
+                    i::Tiling::Optimal,
+                    i::Usage::TRANSFER_SRC | i::Usage::TRANSFER_DST,
+                    hal::memory::SparseFlags::empty(),
+                    i::ViewCapabilities::empty(),
+                )
+            }
+            .unwrap();
+            let resolved_req = unsafe { self.device.get_image_requirements(&resolved_image) };
+            let resolved_type = *self
+                .device_types
+                .iter()
+                .find(|i| resolved_req.type_mask & (1 << i.0) != 0)
+                .unwrap();
+            let resolved_memory = unsafe {
+                self.device
+                    .allocate_memory(resolved_type, resolved_req.size)
+            }
+            .unwrap();
+            unsafe {
+                self.device
+                    .bind_image_memory(&resolved_memory, 0, &mut resolved_image)
+            }
+            .unwrap();
+            Some((resolved_image, resolved_memory))
+        } else {
+            None
+        };
+
         let mut command_pool = unsafe {
             self.device.create_command_pool(
-                self.queue_group.family,
+                self.queue_groups[0].family,
                 hal::pool::CommandPoolCreateFlags::empty(),
             )
         }
@@ -1707,6 +2398,57 @@ impl<B: hal::Backend> Scene<B> {
                 iter::once(pre_barrier),
             );
 
+            let source = match resolved {
+                Some((ref resolved_image, _)) => {
+                    let range = i::SubresourceRange {
+                        aspects: f::Aspects::COLOR,
+                        ..Default::default()
+                    };
+                    let layers = i::SubresourceLayers {
+                        aspects: f::Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    };
+                    cmd_buffer.pipeline_barrier(
+                        pso::PipelineStage::TOP_OF_PIPE..pso::PipelineStage::TRANSFER,
+                        memory::Dependencies::empty(),
+                        iter::once(memory::Barrier::Image {
+                            states: (i::Access::empty(), i::Layout::Undefined)
+                                ..(i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
+                            target: resolved_image,
+                            families: None,
+                            range: range.clone(),
+                        }),
+                    );
+                    cmd_buffer.resolve_image(
+                        &image.handle,
+                        i::Layout::TransferSrcOptimal,
+                        resolved_image,
+                        i::Layout::TransferDstOptimal,
+                        iter::once(c::ImageResolve {
+                            src_subresource: layers.clone(),
+                            src_offset: i::Offset::ZERO,
+                            dst_subresource: layers,
+                            dst_offset: i::Offset::ZERO,
+                            extent: image.kind.extent(),
+                        }),
+                    );
+                    cmd_buffer.pipeline_barrier(
+                        pso::PipelineStage::TRANSFER..pso::PipelineStage::TRANSFER,
+                        memory::Dependencies::empty(),
+                        iter::once(memory::Barrier::Image {
+                            states: (i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal)
+                                ..(i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal),
+                            target: resolved_image,
+                            families: None,
+                            range,
+                        }),
+                    );
+                    resolved_image
+                }
+                None => &image.handle,
+            };
+
             let copy = c::BufferImageCopy {
                 buffer_offset: 0,
                 buffer_width: (row_pitch as u32 * 8) / format_desc.bits as u32,
@@ -1724,7 +2466,7 @@ impl<B: hal::Backend> Scene<B> {
                 },
             };
             cmd_buffer.copy_image_to_buffer(
-                &image.handle,
+                source,
                 i::Layout::TransferSrcOptimal,
                 &down_buffer,
                 iter::once(copy),
@@ -1754,7 +2496,7 @@ impl<B: hal::Backend> Scene<B> {
             .create_fence(false)
             .expect("Can't create copy-fence");
         unsafe {
-            self.queue_group.queues[0].submit(
+            self.queue_groups[0].queues[0].submit(
                 iter::once(&cmd_buffer),
                 iter::empty(),
                 iter::empty(),
@@ -1763,6 +2505,10 @@ impl<B: hal::Backend> Scene<B> {
             self.device.wait_for_fence(&copy_fence, !0).unwrap();
             self.device.destroy_fence(copy_fence);
             self.device.destroy_command_pool(command_pool);
+            if let Some((resolved_image, resolved_memory)) = resolved {
+                self.device.destroy_image(resolved_image);
+                self.device.free_memory(resolved_memory);
+            }
         }
 
         let mapping = unsafe {
@@ -1781,18 +2527,26 @@ impl<B: hal::Backend> Scene<B> {
         }
     }
 
-    pub fn measure_time(&self) -> u32 {
-        let mut results = vec![0u32; 2];
-        if let Some(ref pool) = self.query_pool {
-            unsafe {
-                self.device.wait_idle().unwrap();
-                let raw_data = slice::from_raw_parts_mut(results.as_mut_ptr() as *mut u8, 4 * 2);
-                self.device
-                    .get_query_pool_results(pool, 0..2, raw_data, 4, query::ResultFlags::empty())
-                    .unwrap();
-            }
+    /// Returns the GPU time of the last run in nanoseconds,
+    /// or `None` if the timestamp queries are not supported.
+    pub fn measure_time(&self) -> Option<f64> {
+        let pool = self.query_pool.as_ref()?;
+        let mut results = [0u64; 2];
+        unsafe {
+            self.device.wait_idle().unwrap();
+            let raw_data = slice::from_raw_parts_mut(results.as_mut_ptr() as *mut u8, 8 * 2);
+            self.device
+                .get_query_pool_results(
+                    pool,
+                    0..2,
+                    raw_data,
+                    8,
+                    query::ResultFlags::BITS_64 | query::ResultFlags::WAIT,
+                )
+                .unwrap();
         }
-        results[1] - results[0]
+        let period = self.queue_groups[0].queues[0].timestamp_period();
+        Some(results[1].wrapping_sub(results[0]) as f64 * period as f64)
     }
 }
 
@@ -1804,9 +2558,10 @@ impl<B: hal::Backend> Drop for Scene<B> {
                 self.device.free_memory(memory);
             }
             //TODO: free those properly
-            let _ = &self.queue_group;
-            self.device
-                .destroy_command_pool(self.command_pool.take().unwrap());
+            let _ = &self.queue_groups;
+            for pool in self.command_pools.drain(..) {
+                self.device.destroy_command_pool(pool);
+            }
             if let Some(pool) = self.query_pool.take() {
                 self.device.destroy_query_pool(pool);
             }
@@ -6,14 +6,19 @@ extern crate log;
 #[macro_use]
 extern crate serde;
 
+pub mod capture;
 pub mod gpu;
 pub mod raw;
 
 #[derive(Debug, serde::Deserialize)]
-pub enum Feature {}
+pub enum Feature {
+    DrawIndirectCount,
+}
 
 impl Feature {
     pub fn into_hal(self) -> hal::Features {
-        match self {}
+        match self {
+            Feature::DrawIndirectCount => hal::Features::DRAW_INDIRECT_COUNT,
+        }
     }
 }
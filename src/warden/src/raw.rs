@@ -3,7 +3,7 @@ use std::ops::Range;
 
 use hal;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum ClearColor {
     Float([f32; 4]),
     Uint([u32; 4]),
@@ -20,7 +20,7 @@ impl ClearColor {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum ClearValue {
     Color(ClearColor),
     DepthStencil(hal::command::ClearDepthStencil),
@@ -37,10 +37,10 @@ impl ClearValue {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AttachmentRef(pub String, pub hal::pass::AttachmentLayout);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Subpass {
     pub colors: Vec<AttachmentRef>,
     pub depth_stencil: Option<AttachmentRef>,
@@ -52,14 +52,14 @@ pub struct Subpass {
     pub resolves: Vec<AttachmentRef>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SubpassDependency {
     pub passes: Range<String>,
     pub stages: Range<hal::pso::PipelineStage>,
     pub accesses: Range<hal::image::Access>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GraphicsShaderSet {
     pub vertex: String,
     #[serde(default)]
@@ -72,13 +72,33 @@ pub struct GraphicsShaderSet {
     pub fragment: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum SpecializationConstant {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+}
+
+impl SpecializationConstant {
+    pub fn to_bytes(&self) -> [u8; 4] {
+        match *self {
+            // booleans are 32-bit in SPIR-V
+            SpecializationConstant::Bool(value) => (value as u32).to_ne_bytes(),
+            SpecializationConstant::U32(value) => value.to_ne_bytes(),
+            SpecializationConstant::I32(value) => value.to_ne_bytes(),
+            SpecializationConstant::F32(value) => value.to_ne_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SubpassRef {
     pub parent: String,
     pub index: hal::pass::SubpassId,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum Resource {
     Buffer {
         size: usize,
@@ -96,6 +116,12 @@ pub enum Resource {
         #[serde(default)]
         data: String,
     },
+    BufferView {
+        buffer: String,
+        format: Option<hal::format::Format>,
+        #[serde(default)]
+        range: hal::buffer::SubRange,
+    },
     ImageView {
         image: String,
         kind: hal::image::ViewKind,
@@ -109,12 +135,14 @@ pub enum Resource {
     },
     RenderPass {
         attachments: HashMap<String, hal::pass::Attachment>,
-        subpasses: HashMap<String, Subpass>,
+        /// Subpasses in the order of execution.
+        subpasses: Vec<(String, Subpass)>,
         dependencies: Vec<SubpassDependency>,
     },
     Shader(String),
     DescriptorSetLayout {
         bindings: Vec<hal::pso::DescriptorSetLayoutBinding>,
+        /// Samplers consumed in order by the bindings that have `immutable_samplers` set.
         #[serde(default)]
         immutable_samplers: Vec<String>,
     },
@@ -125,7 +153,12 @@ pub enum Resource {
     DescriptorSet {
         pool: String,
         layout: String,
+        /// Descriptors for the layout bindings, in order.
+        #[serde(default)]
         data: Vec<DescriptorRange>,
+        /// Descriptors for explicit bindings and array elements.
+        #[serde(default)]
+        writes: Vec<DescriptorWrite>,
     },
     PipelineLayout {
         set_layouts: Vec<String>,
@@ -142,21 +175,29 @@ pub enum Resource {
         blender: hal::pso::BlendDesc,
         #[serde(default)]
         depth_stencil: hal::pso::DepthStencilDesc,
+        #[serde(default)]
+        multisampling: Option<hal::pso::Multisampling>,
         layout: String,
         subpass: SubpassRef,
+        #[serde(default)]
+        specialization: Vec<(u32, SpecializationConstant)>,
     },
     ComputePipeline {
         shader: String,
         layout: String,
+        #[serde(default)]
+        specialization: Vec<(u32, SpecializationConstant)>,
     },
     Framebuffer {
         pass: String,
         attachments: HashMap<String, hal::image::FramebufferAttachment>,
         extent: hal::image::Extent,
     },
+    Event,
+    Semaphore,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum TransferCommand {
     CopyBuffer {
         src: String,
@@ -197,18 +238,94 @@ pub enum TransferCommand {
     },
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Barrier {
+    AllBuffers(Range<hal::buffer::Access>),
+    AllImages(Range<hal::image::Access>),
+    Buffer {
+        buffer: String,
+        states: Range<hal::buffer::State>,
+        #[serde(default)]
+        range: hal::buffer::SubRange,
+        /// Queue names for an ownership transfer.
+        #[serde(default)]
+        families: Option<Range<String>>,
+    },
+    Image {
+        image: String,
+        states: Range<hal::image::State>,
+        range: hal::image::SubresourceRange,
+        /// Queue names for an ownership transfer.
+        #[serde(default)]
+        families: Option<Range<String>>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum SyncCommand {
+    PipelineBarrier {
+        stages: Range<hal::pso::PipelineStage>,
+        barriers: Vec<Barrier>,
+    },
+    SetEvent {
+        event: String,
+        stages: hal::pso::PipelineStage,
+    },
+    ResetEvent {
+        event: String,
+        stages: hal::pso::PipelineStage,
+    },
+    WaitEvents {
+        events: Vec<String>,
+        stages: Range<hal::pso::PipelineStage>,
+        #[serde(default)]
+        barriers: Vec<Barrier>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum DescriptorRange {
     Buffers(Vec<String>),
+    BufferRanges(Vec<(String, hal::buffer::SubRange)>),
+    TexelBuffers(Vec<String>),
     Images(Vec<(String, hal::image::Layout)>),
     Samplers(Vec<String>),
+    CombinedImageSamplers(Vec<(String, hal::image::Layout, String)>),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DescriptorWrite {
+    pub binding: hal::pso::DescriptorBinding,
+    #[serde(default)]
+    pub array_offset: hal::pso::DescriptorArrayIndex,
+    pub descriptors: DescriptorRange,
+}
+
+/// Returns the size of an image level in the data files, which store
+/// the levels in order, each as its layers and depth slices of tightly
+/// packed rows of blocks.
+pub fn image_level_data_size(
+    kind: hal::image::Kind,
+    format: hal::format::Format,
+    level: hal::image::Level,
+) -> usize {
+    let desc = format.surface_desc();
+    let (block_width, block_height) = (desc.dim.0 as usize, desc.dim.1 as usize);
+    let extent = kind.level_extent(level);
+    let width_blocks = (extent.width as usize + block_width - 1) / block_width;
+    let height_blocks = (extent.height as usize + block_height - 1) / block_height;
+    width_blocks
+        * height_blocks
+        * extent.depth as usize
+        * kind.num_layers() as usize
+        * (desc.bits as usize / 8)
 }
 
 fn default_instance_range() -> Range<hal::InstanceCount> {
     0..1
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum DrawCommand {
     BindIndexBuffer {
         buffer: String,
@@ -221,6 +338,8 @@ pub enum DrawCommand {
         layout: String,
         first: usize,
         sets: Vec<String>,
+        #[serde(default)]
+        dynamic_offsets: Vec<hal::command::DescriptorSetOffset>,
     },
     Draw {
         vertices: Range<hal::VertexCount>,
@@ -232,40 +351,191 @@ pub enum DrawCommand {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: hal::buffer::Stride,
+    },
+    DrawIndexedIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: hal::buffer::Stride,
+    },
+    DrawIndirectCount {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        count_buffer: String,
+        count_buffer_offset: hal::buffer::Offset,
+        max_draw_count: hal::DrawCount,
+        stride: hal::buffer::Stride,
+    },
+    DrawIndexedIndirectCount {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        count_buffer: String,
+        count_buffer_offset: hal::buffer::Offset,
+        max_draw_count: hal::DrawCount,
+        stride: hal::buffer::Stride,
+    },
+    PushConstants {
+        layout: String,
+        stages: hal::pso::ShaderStageFlags,
+        offset: u32,
+        data: Vec<u32>,
+    },
     SetViewports(Vec<hal::pso::Viewport>),
     SetScissors(Vec<hal::pso::Rect>),
+    SetStencilReference {
+        faces: hal::pso::Face,
+        value: hal::pso::StencilValue,
+    },
+    SetBlendConstants(hal::pso::ColorValue),
+    SetDepthBias(hal::pso::DepthBias),
 }
 
-#[derive(Debug, Deserialize)]
+impl DrawCommand {
+    /// Returns the names of the buffers this command reads its
+    /// draw parameters from.
+    pub fn indirect_buffers(&self) -> Vec<&String> {
+        match *self {
+            DrawCommand::DrawIndirect { ref buffer, .. }
+            | DrawCommand::DrawIndexedIndirect { ref buffer, .. } => vec![buffer],
+            DrawCommand::DrawIndirectCount {
+                ref buffer,
+                ref count_buffer,
+                ..
+            }
+            | DrawCommand::DrawIndexedIndirectCount {
+                ref buffer,
+                ref count_buffer,
+                ..
+            } => vec![buffer, count_buffer],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum DispatchCommand {
+    PushConstants {
+        offset: u32,
+        data: Vec<u32>,
+    },
+    Dispatch(hal::WorkGroupCount),
+    DispatchIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DrawPass {
     pub commands: Vec<DrawCommand>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RenderAttachmentInfo {
     pub image_view: String,
     pub clear_value: ClearValue,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum Job {
     Transfer {
+        #[serde(default)]
+        queue: String,
         commands: Vec<TransferCommand>,
     },
     Graphics {
+        #[serde(default)]
+        queue: String,
         framebuffer: String,
         attachments: HashMap<String, RenderAttachmentInfo>,
         pass: (String, HashMap<String, DrawPass>),
     },
     Compute {
+        #[serde(default)]
+        queue: String,
         pipeline: String,
         descriptor_sets: Vec<String>,
+        #[serde(default)]
+        dynamic_offsets: Vec<hal::command::DescriptorSetOffset>,
+        /// Commands recorded after binding the pipeline and the descriptor sets.
+        #[serde(default)]
+        commands: Vec<DispatchCommand>,
+        /// Final dispatch, skipped if any of the dimensions is zero.
+        #[serde(default)]
         dispatch: hal::WorkGroupCount,
     },
+    /// Explicit synchronization. The resources are expected to be
+    /// back in their stable states by the end of the job.
+    Sync {
+        #[serde(default)]
+        queue: String,
+        commands: Vec<SyncCommand>,
+    },
 }
 
-#[derive(Debug, Deserialize)]
+impl Job {
+    /// Returns the name of the queue this job is recorded for.
+    pub fn queue(&self) -> &str {
+        match *self {
+            Job::Transfer { ref queue, .. }
+            | Job::Graphics { ref queue, .. }
+            | Job::Compute { ref queue, .. }
+            | Job::Sync { ref queue, .. } => queue,
+        }
+    }
+
+    /// Returns the largest work group count dispatched directly by this job.
+    pub fn max_dispatch(&self) -> hal::WorkGroupCount {
+        let mut max = [0; 3];
+        if let Job::Compute {
+            ref commands,
+            dispatch,
+            ..
+        } = *self
+        {
+            let counts = commands
+                .iter()
+                .filter_map(|command| match *command {
+                    DispatchCommand::Dispatch(count) => Some(count),
+                    _ => None,
+                })
+                .chain(Some(dispatch));
+            for count in counts {
+                for (m, c) in max.iter_mut().zip(count.iter()) {
+                    *m = (*m).max(*c);
+                }
+            }
+        }
+        max
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Submission {
+    #[serde(default)]
+    pub queue: String,
+    pub jobs: Vec<String>,
+    #[serde(default)]
+    pub wait_semaphores: Vec<(String, hal::pso::PipelineStage)>,
+    #[serde(default)]
+    pub signal_semaphores: Vec<String>,
+    /// Block on the host until the submission is complete.
+    #[serde(default)]
+    pub host_wait: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Scene {
     pub resources: HashMap<String, Resource>,
     pub jobs: HashMap<String, Job>,
+    /// Additional queues, with the empty name standing for the default one.
+    #[serde(default)]
+    pub queues: HashMap<String, hal::queue::QueueType>,
+    #[serde(default)]
+    pub submissions: HashMap<String, Submission>,
 }
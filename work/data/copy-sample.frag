@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInputMS u_Samples;
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    // the last sample is left out by the masked pipeline
+    o_Color = subpassLoad(u_Samples, 3);
+}
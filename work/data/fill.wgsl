@@ -0,0 +1,12 @@
+[[block]]
+struct Output {
+    data: [[stride(4)]] array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<storage> output: [[access(read_write)]] Output;
+
+[[stage(compute), workgroup_size(1)]]
+fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    output.data[global_id.x] = 2u;
+}